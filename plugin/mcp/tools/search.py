@@ -17,7 +17,19 @@ READONLY_ANNOTATIONS = {
 
 @mcp.tool(annotations=READONLY_ANNOTATIONS)
 async def search_history(
-    query: Annotated[str, Field(description="Full-text search query string")],
+    query: Annotated[
+        str,
+        Field(
+            description=(
+                "Search query. Words are ANDed; use \"quoted phrases\", -word or NOT "
+                "to exclude, OR and parentheses. Field terms filter without the "
+                "other parameters: project:NAME host:NAME role:user|assistant "
+                "branch:NAME session:ID tool:Bash file:src/main.rs command:cargo "
+                "after:-7d before:2026-10-01 (dates also take -12h, -2w, today, "
+                "yesterday). Example: project:quill tool:Edit file:migrations after:-7d"
+            )
+        ),
+    ],
     project: Annotated[
         str | None, Field(description="Filter by project working directory (cwd)")
     ] = None,
@@ -43,7 +55,8 @@ async def search_history(
     """Full-text search across conversation history, code changes (Edit/Write),
     commands run (Bash), and tool details (Read/Grep/Glob/Agent). Returns
     matching messages with all indexed fields. Search for file names, commands,
-    error messages, or any conversation content."""
+    error messages, or any conversation content. An invalid query returns an
+    "error" explaining what to fix."""
     client = await get_http_client()
     params: dict = {"q": query, "page_size": str(limit)}
     if project is not None:
@@ -59,6 +72,9 @@ async def search_history(
     if date_to is not None:
         params["date_to"] = date_to
     resp = await client.get("/api/v1/sessions/search", params=params)
+    if resp.status_code == 400:
+        # Query syntax errors say what to fix
+        return resp.json()
     resp.raise_for_status()
     return resp.json()
 
@@ -5,6 +5,7 @@ const fs = require("fs");
 const path = require("path");
 const https = require("https");
 const http = require("http");
+const { pinnedOptions } = require("./pinned-tls.cjs");
 
 function truncate(value, maxLen = 2048) {
   if (value === undefined || value === null) return null;
@@ -46,6 +47,7 @@ function main() {
     const mod = url.protocol === "https:" ? https : http;
 
     const req = mod.request(url, {
+      ...pinnedOptions(config, url),
       method: "POST",
       headers: {
         "Content-Type": "application/json",
@@ -0,0 +1,55 @@
+"use strict";
+
+const crypto = require("crypto");
+const net = require("net");
+const tls = require("tls");
+
+function normalizeFingerprint(fp) {
+  return String(fp || "").replace(/:/g, "").toLowerCase();
+}
+
+// Extra request options for the configured Quill server. When config.json has
+// a `fingerprint`, HTTPS connections skip CA validation and are used only if
+// the server certificate's SHA-256 matches it. The check runs before the
+// socket is handed to the request, so no headers or body are sent to an
+// unpinned server.
+function pinnedOptions(config, url) {
+  if (url.protocol !== "https:" || !config.fingerprint) return {};
+  const expected = normalizeFingerprint(config.fingerprint);
+
+  return {
+    createConnection(opts, callback) {
+      const host = opts.host || url.hostname;
+      const socket = tls.connect({
+        host,
+        port: opts.port,
+        servername: net.isIP(host) ? undefined : host,
+        rejectUnauthorized: false,
+        ALPNProtocols: ["http/1.1"],
+      });
+
+      let settled = false;
+      const finish = (err) => {
+        if (settled) return;
+        settled = true;
+        callback(err, err ? undefined : socket);
+      };
+
+      socket.once("secureConnect", () => {
+        const cert = socket.getPeerCertificate();
+        const actual = cert && cert.raw
+          ? crypto.createHash("sha256").update(cert.raw).digest("hex")
+          : "";
+        if (actual !== expected) {
+          socket.destroy();
+          finish(new Error("server certificate does not match the pinned fingerprint"));
+          return;
+        }
+        finish(null);
+      });
+      socket.once("error", finish);
+    },
+  };
+}
+
+module.exports = { pinnedOptions };
@@ -5,6 +5,7 @@ const fs = require("fs");
 const path = require("path");
 const https = require("https");
 const http = require("http");
+const { pinnedOptions } = require("./pinned-tls.cjs");
 
 async function main() {
   try {
@@ -31,6 +32,7 @@ async function main() {
     const mod = url.protocol === "https:" ? https : http;
 
     const req = mod.request(url, {
+      ...pinnedOptions(config, url),
       method: "POST",
       headers: {
         "Content-Type": "application/json",
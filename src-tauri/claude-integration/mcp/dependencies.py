@@ -1,13 +1,16 @@
 from __future__ import annotations
 
 import asyncio
+import hashlib
 import json
 import sqlite3
+import ssl
 import sys
 import threading
 from contextlib import asynccontextmanager
 from functools import lru_cache
 from pathlib import Path
+from urllib.parse import urlsplit
 
 import httpx
 
@@ -39,15 +42,38 @@ _http_client: httpx.AsyncClient | None = None
 _http_lock = asyncio.Lock()
 
 
+def _pinned_ssl_context(url: str, fingerprint: str) -> ssl.SSLContext:
+    """Trust only the server certificate whose SHA-256 matches `fingerprint`."""
+    parts = urlsplit(url)
+    pem = ssl.get_server_certificate((parts.hostname, parts.port or 443), timeout=10)
+    actual = hashlib.sha256(ssl.PEM_cert_to_DER_cert(pem)).hexdigest()
+    if actual != fingerprint.replace(":", "").lower():
+        raise RuntimeError(
+            "Quill server certificate does not match the pinned fingerprint. "
+            "If the server certificate changed, run /quill:setup again."
+        )
+    ctx = ssl.create_default_context(cadata=pem)
+    # The pinned certificate is the trust anchor; its names need not match the URL
+    ctx.check_hostname = False
+    ctx.verify_flags |= ssl.VERIFY_X509_PARTIAL_CHAIN
+    return ctx
+
+
 async def get_http_client() -> httpx.AsyncClient:
     global _http_client
     async with _http_lock:
         if _http_client is None:
             config = get_config()
+            verify: ssl.SSLContext | bool = True
+            if config["url"].startswith("https://") and config.get("fingerprint"):
+                verify = await asyncio.to_thread(
+                    _pinned_ssl_context, config["url"], config["fingerprint"]
+                )
             _http_client = httpx.AsyncClient(
                 base_url=config["url"],
                 headers={"Authorization": f"Bearer {config['secret']}"},
                 timeout=30.0,
+                verify=verify,
             )
     return _http_client
 
@@ -1,10 +1,10 @@
 from __future__ import annotations
 
-from typing import Annotated
+from typing import Annotated, Literal
 
 from pydantic import Field
 
-from dependencies import get_db
+from dependencies import get_db, get_http_client
 from server import mcp
 
 READONLY_ANNOTATIONS = {
@@ -68,6 +68,40 @@ def get_token_usage(
     return [dict(row) for row in rows]
 
 
+
+@mcp.tool(annotations=READONLY_ANNOTATIONS)
+async def get_usage_breakdown(
+    by: Annotated[
+        Literal["hosts", "projects", "sessions", "models", "costs"],
+        Field(description="What to group token usage and cost by"),
+    ] = "projects",
+    days: Annotated[
+        int, Field(description="Number of days to cover", ge=1, le=3650)
+    ] = 7,
+    hostname: Annotated[
+        str | None,
+        Field(description="Filter by host (sessions, models and costs only)"),
+    ] = None,
+    model: Annotated[
+        str | None,
+        Field(
+            description="Filter by model id substring, e.g. 'opus' (hosts, projects and sessions only)"
+        ),
+    ] = None,
+) -> list[dict]:
+    """Token and cost totals from the Quill server, grouped by host, project,
+    session, or model. These are the same numbers the Quill widget shows."""
+    client = await get_http_client()
+    params: dict = {"days": str(days)}
+    if hostname is not None:
+        params["hostname"] = hostname
+    if model is not None:
+        params["model"] = model
+    resp = await client.get(f"/api/v1/analytics/{by}", params=params)
+    resp.raise_for_status()
+    return resp.json()
+
+
 @mcp.tool(annotations=READONLY_ANNOTATIONS)
 def get_learned_rules(
     domain: Annotated[
@@ -1,5 +1,5 @@
 #!/usr/bin/env bash
-# Reports token usage from the last assistant turn to the Quill widget.
+# Reports token usage from the last assistant response to the Quill widget.
 # Runs as a Claude Code Stop hook via the quill app-bundled hooks.
 
 set -euo pipefail
@@ -20,11 +20,13 @@ with open(sys.argv[1]) as f:
 print(c.get('url', ''))
 print(c.get('hostname', ''))
 print(c.get('secret', ''))
+print(c.get('fingerprint', ''))
 " "$CONFIG_FILE" 2>/dev/null || true)
 
 USAGE_URL=$(echo "$CONFIG_VALUES" | sed -n '1p')
 HOSTNAME_ID=$(echo "$CONFIG_VALUES" | sed -n '2p')
 SECRET=$(echo "$CONFIG_VALUES" | sed -n '3p')
+FINGERPRINT=$(echo "$CONFIG_VALUES" | sed -n '4p')
 
 # If config exists but URL is empty/missing, skip
 if [ -z "$USAGE_URL" ]; then
@@ -60,10 +62,14 @@ if [ -z "$SESSION_ID" ] || [ -z "$TRANSCRIPT_PATH" ] || [ ! -f "$TRANSCRIPT_PATH
     exit 0
 fi
 
-# Find the last assistant message with usage data in the JSONL transcript
+# Collect the usage of every API call in the last response from the JSONL transcript
 USAGE_JSON=$(tac "$TRANSCRIPT_PATH" | python3 -c "
 import sys, json
 
+# Every API call since the user's last prompt; tool results don't end the
+# turn. Streamed content blocks of one call share a message id and the last
+# one carries the final usage, so the id doubles as the idempotency key.
+turns = {}
 for line in sys.stdin:
     line = line.strip()
     if not line:
@@ -73,43 +79,53 @@ for line in sys.stdin:
     except json.JSONDecodeError:
         continue
 
+    message = msg.get('message') or {}
+    if msg.get('type') == 'user' and not msg.get('isMeta'):
+        content = message.get('content')
+        if not (isinstance(content, list) and any(
+                isinstance(b, dict) and b.get('type') == 'tool_result' for b in content)):
+            break
+        continue
+
     if msg.get('type') != 'assistant':
         continue
 
-    usage = msg.get('message', {}).get('usage')
-    if usage is None:
+    usage = message.get('usage')
+    key = message.get('id') or msg.get('uuid')
+    if usage is None or not key or key in turns or message.get('model') == '<synthetic>':
         continue
 
-    result = {
+    turns[key] = {
         'input_tokens': usage.get('input_tokens', 0),
         'output_tokens': usage.get('output_tokens', 0),
         'cache_creation_input_tokens': usage.get('cache_creation_input_tokens', 0),
         'cache_read_input_tokens': usage.get('cache_read_input_tokens', 0),
+        'model': message.get('model') or '',
+        'idempotency_key': key,
     }
-    print(json.dumps(result))
-    break
+    if len(turns) >= 500:
+        break
+
+if turns:
+    print(json.dumps(list(reversed(list(turns.values())))))
 " 2>/dev/null || true)
 
 if [ -z "$USAGE_JSON" ]; then
     exit 0
 fi
 
-# Build the full payload
+# Build the batch payload
 PAYLOAD=$(python3 -c "
 import sys, json
-usage = json.loads(sys.argv[1])
-payload = {
-    'session_id': sys.argv[2],
-    'hostname': sys.argv[3],
-    'input_tokens': usage['input_tokens'],
-    'output_tokens': usage['output_tokens'],
-    'cache_creation_input_tokens': usage['cache_creation_input_tokens'],
-    'cache_read_input_tokens': usage['cache_read_input_tokens'],
-}
+turns = json.loads(sys.argv[1])
 cwd = sys.argv[4]
-if cwd:
-    payload['cwd'] = cwd
-print(json.dumps(payload))
+for turn in turns:
+    turn['session_id'] = sys.argv[2]
+    if cwd:
+        turn['cwd'] = cwd
+    if not turn.get('model'):
+        turn.pop('model', None)
+print(json.dumps({'hostname': sys.argv[3], 'turns': turns}))
 " "$USAGE_JSON" "$SESSION_ID" "$HOSTNAME_ID" "$CWD" 2>/dev/null || true)
 
 if [ -z "$PAYLOAD" ]; then
@@ -122,10 +138,30 @@ if [ -z "$SECRET" ]; then
     exit 0
 fi
 
+# With a pinned fingerprint, only talk to the server whose certificate matches it
+if [ -n "$FINGERPRINT" ] && [[ "$USAGE_URL" == https://* ]]; then
+    python3 -c "
+import hashlib, http.client, ssl, sys, urllib.parse
+url = urllib.parse.urlsplit(sys.argv[1])
+ctx = ssl.create_default_context()
+ctx.check_hostname = False
+ctx.verify_mode = ssl.CERT_NONE
+conn = http.client.HTTPSConnection(url.hostname, url.port or 443, timeout=2, context=ctx)
+conn.connect()
+actual = hashlib.sha256(conn.sock.getpeercert(binary_form=True)).hexdigest()
+if actual != sys.argv[2].replace(':', '').lower():
+    sys.exit('server certificate does not match the pinned fingerprint')
+conn.request('POST', url.path.rstrip('/') + '/api/v1/tokens/batch', body=sys.argv[4],
+             headers={'Content-Type': 'application/json', 'Authorization': 'Bearer ' + sys.argv[3]})
+conn.getresponse().read()
+" "$USAGE_URL" "$FINGERPRINT" "$SECRET" "$PAYLOAD" >/dev/null 2>&1 || true
+    exit 0
+fi
+
 curl -s -m 2 \
     -X POST \
     -H 'Content-Type: application/json' \
     -H "Authorization: Bearer $SECRET" \
     -d "$PAYLOAD" \
-    "${USAGE_URL}/api/v1/tokens" \
+    "${USAGE_URL}/api/v1/tokens/batch" \
     >/dev/null 2>&1 || true
@@ -6,6 +6,10 @@ const path = require("path");
 const os = require("os");
 const https = require("https");
 const http = require("http");
+const zlib = require("zlib");
+const { pinnedOptions } = require("./pinned-tls.cjs");
+
+const MAX_CONTENT_BYTES = 999000;
 
 function isLocal(urlStr) {
   return urlStr.includes("localhost") || urlStr.includes("127.0.0.1");
@@ -17,6 +21,7 @@ function postJSON(config, endpoint, payload) {
   const mod = url.protocol === "https:" ? https : http;
 
   const req = mod.request(url, {
+    ...pinnedOptions(config, url),
     method: "POST",
     headers: {
       "Content-Type": "application/json",
@@ -37,63 +42,144 @@ function postJSON(config, endpoint, payload) {
   req.end(body);
 }
 
-function extractMessages(lines) {
-  const messages = [];
-
-  for (const line of lines) {
-    let entry;
-    try {
-      entry = JSON.parse(line);
-    } catch (_) {
-      continue;
-    }
+function getJSON(config, endpoint) {
+  const url = new URL(`${config.url}${endpoint}`);
+  const mod = url.protocol === "https:" ? https : http;
 
-    if (!entry.type || entry.type === "system") continue;
-
-    // User messages
-    if (entry.type === "human" || entry.type === "user") {
-      const content = entry.message?.content;
-      if (!content) continue;
-
-      if (typeof content === "string") {
-        messages.push({ role: "user", text: content });
-      } else if (Array.isArray(content)) {
-        const textParts = content
-          .filter((b) => b.type === "text" && !b.isMeta)
-          .map((b) => b.text)
-          .filter(Boolean);
-        if (textParts.length > 0) {
-          messages.push({ role: "user", text: textParts.join("\n") });
+  return new Promise((resolve, reject) => {
+    const req = mod.request(url, {
+      ...pinnedOptions(config, url),
+      method: "GET",
+      headers: { Authorization: `Bearer ${config.secret}` },
+      timeout: 3000,
+    }, (res) => {
+      let data = "";
+      res.setEncoding("utf8");
+      res.on("data", (chunk) => { data += chunk; });
+      res.on("end", () => {
+        if (res.statusCode !== 200) {
+          reject(new Error(`server returned ${res.statusCode}`));
+          return;
         }
-      }
-      continue;
-    }
+        try {
+          resolve(JSON.parse(data));
+        } catch (err) {
+          reject(err);
+        }
+      });
+    });
+    req.on("timeout", () => req.destroy(new Error("timeout")));
+    req.on("error", reject);
+    req.end();
+  });
+}
 
-    // Assistant messages
-    if (entry.type === "assistant") {
-      const content = entry.message?.content;
-      if (!content || !Array.isArray(content)) continue;
-
-      const textParts = content
-        .filter((b) => {
-          if (b.type !== "text") return false;
-          if (b.isMeta) return false;
-          return true;
-        })
-        .map((b) => b.text)
-        .filter(Boolean);
-
-      if (textParts.length > 0) {
-        messages.push({ role: "assistant", text: textParts.join("\n") });
-      }
-      continue;
+// Streams NDJSON lines gzip-compressed. The server indexes them as they
+// arrive, so a hook killed mid-upload still keeps what got through.
+function postNDJSON(config, endpoint, lines) {
+  const url = new URL(`${config.url}${endpoint}`);
+  const mod = url.protocol === "https:" ? https : http;
+
+  const req = mod.request(url, {
+    ...pinnedOptions(config, url),
+    method: "POST",
+    headers: {
+      "Content-Type": "application/x-ndjson",
+      "Content-Encoding": "gzip",
+      Authorization: `Bearer ${config.secret}`,
+    },
+  }, (res) => {
+    if (res.statusCode >= 400 && process.env.QUILL_DEBUG) {
+      console.error(`session-sync: server returned ${res.statusCode}`);
     }
+    res.resume();
+  });
+
+  req.on("error", (err) => {
+    if (process.env.QUILL_DEBUG) console.error("session-sync: request error:", err.message);
+  });
+
+  const gzip = zlib.createGzip();
+  gzip.pipe(req);
+  for (const line of lines) gzip.write(JSON.stringify(line) + "\n");
+  gzip.end();
+}
+
+function textOf(content, role) {
+  if (typeof content === "string") return role === "user" ? content : "";
+  if (!Array.isArray(content)) return "";
+  return content
+    .filter((b) => b.type === "text" && !b.isMeta)
+    .map((b) => b.text)
+    .filter(Boolean)
+    .join("\n");
+}
+
+// Turns one transcript line into a message for the server, or null when the
+// line has no user or assistant text.
+function extractMessage(line) {
+  let entry;
+  try {
+    entry = JSON.parse(line);
+  } catch (_) {
+    return null;
+  }
+
+  if (!entry.uuid || !entry.type || entry.isMeta) return null;
+  const role = entry.type === "human" || entry.type === "user"
+    ? "user"
+    : entry.type === "assistant" ? "assistant" : null;
+  if (!role) return null;
+
+  let text = textOf(entry.message?.content, role);
+  if (!text) return null;
+  // The server refuses messages over 1 MB, which would stall the upload
+  if (Buffer.byteLength(text) > MAX_CONTENT_BYTES) {
+    text = Buffer.from(text).subarray(0, MAX_CONTENT_BYTES).toString("utf8");
   }
 
-  return messages;
+  return {
+    uuid: entry.uuid,
+    type: entry.type,
+    timestamp: entry.timestamp || "",
+    content: text,
+    role,
+    gitBranch: entry.gitBranch,
+  };
 }
 
-function main() {
+// Reads the transcript from `offset` up to its last complete line. Returns
+// the messages with the byte offset just past each one, and the offset the
+// read ends at.
+function readFrom(transcriptPath, offset) {
+  const fd = fs.openSync(transcriptPath, "r");
+  try {
+    const size = fs.fstatSync(fd).size;
+    // A shorter file was rewritten; start over (the server replaces by uuid)
+    if (size < offset) offset = 0;
+    const buf = Buffer.alloc(size - offset);
+    fs.readSync(fd, buf, 0, buf.length, offset);
+
+    const messages = [];
+    let gitBranch = "";
+    let start = 0;
+    let nl;
+    while ((nl = buf.indexOf(10, start)) !== -1) {
+      const msg = extractMessage(buf.toString("utf8", start, nl));
+      start = nl + 1;
+      if (!msg) continue;
+      if (msg.gitBranch) gitBranch = msg.gitBranch;
+      delete msg.gitBranch;
+      msg.offset = offset + start;
+      messages.push(msg);
+    }
+    return { messages, gitBranch, end: offset + start };
+  } finally {
+    fs.closeSync(fd);
+  }
+}
+
+async function main() {
   try {
     const raw = fs.readFileSync(0, "utf8");
     const input = JSON.parse(raw);
@@ -121,44 +207,27 @@ function main() {
       return;
     }
 
-    // REMOTE: read JSONL, extract new messages, send them
-    const trackingFile = path.join(os.tmpdir(), `.quill-sync-${sessionId}`);
+    // REMOTE: ask the server how far it has this session, send the rest
+    const host = os.hostname();
+    const query = new URLSearchParams({ host, session_id: sessionId });
+    const sync = await getJSON(config, `/api/v1/sessions/sync?${query}`);
 
-    let lastSent = 0;
+    let read;
     try {
-      lastSent = parseInt(fs.readFileSync(trackingFile, "utf8"), 10) || 0;
-    } catch (_) {
-      // No tracking file yet
-    }
-
-    let content;
-    try {
-      content = fs.readFileSync(transcriptPath, "utf8");
+      read = readFrom(transcriptPath, sync.offset || 0);
     } catch (_) {
       return;
     }
+    if (read.end === sync.offset) return;
 
-    const allLines = content.split("\n").filter((l) => l.trim().length > 0);
-    if (allLines.length <= lastSent) return;
-
-    const newLines = allLines.slice(lastSent);
-    const messages = extractMessages(newLines);
-
-    if (messages.length === 0) {
-      // Update tracking even if no extractable messages, to skip these lines next time
-      fs.writeFileSync(trackingFile, String(allLines.length));
-      return;
-    }
-
-    postJSON(config, "/api/v1/sessions/messages", {
-      host: os.hostname(),
+    const header = {
+      host,
       session_id: sessionId,
-      project: path.basename(input.cwd || ""),
-      git_branch: input.git_branch || null,
-      messages,
-    });
-
-    fs.writeFileSync(trackingFile, String(allLines.length));
+      project: path.basename(input.cwd || "") || "unknown",
+      git_branch: read.gitBranch,
+      offset: read.end,
+    };
+    postNDJSON(config, "/api/v1/sessions/messages/stream", [header, ...read.messages]);
   } catch (err) {
     if (process.env.QUILL_DEBUG) console.error("session-sync: error:", err.message);
   }
@@ -0,0 +1,346 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::config::http_client;
+use crate::events::EventBus;
+use crate::models::{AlertFiring, AlertRule, UsageBucket};
+use crate::storage::Storage;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Reset times this close to the previous firing's belong to the same window.
+/// The shortest bucket resets every five hours.
+const SAME_WINDOW: TimeDelta = TimeDelta::hours(1);
+
+// ── Rule evaluation ──
+
+/// Evaluates all enabled alert rules against freshly fetched buckets and
+/// dispatches every rule that crossed its threshold for the first time in the
+/// current reset window.
+pub async fn evaluate(storage: &'static Storage, buckets: &[UsageBucket], events: &EventBus) {
+    let rules = match tokio::task::block_in_place(|| storage.get_alert_rules()) {
+        Ok(r) => r,
+        Err(e) => {
+            log::warn!("Failed to load alert rules: {e}");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    for rule in rules.iter().filter(|r| r.enabled) {
+        let Some(bucket) = buckets.iter().find(|b| b.label == rule.bucket_label) else {
+            continue;
+        };
+        if !rule_matches(rule, bucket, now) {
+            continue;
+        }
+        let previous =
+            match tokio::task::block_in_place(|| storage.last_alert_firing(rule.id, &bucket.label))
+            {
+                Ok(p) => p,
+                Err(e) => {
+                    log::warn!("Failed to load last firing for '{}': {e}", rule.name);
+                    continue;
+                }
+            };
+
+        let mut firing = AlertFiring {
+            id: 0,
+            rule_id: rule.id,
+            rule_name: rule.name.clone(),
+            bucket_label: bucket.label.clone(),
+            utilization: bucket.utilization,
+            threshold: rule.threshold,
+            resets_at: bucket.resets_at.clone(),
+            window_key: window_key(bucket, previous.as_ref(), now),
+            message: alert_message(rule, bucket, now),
+            fired_at: now.to_rfc3339(),
+        };
+
+        match tokio::task::block_in_place(|| storage.record_alert_firing(&firing)) {
+            Ok(Some(id)) => firing.id = id,
+            Ok(None) => continue,
+            Err(e) => {
+                log::warn!("Failed to record alert firing for '{}': {e}", rule.name);
+                continue;
+            }
+        }
+
+        dispatch(rule, &firing, events).await;
+    }
+}
+
+fn rule_matches(rule: &AlertRule, bucket: &UsageBucket, now: DateTime<Utc>) -> bool {
+    if bucket.utilization < rule.threshold {
+        return false;
+    }
+    if rule.min_hours_to_reset.is_none() && rule.max_hours_to_reset.is_none() {
+        return true;
+    }
+    // Time-to-reset conditions can only be checked when the bucket has a reset time
+    let Some(hours) = hours_to_reset(bucket, now) else {
+        return false;
+    };
+    if rule.min_hours_to_reset.is_some_and(|min| hours <= min) {
+        return false;
+    }
+    if rule.max_hours_to_reset.is_some_and(|max| hours >= max) {
+        return false;
+    }
+    true
+}
+
+fn hours_to_reset(bucket: &UsageBucket, now: DateTime<Utc>) -> Option<f64> {
+    let resets_at = bucket.resets_at.as_deref()?;
+    let reset = DateTime::parse_from_rfc3339(resets_at).ok()?;
+    Some((reset.with_timezone(&Utc) - now).num_seconds() as f64 / 3600.0)
+}
+
+/// Identifies the reset window a firing belongs to: the bucket's reset time
+/// truncated to the hour. The API reports `resets_at` with sub-second jitter
+/// between polls, which can cross an hour boundary, so a reset within
+/// [`SAME_WINDOW`] of the previous firing's keeps that firing's key. Buckets
+/// without a reset time fall back to one window per UTC day.
+fn window_key(bucket: &UsageBucket, previous: Option<&AlertFiring>, now: DateTime<Utc>) -> String {
+    let parse = |r: &str| {
+        DateTime::parse_from_rfc3339(r)
+            .ok()
+            .map(|r| r.with_timezone(&Utc))
+    };
+    let Some(reset) = bucket.resets_at.as_deref().and_then(parse) else {
+        return format!("{}|{}", bucket.label, now.format("%Y-%m-%d"));
+    };
+    if let Some(previous) = previous
+        && let Some(last) = previous.resets_at.as_deref().and_then(parse)
+        && (reset - last).abs() < SAME_WINDOW
+    {
+        return previous.window_key.clone();
+    }
+    format!("{}|{}", bucket.label, reset.format("%Y-%m-%dT%H:00"))
+}
+
+fn alert_message(rule: &AlertRule, bucket: &UsageBucket, now: DateTime<Utc>) -> String {
+    let mut msg = format!(
+        "{} usage is at {:.0}% (threshold {:.0}%)",
+        bucket.label, bucket.utilization, rule.threshold
+    );
+    if let Some(hours) = hours_to_reset(bucket, now) {
+        if hours >= 1.0 {
+            msg.push_str(&format!(" with {hours:.1}h until reset"));
+        } else {
+            msg.push_str(&format!(
+                " with {}m until reset",
+                (hours * 60.0).max(0.0) as i64
+            ));
+        }
+    }
+    msg
+}
+
+// ── Sinks ──
+
+async fn dispatch(rule: &AlertRule, firing: &AlertFiring, events: &EventBus) {
+    events.emit("usage-alert", firing);
+
+    if rule.notify_desktop {
+        events.notify(&format!("Quill: {}", rule.name), &firing.message);
+    }
+
+    if let Some(url) = rule.webhook_url.as_deref().filter(|u| !u.is_empty())
+        && let Err(e) = send_webhook(url, firing).await
+    {
+        log::warn!("Alert webhook for '{}' failed: {e}", rule.name);
+    }
+}
+
+/// POSTs the firing as JSON to a webhook endpoint.
+pub async fn send_webhook(url: &str, firing: &AlertFiring) -> Result<(), String> {
+    let resp = http_client()
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(firing)
+        .send()
+        .await
+        .map_err(|e| format!("Webhook request failed: {e}"))?;
+    if !resp.status().is_success() {
+        return Err(format!("Webhook returned HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AlertRulePayload;
+
+    /// Labels as `fetcher` reports them
+    const FIVE_HOURS: &str = "5 hours";
+    const SEVEN_DAYS: &str = "7 days";
+    const OPUS: &str = "Opus";
+
+    fn bucket(label: &str, utilization: f64, resets_at: &str) -> UsageBucket {
+        UsageBucket {
+            label: label.to_string(),
+            utilization,
+            resets_at: Some(resets_at.to_string()),
+        }
+    }
+
+    fn fired(resets_at: &str, window_key: &str) -> AlertFiring {
+        AlertFiring {
+            id: 1,
+            rule_id: 1,
+            rule_name: "high".to_string(),
+            bucket_label: FIVE_HOURS.to_string(),
+            utilization: 90.0,
+            threshold: 80.0,
+            resets_at: Some(resets_at.to_string()),
+            window_key: window_key.to_string(),
+            message: String::new(),
+            fired_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn jitter_around_half_past_keeps_the_window() {
+        let now = Utc::now();
+        let before = window_key(
+            &bucket(FIVE_HOURS, 90.0, "2099-01-01T04:29:59.8+00:00"),
+            None,
+            now,
+        );
+        let after = window_key(
+            &bucket(FIVE_HOURS, 90.0, "2099-01-01T04:30:00.2+00:00"),
+            None,
+            now,
+        );
+        assert_eq!(before, "5 hours|2099-01-01T04:00");
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn jitter_across_the_hour_keeps_the_previous_window() {
+        let previous = fired("2099-01-01T04:59:59.9+00:00", "5 hours|2099-01-01T04:00");
+        let key = window_key(
+            &bucket(FIVE_HOURS, 90.0, "2099-01-01T05:00:00.1+00:00"),
+            Some(&previous),
+            Utc::now(),
+        );
+        assert_eq!(key, "5 hours|2099-01-01T04:00");
+    }
+
+    #[test]
+    fn next_reset_starts_a_new_window() {
+        let previous = fired("2099-01-01T05:00:00+00:00", "5 hours|2099-01-01T05:00");
+        let key = window_key(
+            &bucket(FIVE_HOURS, 90.0, "2099-01-01T10:00:00.3+00:00"),
+            Some(&previous),
+            Utc::now(),
+        );
+        assert_eq!(key, "5 hours|2099-01-01T10:00");
+    }
+
+    #[test]
+    fn opus_rule_needs_days_left_before_reset() {
+        let rule = AlertRule {
+            id: 1,
+            name: "Opus early".to_string(),
+            bucket_label: OPUS.to_string(),
+            threshold: 90.0,
+            min_hours_to_reset: Some(48.0),
+            max_hours_to_reset: None,
+            notify_desktop: true,
+            webhook_url: None,
+            enabled: true,
+            created_at: String::new(),
+        };
+        let now = DateTime::parse_from_rfc3339("2099-01-01T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(rule_matches(
+            &rule,
+            &bucket(OPUS, 93.0, "2099-01-04T00:00:00+00:00"),
+            now
+        ));
+        assert!(!rule_matches(
+            &rule,
+            &bucket(OPUS, 93.0, "2099-01-02T00:00:00+00:00"),
+            now
+        ));
+        assert!(!rule_matches(
+            &rule,
+            &bucket(OPUS, 85.0, "2099-01-04T00:00:00+00:00"),
+            now
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn webhook_fires_once_per_window() {
+        let path = std::env::temp_dir().join(format!("quill-alerts-{}.db", std::process::id()));
+        let storage: &'static Storage = Box::leak(Box::new(Storage::open(&path).unwrap()));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+        let hook = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send(body);
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, hook).await });
+
+        let rule_id = storage
+            .create_alert_rule(&AlertRulePayload {
+                name: "5 hours high".to_string(),
+                bucket_label: FIVE_HOURS.to_string(),
+                threshold: 80.0,
+                min_hours_to_reset: None,
+                max_hours_to_reset: None,
+                notify_desktop: false,
+                webhook_url: Some(format!("http://{addr}/hook")),
+                enabled: true,
+            })
+            .unwrap();
+        let events = EventBus::new();
+
+        evaluate(
+            storage,
+            &[
+                bucket(FIVE_HOURS, 92.0, "2099-01-01T04:29:59.8+00:00"),
+                bucket(SEVEN_DAYS, 95.0, "2099-01-05T00:00:00+00:00"),
+            ],
+            &events,
+        )
+        .await;
+        let body = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("webhook called")
+            .unwrap();
+        assert_eq!(body["rule_id"], rule_id);
+        assert_eq!(body["rule_name"], "5 hours high");
+        assert_eq!(body["bucket_label"], "5 hours");
+        assert_eq!(body["utilization"], 92.0);
+        assert_eq!(body["threshold"], 80.0);
+        assert_eq!(body["resets_at"], "2099-01-01T04:29:59.8+00:00");
+        assert_eq!(body["window_key"], "5 hours|2099-01-01T04:00");
+
+        // The next poll reports the same window with jitter; evaluate only
+        // returns once any webhook has been answered
+        evaluate(
+            storage,
+            &[bucket(FIVE_HOURS, 95.0, "2099-01-01T04:30:00.2+00:00")],
+            &events,
+        )
+        .await;
+        assert!(rx.try_recv().is_err(), "second evaluation fired again");
+        assert_eq!(storage.get_alert_firings(10).unwrap().len(), 1);
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+#[cfg(feature = "gui")]
+use std::io::Write;
+
+#[cfg(feature = "gui")]
+use serde::Serialize;
+#[cfg(feature = "gui")]
+use serde_json::Value;
+
+use crate::models::AuditEntry;
+#[cfg(feature = "gui")]
+use crate::models::AuditQuery;
+use crate::storage::Storage;
+
+// ── Audit log ──
+//
+// Every authenticated API request and every destructive app command is
+// appended to `audit_log`, so when data disappears from a dashboard there is
+// a record of who or what removed it. The table rejects updates, and entries
+// younger than a week can't be deleted; older ones are pruned hourly after
+// `audit.retention_days`.
+
+pub const RETENTION_SETTING: &str = "audit.retention_days";
+const DEFAULT_RETENTION_DAYS: i64 = 90;
+/// Matches the `audit_log_min_retention` trigger.
+const MIN_RETENTION_DAYS: i64 = 7;
+
+pub fn retention_days(storage: &Storage) -> i64 {
+    storage
+        .get_setting(RETENTION_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+        .max(MIN_RETENTION_DAYS)
+}
+
+pub fn set_retention_days(storage: &Storage, days: i64) -> Result<(), String> {
+    if days < MIN_RETENTION_DAYS {
+        return Err(format!(
+            "audit retention must be at least {MIN_RETENTION_DAYS} days"
+        ));
+    }
+    storage.set_setting(RETENTION_SETTING, &days.to_string())
+}
+
+pub fn prune(storage: &Storage) -> Result<usize, String> {
+    storage.prune_audit_log(retention_days(storage))
+}
+
+/// Appends an entry, stamping the current time. Failures are logged rather
+/// than returned so auditing never blocks the action itself.
+pub fn record(storage: &Storage, mut entry: AuditEntry) {
+    entry.timestamp = chrono::Utc::now().to_rfc3339();
+    if let Err(e) = storage.append_audit_entry(&entry) {
+        log::error!("Failed to record audit entry for {}: {e}", entry.action);
+    }
+}
+
+/// Records a destructive command from the app with its arguments and outcome.
+#[cfg(feature = "gui")]
+pub fn record_command<T: Serialize>(
+    storage: &Storage,
+    command: &str,
+    args: Value,
+    result: &Result<T, String>,
+) {
+    let (outcome, error) = match result {
+        Ok(value) => (serde_json::to_value(value).unwrap_or(Value::Null), None),
+        Err(e) => (Value::Null, Some(e.clone())),
+    };
+    let detail = serde_json::json!({"args": args, "result": outcome});
+    record(
+        storage,
+        AuditEntry {
+            id: 0,
+            timestamp: String::new(),
+            kind: "command".to_string(),
+            action: command.to_string(),
+            actor: "app".to_string(),
+            token_id: None,
+            hostname: None,
+            client_addr: None,
+            status: None,
+            bytes: None,
+            detail: Some(detail.to_string()),
+            error,
+        },
+    );
+}
+
+/// Writes matching entries to `path` as JSON Lines, oldest first. Returns
+/// the number written.
+#[cfg(feature = "gui")]
+pub fn export(storage: &Storage, query: &AuditQuery, path: &str) -> Result<usize, String> {
+    let mut entries = storage.get_audit_log(query)?;
+    entries.reverse();
+
+    let file = std::fs::File::create(path).map_err(|e| format!("Failed to create {path}: {e}"))?;
+    let mut out = std::io::BufWriter::new(file);
+    for entry in &entries {
+        serde_json::to_writer(&mut out, entry).map_err(|e| format!("Serialize error: {e}"))?;
+        out.write_all(b"\n")
+            .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    }
+    out.flush()
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    Ok(entries.len())
+}
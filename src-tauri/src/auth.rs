@@ -1,15 +1,19 @@
 use rand::RngCore;
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
 
-fn secret_path() -> Result<PathBuf, String> {
+use crate::models::IssuedApiToken;
+use crate::storage::Storage;
+
+pub(crate) fn secret_path() -> Result<PathBuf, String> {
     let data_dir = dirs::data_local_dir()
         .ok_or_else(|| "cannot determine local data directory".to_string())?;
     Ok(data_dir.join("com.quilltoolkit.app").join("auth_secret"))
 }
 
-fn create_secret_file(path: &std::path::Path) -> std::io::Result<fs::File> {
+pub(crate) fn create_secret_file(path: &std::path::Path) -> std::io::Result<fs::File> {
     let mut opts = fs::OpenOptions::new();
     opts.write(true).create(true).truncate(true);
 
@@ -22,6 +26,14 @@ fn create_secret_file(path: &std::path::Path) -> std::io::Result<fs::File> {
     opts.open(path)
 }
 
+/// Reads the existing auth secret without generating one.
+pub fn load_secret() -> Result<String, String> {
+    let path = secret_path()?;
+    let secret =
+        fs::read_to_string(&path).map_err(|e| format!("failed to read auth secret: {e}"))?;
+    Ok(secret.trim().to_string())
+}
+
 pub fn load_or_create_secret() -> Result<String, String> {
     let path = secret_path()?;
 
@@ -52,3 +64,124 @@ pub fn load_or_create_secret() -> Result<String, String> {
     log::info!("Generated new auth secret at {}", path.display());
     Ok(secret)
 }
+
+// ── Per-host API tokens ──
+
+/// Scope that grants a token full access, like the shared secret.
+pub const ALL_SCOPES: &str = "*";
+
+/// Scopes a per-host API token can be granted, besides [`ALL_SCOPES`]. Every
+/// token needs at least one.
+pub const SCOPES: &[&str] = &[
+    "tokens:write",
+    "usage:read",
+    "learning:read",
+    "learning:write",
+    "sessions:write",
+    "search:read",
+    "events:read",
+    "metrics:read",
+    RELAY_SCOPE,
+];
+
+/// Lets a token without a host binding report for any host, as a relay
+/// forwarding its clients' data must.
+pub const RELAY_SCOPE: &str = "relay:forward";
+
+/// Scopes that submit data on behalf of a host, so a token holding one needs
+/// a host binding or [`RELAY_SCOPE`].
+const HOST_SCOPES: &[&str] = &["tokens:write", "learning:write", "sessions:write"];
+
+/// Whether `scopes` let a token report for hosts other than its own.
+pub fn may_forward(scopes: &[String]) -> bool {
+    scopes.iter().any(|s| s == RELAY_SCOPE || s == ALL_SCOPES)
+}
+
+/// Prefix that distinguishes API tokens from the hex shared secret.
+const API_TOKEN_PREFIX: &str = "qt_";
+
+/// Characters of the plaintext token kept for display, e.g. `qt_1a2b3c4d`.
+const API_TOKEN_DISPLAY_LEN: usize = 11;
+
+/// Generates a new API token and returns `(plaintext, hash)`. Only the hash
+/// is persisted; the plaintext is shown to the user once.
+fn generate_api_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token = format!("{API_TOKEN_PREFIX}{}", hex::encode(bytes));
+    let hash = hash_api_token(&token);
+    (token, hash)
+}
+
+/// SHA-256 of the plaintext token. Tokens carry 256 bits of randomness, so an
+/// unsalted fast hash is sufficient.
+pub fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn validate_scopes(scopes: &[String]) -> Result<(), String> {
+    if scopes.is_empty() {
+        return Err(format!(
+            "A token needs at least one scope (use '{ALL_SCOPES}' for full access)"
+        ));
+    }
+    match scopes
+        .iter()
+        .find(|s| *s != ALL_SCOPES && !SCOPES.contains(&s.as_str()))
+    {
+        Some(unknown) => Err(format!(
+            "Unknown scope '{unknown}' (expected '{ALL_SCOPES}' or one of: {})",
+            SCOPES.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Creates a token for `name`, optionally bound to `hostname` so reports can
+/// only be filed under that host.
+pub fn issue_api_token(
+    storage: &Storage,
+    name: &str,
+    hostname: Option<&str>,
+    scopes: &[String],
+) -> Result<IssuedApiToken, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Token name cannot be empty".to_string());
+    }
+    let hostname = hostname.map(str::trim).filter(|h| !h.is_empty());
+    validate_scopes(scopes)?;
+    if hostname.is_none()
+        && !may_forward(scopes)
+        && let Some(scope) = scopes.iter().find(|s| HOST_SCOPES.contains(&s.as_str()))
+    {
+        return Err(format!(
+            "A token with '{scope}' must be bound to a host, or also granted '{RELAY_SCOPE}' \
+             to report for any host"
+        ));
+    }
+
+    let (token, hash) = generate_api_token();
+    let id = storage.create_api_token(
+        name,
+        hostname,
+        scopes,
+        &hash,
+        &token[..API_TOKEN_DISPLAY_LEN],
+    )?;
+    issued(storage, id, token)
+}
+
+/// Replaces a token's secret, keeping its name, host binding and scopes.
+pub fn rotate_api_token(storage: &Storage, id: i64) -> Result<IssuedApiToken, String> {
+    let (token, hash) = generate_api_token();
+    storage.rotate_api_token(id, &hash, &token[..API_TOKEN_DISPLAY_LEN])?;
+    issued(storage, id, token)
+}
+
+fn issued(storage: &Storage, id: i64, token: String) -> Result<IssuedApiToken, String> {
+    let info = storage
+        .get_api_token(id)?
+        .ok_or_else(|| format!("API token {id} not found"))?;
+    Ok(IssuedApiToken { token, info })
+}
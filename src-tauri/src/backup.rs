@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::BackupInfo;
+use crate::sessions::SessionIndex;
+use crate::storage::{self, Storage};
+
+// ── Backups ──
+//
+// Copies of everything a reimaged machine would otherwise lose: the database
+// (token history, learned rules, settings), the session search index and the
+// auth secret that remote hooks use. Each backup is a directory named after
+// its creation time under `backup.dir`, written to a `.partial` directory
+// first so an interrupted run never looks complete. The newest `backup.keep`
+// are kept. Restoring swaps the files in while Quill isn't running; the
+// replaced ones are moved to `pre-restore` in the data directory.
+
+pub const BACKUP_SETTINGS: &[&str] = &["backup.dir", "backup.interval_hours", "backup.keep"];
+const DEFAULT_INTERVAL_HOURS: i64 = 24;
+const DEFAULT_KEEP: usize = 7;
+
+const NAME_PREFIX: &str = "quill-";
+const PARTIAL_SUFFIX: &str = ".partial";
+const MANIFEST_FILE: &str = "manifest.json";
+const DB_FILE: &str = "usage.db";
+const INDEX_DIR: &str = "session-index";
+const SECRET_FILE: &str = "auth_secret";
+const PRE_RESTORE_DIR: &str = "pre-restore";
+/// Attempts at copying the session index before giving up on a merge that
+/// keeps changing it underneath.
+const INDEX_COPY_ATTEMPTS: usize = 3;
+
+fn data_dir() -> Result<PathBuf, String> {
+    let db = storage::db_path()?;
+    db.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| format!("{} has no parent directory", db.display()))
+}
+
+/// Where backups are written: `backup.dir`, or `backups` in the data
+/// directory.
+pub fn backup_dir(storage: &Storage) -> Result<PathBuf, String> {
+    match storage.get_setting("backup.dir")? {
+        Some(dir) if !dir.trim().is_empty() => Ok(PathBuf::from(dir.trim())),
+        _ => Ok(data_dir()?.join("backups")),
+    }
+}
+
+fn interval_hours(storage: &Storage) -> i64 {
+    storage
+        .get_setting("backup.interval_hours")
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_INTERVAL_HOURS)
+}
+
+fn keep(storage: &Storage) -> usize {
+    storage
+        .get_setting("backup.keep")
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_KEEP)
+        .max(1)
+}
+
+/// Checks a single `backup.*` value; an empty value restores the default.
+pub fn validate_setting(key: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    match key {
+        "backup.dir" => {
+            if !Path::new(value).is_absolute() {
+                return Err(format!(
+                    "backup.dir must be an absolute path, got '{value}'"
+                ));
+            }
+            Ok(())
+        }
+        "backup.interval_hours" => match value.parse::<i64>() {
+            Ok(n) if n >= 0 => Ok(()),
+            _ => Err(format!(
+                "backup.interval_hours must be a whole number of hours (0 disables), got '{value}'"
+            )),
+        },
+        "backup.keep" => match value.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(()),
+            _ => Err(format!("backup.keep must be at least 1, got '{value}'")),
+        },
+        _ => Err(format!("unknown backup setting '{key}'")),
+    }
+}
+
+/// Backups in the configured directory, newest first.
+pub fn list(storage: &Storage) -> Result<Vec<BackupInfo>, String> {
+    list_in(&backup_dir(storage)?)
+}
+
+fn list_in(dir: &Path) -> Result<Vec<BackupInfo>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {e}", dir.display())),
+    };
+    let mut backups: Vec<BackupInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with(NAME_PREFIX) && !name.ends_with(PARTIAL_SUFFIX)
+        })
+        .filter_map(|entry| match read_backup(&entry.path()) {
+            Ok(info) => Some(info),
+            Err(e) => {
+                log::warn!("Skipping {}: {e}", entry.path().display());
+                None
+            }
+        })
+        .collect();
+    // Names embed the creation time, so they sort chronologically
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+/// Reads a backup's manifest, taking its name, path and size from where it
+/// is now rather than where it was written.
+fn read_backup(path: &Path) -> Result<BackupInfo, String> {
+    let manifest = fs::read_to_string(path.join(MANIFEST_FILE))
+        .map_err(|e| format!("not a Quill backup (no readable {MANIFEST_FILE}): {e}"))?;
+    let mut info: BackupInfo =
+        serde_json::from_str(&manifest).map_err(|e| format!("invalid {MANIFEST_FILE}: {e}"))?;
+    info.name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    info.path = path.display().to_string();
+    info.size_bytes = dir_size(path);
+    Ok(info)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Writes a new backup and drops the oldest beyond `backup.keep`. Pass the
+/// open session index, if any, so it isn't committed to mid-copy.
+pub fn create(storage: &Storage, index: Option<&SessionIndex>) -> Result<BackupInfo, String> {
+    let dir = backup_dir(storage)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&dir, fs::Permissions::from_mode(0o700));
+    }
+
+    let now = chrono::Utc::now();
+    let name = format!("{NAME_PREFIX}{}", now.format("%Y%m%dT%H%M%SZ"));
+    let target = dir.join(&name);
+    if target.exists() {
+        return Err(format!("Backup {name} already exists"));
+    }
+    let staging = dir.join(format!("{name}{PARTIAL_SUFFIX}"));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to clear {}: {e}", staging.display()))?;
+    }
+    fs::create_dir(&staging).map_err(|e| format!("Failed to create {}: {e}", staging.display()))?;
+
+    if let Err(e) = write_backup(storage, index, &staging, now) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+    fs::rename(&staging, &target).map_err(|e| format!("Failed to finish backup {name}: {e}"))?;
+
+    for old in list_in(&dir)?.iter().skip(keep(storage)) {
+        if let Err(e) = fs::remove_dir_all(&old.path) {
+            log::warn!("Failed to remove old backup {}: {e}", old.name);
+        }
+    }
+
+    read_backup(&target)
+}
+
+fn write_backup(
+    storage: &Storage,
+    index: Option<&SessionIndex>,
+    staging: &Path,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), String> {
+    let db = staging.join(DB_FILE);
+    storage.backup_to(&db)?;
+    let schema_version = Storage::check_backup(&db)?;
+
+    let index_dir = crate::session_index_dir();
+    let index_files = if index_dir.is_dir() {
+        copy_index(&index_dir, &staging.join(INDEX_DIR), index)?
+    } else {
+        0
+    };
+
+    let secret = crate::auth::secret_path()?;
+    let has_secret = secret.is_file();
+    if has_secret {
+        copy_secret(&secret, &staging.join(SECRET_FILE))?;
+    }
+
+    let info = BackupInfo {
+        name: String::new(),
+        path: String::new(),
+        created_at: now.to_rfc3339(),
+        schema_version,
+        quill_version: env!("CARGO_PKG_VERSION").to_string(),
+        index_files,
+        has_secret,
+        size_bytes: 0,
+    };
+    let manifest =
+        serde_json::to_string_pretty(&info).map_err(|e| format!("Serialize error: {e}"))?;
+    fs::write(staging.join(MANIFEST_FILE), manifest)
+        .map_err(|e| format!("Failed to write {MANIFEST_FILE}: {e}"))
+}
+
+/// Copies the index directory, holding the writer lock so nothing is
+/// committed meanwhile. A background merge can still replace segments, so
+/// the copy is retried until `meta.json` is the same before and after.
+fn copy_index(src: &Path, dst: &Path, index: Option<&SessionIndex>) -> Result<usize, String> {
+    let _writer = index.map(|idx| idx.writer.lock());
+    let meta = || fs::read(src.join("meta.json")).ok();
+
+    let mut last_error = String::new();
+    for _ in 0..INDEX_COPY_ATTEMPTS {
+        if dst.exists() {
+            fs::remove_dir_all(dst)
+                .map_err(|e| format!("Failed to clear {}: {e}", dst.display()))?;
+        }
+        fs::create_dir_all(dst).map_err(|e| format!("Failed to create {}: {e}", dst.display()))?;
+
+        let before = meta();
+        match copy_dir_files(src, dst) {
+            Ok(count) if meta() == before => return Ok(count),
+            Ok(_) => last_error = "the index changed while it was copied".to_string(),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(format!("Failed to copy the session index: {last_error}"))
+}
+
+/// Copies the regular files directly inside `src`, skipping tantivy's lock
+/// files. Returns how many were copied.
+fn copy_dir_files(src: &Path, dst: &Path) -> Result<usize, String> {
+    let entries =
+        fs::read_dir(src).map_err(|e| format!("Failed to read {}: {e}", src.display()))?;
+    let mut count = 0;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {e}", src.display()))?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with(".tantivy-") || !entry.path().is_file() {
+            continue;
+        }
+        fs::copy(entry.path(), dst.join(&name))
+            .map_err(|e| format!("Failed to copy {}: {e}", entry.path().display()))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn copy_secret(src: &Path, dst: &Path) -> Result<(), String> {
+    use std::io::Write;
+    let secret = fs::read(src).map_err(|e| format!("Failed to read {}: {e}", src.display()))?;
+    crate::auth::create_secret_file(dst)
+        .and_then(|mut f| f.write_all(&secret))
+        .map_err(|e| format!("Failed to write {}: {e}", dst.display()))
+}
+
+/// Writes a backup if the newest is older than `backup.interval_hours`.
+/// Returns None when none was due or scheduling is off.
+pub fn run_if_due(
+    storage: &Storage,
+    index: Option<&SessionIndex>,
+) -> Result<Option<BackupInfo>, String> {
+    let hours = interval_hours(storage);
+    if hours <= 0 {
+        return Ok(None);
+    }
+    let recent = list(storage)?
+        .first()
+        .and_then(|b| chrono::DateTime::parse_from_rfc3339(&b.created_at).ok())
+        .is_some_and(|at| chrono::Utc::now() - at.to_utc() < chrono::TimeDelta::hours(hours));
+    if recent {
+        return Ok(None);
+    }
+    create(storage, index).map(Some)
+}
+
+/// Finds a backup by name in the configured directory, or by path.
+pub fn resolve(storage: &Storage, name_or_path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(name_or_path);
+    if path.is_absolute() || path.components().count() > 1 {
+        return Ok(path.to_path_buf());
+    }
+    Ok(backup_dir(storage)?.join(name_or_path))
+}
+
+/// Replaces the database, session index and auth secret with a backup's.
+/// Quill must not be running. The backup is validated and staged beside the
+/// live files first, so a bad backup leaves them untouched; the replaced
+/// files are kept in `pre-restore`.
+pub fn restore(source: &Path) -> Result<BackupInfo, String> {
+    let info = read_backup(source)?;
+    Storage::check_backup(&source.join(DB_FILE))?;
+
+    let data_dir = data_dir()?;
+    let live_db = storage::db_path()?;
+
+    let staging = data_dir.join(format!("restore{PARTIAL_SUFFIX}"));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to clear {}: {e}", staging.display()))?;
+    }
+    fs::create_dir(&staging).map_err(|e| format!("Failed to create {}: {e}", staging.display()))?;
+    if let Err(e) = stage_restore(source, &staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let aside = data_dir.join(PRE_RESTORE_DIR);
+    if aside.exists() {
+        fs::remove_dir_all(&aside)
+            .map_err(|e| format!("Failed to clear {}: {e}", aside.display()))?;
+    }
+    fs::create_dir(&aside).map_err(|e| format!("Failed to create {}: {e}", aside.display()))?;
+
+    // (staged name, live path). The database's -wal and -shm belong to the
+    // replaced file, so they are moved aside with nothing to take their
+    // place. An index or secret the backup lacks is left as it is.
+    let mut swaps: Vec<(Option<&str>, PathBuf)> = vec![
+        (Some(DB_FILE), live_db.clone()),
+        (None, PathBuf::from(format!("{}-wal", live_db.display()))),
+        (None, PathBuf::from(format!("{}-shm", live_db.display()))),
+    ];
+    if staging.join(INDEX_DIR).is_dir() {
+        swaps.push((Some(INDEX_DIR), crate::session_index_dir()));
+    }
+    if staging.join(SECRET_FILE).is_file() {
+        swaps.push((Some(SECRET_FILE), crate::auth::secret_path()?));
+    }
+
+    let mut moved: Vec<&Path> = Vec::new();
+    for (staged, live) in &swaps {
+        let live = live.as_path();
+        let result = move_aside(live, &aside).and_then(|was_there| {
+            if was_there {
+                moved.push(live);
+            }
+            match staged {
+                Some(name) => rename(&staging.join(name), live),
+                None => Ok(()),
+            }
+        });
+        if let Err(e) = result {
+            // Put back what was already replaced
+            for live in moved {
+                let _ = fs::remove_dir_all(live).or_else(|_| fs::remove_file(live));
+                let _ = rename(&aside.join(file_name(live)), live);
+            }
+            let _ = fs::remove_dir_all(&staging);
+            return Err(format!("Restore failed, previous data kept: {e}"));
+        }
+    }
+    let _ = fs::remove_dir_all(&staging);
+    Ok(info)
+}
+
+fn stage_restore(source: &Path, staging: &Path) -> Result<(), String> {
+    let db = staging.join(DB_FILE);
+    fs::copy(source.join(DB_FILE), &db).map_err(|e| format!("Failed to copy the database: {e}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&db, fs::Permissions::from_mode(0o600));
+    }
+    let index = source.join(INDEX_DIR);
+    if index.is_dir() {
+        fs::create_dir(staging.join(INDEX_DIR))
+            .map_err(|e| format!("Failed to stage the session index: {e}"))?;
+        copy_dir_files(&index, &staging.join(INDEX_DIR))?;
+    }
+    let secret = source.join(SECRET_FILE);
+    if secret.is_file() {
+        copy_secret(&secret, &staging.join(SECRET_FILE))?;
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Moves `live` into `aside`, returning whether there was anything to move.
+fn move_aside(live: &Path, aside: &Path) -> Result<bool, String> {
+    if !live.exists() {
+        return Ok(false);
+    }
+    rename(live, &aside.join(file_name(live)))?;
+    Ok(true)
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), String> {
+    fs::rename(from, to)
+        .map_err(|e| format!("Failed to move {} to {}: {e}", from.display(), to.display()))
+}
@@ -0,0 +1,3 @@
+fn main() {
+    quill_lib::daemon::run()
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeDelta, TimeZone, Utc};
+
+use crate::events::EventBus;
+use crate::models::{Budget, BudgetStatus, BudgetThresholdEvent};
+use crate::storage::Storage;
+
+/// Percent-of-limit marks that emit a `budget-threshold` event, once per period.
+pub const THRESHOLDS: &[i64] = &[50, 80, 100];
+
+#[cfg(feature = "gui")]
+pub const SCOPE_TYPES: &[&str] = &["project", "host"];
+#[cfg(feature = "gui")]
+pub const PERIODS: &[&str] = &["daily", "weekly", "monthly"];
+#[cfg(feature = "gui")]
+pub const METRICS: &[&str] = &["tokens", "cost"];
+
+// ── Period bounds ──
+
+/// Start and end of the budget period containing `now`. Periods follow the
+/// local calendar: days start at midnight, weeks on Monday, months on the 1st.
+fn period_bounds(period: &str, now: DateTime<Local>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = now.date_naive();
+    let (start, end) = match period {
+        "daily" => (today, today.succ_opt()?),
+        "weekly" => {
+            let start = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+            (start, start + TimeDelta::days(7))
+        }
+        "monthly" => {
+            let start = today.with_day(1)?;
+            let end = if start.month() == 12 {
+                start.with_year(start.year() + 1)?.with_month(1)?
+            } else {
+                start.with_month(start.month() + 1)?
+            };
+            (start, end)
+        }
+        _ => return None,
+    };
+
+    let to_utc = |d: chrono::NaiveDate| {
+        Local
+            .from_local_datetime(&d.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+    };
+    Some((to_utc(start)?, to_utc(end)?))
+}
+
+// ── Status ──
+
+fn budget_status(
+    storage: &Storage,
+    budget: Budget,
+    now: DateTime<Local>,
+) -> Result<BudgetStatus, String> {
+    let (start, end) = period_bounds(&budget.period, now)
+        .ok_or_else(|| format!("Unknown budget period '{}'", budget.period))?;
+    let period_start = start.to_rfc3339();
+    let (tokens, cost) = storage.budget_consumption(&budget, &period_start)?;
+    let consumed = if budget.metric == "cost" {
+        cost
+    } else {
+        tokens as f64
+    };
+
+    let percent = if budget.limit_value > 0.0 {
+        consumed / budget.limit_value * 100.0
+    } else {
+        0.0
+    };
+
+    // Linear burn-down: extrapolate the rate so far over the rest of the period
+    let now_utc = now.with_timezone(&Utc);
+    let elapsed = (now_utc - start).num_seconds().max(1) as f64;
+    let remaining = (end - now_utc).num_seconds().max(0) as f64;
+    let rate = consumed / elapsed;
+    let projected = consumed + rate * remaining;
+
+    let exhausted_at = if consumed >= budget.limit_value {
+        // Already over: report when the 100% mark was crossed, if it was seen
+        storage.budget_crossed_at(budget.id, &period_start, 100)?
+    } else if rate > 0.0 {
+        let secs = ((budget.limit_value - consumed) / rate) as i64;
+        let at = now_utc + TimeDelta::seconds(secs);
+        (at < end).then(|| at.to_rfc3339())
+    } else {
+        None
+    };
+
+    Ok(BudgetStatus {
+        projected_overrun: (projected - budget.limit_value).max(0.0),
+        budget,
+        period_start,
+        period_end: end.to_rfc3339(),
+        consumed,
+        percent,
+        projected,
+        exhausted_at,
+    })
+}
+
+/// Current-period consumption and projection for every budget.
+#[cfg(feature = "gui")]
+pub fn get_budget_statuses(storage: &Storage) -> Result<Vec<BudgetStatus>, String> {
+    let now = Local::now();
+    storage
+        .get_budgets()?
+        .into_iter()
+        .map(|b| budget_status(storage, b, now))
+        .collect()
+}
+
+// ── Threshold events ──
+
+/// Re-computes enabled budgets after new token data arrives and emits
+/// `budget-threshold` for every 50/80/100% mark crossed for the first time in
+/// the current period.
+pub fn evaluate(storage: &Storage, events: &EventBus) {
+    let budgets = match storage.get_budgets() {
+        Ok(b) => b,
+        Err(e) => {
+            log::warn!("Failed to load budgets: {e}");
+            return;
+        }
+    };
+
+    let now = Local::now();
+    for budget in budgets.into_iter().filter(|b| b.enabled) {
+        let status = match budget_status(storage, budget, now) {
+            Ok(s) => s,
+            Err(e) => {
+                log::warn!("Failed to compute budget status: {e}");
+                continue;
+            }
+        };
+
+        // Only the highest newly crossed mark is reported, but all are recorded
+        let mut crossed = None;
+        for &threshold in THRESHOLDS.iter().filter(|&&t| status.percent >= t as f64) {
+            match storage.record_budget_crossing(status.budget.id, &status.period_start, threshold)
+            {
+                Ok(true) => crossed = Some(threshold),
+                Ok(false) => {}
+                Err(e) => log::warn!("Failed to record budget crossing: {e}"),
+            }
+        }
+
+        if let Some(threshold) = crossed {
+            let event = BudgetThresholdEvent {
+                budget_id: status.budget.id,
+                name: status.budget.name.clone(),
+                threshold,
+                percent: status.percent,
+                consumed: status.consumed,
+                limit_value: status.budget.limit_value,
+                metric: status.budget.metric.clone(),
+                period_start: status.period_start.clone(),
+            };
+            events.emit("budget-threshold", &event);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+#[cfg(feature = "gui")]
 use tauri::Manager;
 
 // ── Path helpers ──
@@ -16,6 +17,7 @@ fn config_dir() -> PathBuf {
 /// Returns the platform-aware app data dir
 /// Linux: ~/.local/share/com.quilltoolkit.app/
 /// macOS: ~/Library/Application Support/com.quilltoolkit.app/
+#[cfg(feature = "gui")]
 fn app_data_dir() -> PathBuf {
     dirs::data_local_dir()
         .or_else(|| {
@@ -32,26 +34,31 @@ fn app_data_dir() -> PathBuf {
 }
 
 /// Returns ~/.config/quill/scripts/
+#[cfg(feature = "gui")]
 fn scripts_dir() -> PathBuf {
     config_dir().join("scripts")
 }
 
 /// Returns ~/.config/quill/mcp/
+#[cfg(feature = "gui")]
 fn mcp_dir() -> PathBuf {
     config_dir().join("mcp")
 }
 
 /// Returns ~/.config/quill/skills/
+#[cfg(feature = "gui")]
 fn skills_dir() -> PathBuf {
     config_dir().join("skills")
 }
 
 /// Returns ~/.config/quill/templates/
+#[cfg(feature = "gui")]
 fn templates_dir() -> PathBuf {
     config_dir().join("templates")
 }
 
 /// Returns ~/.claude/commands/
+#[cfg(feature = "gui")]
 fn commands_dir() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("/tmp"))
@@ -70,9 +77,21 @@ fn get_hostname() -> String {
         .unwrap_or_else(|| "local".to_string())
 }
 
+/// Hostname the local hooks report token usage under: the `hostname` from
+/// ~/.config/quill/config.json when set, else the short system hostname.
+pub fn reporting_hostname() -> String {
+    fs::read_to_string(config_dir().join("config.json"))
+        .ok()
+        .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+        .and_then(|c| c.get("hostname")?.as_str().map(|h| h.to_string()))
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(get_hostname)
+}
+
 // ── File deployment ──
 
 /// Recursively copy all files from `src` into `dst`, creating directories as needed.
+#[cfg(feature = "gui")]
 fn copy_dir_recursive(src: &std::path::Path, dst: &std::path::Path) -> Result<(), String> {
     if !src.exists() {
         return Ok(()); // Source subdirectory not present in bundle — skip
@@ -108,6 +127,7 @@ fn copy_dir_recursive(src: &std::path::Path, dst: &std::path::Path) -> Result<()
 }
 
 /// Remove and recreate a directory we own entirely, ensuring no stale files remain.
+#[cfg(feature = "gui")]
 fn clean_owned_dir(dir: &std::path::Path) -> Result<(), String> {
     if dir.exists() {
         fs::remove_dir_all(dir).map_err(|e| format!("Failed to clean {}: {e}", dir.display()))?;
@@ -118,6 +138,7 @@ fn clean_owned_dir(dir: &std::path::Path) -> Result<(), String> {
 
 /// Remove Quill-managed command files from ~/.claude/commands/ (shared directory).
 /// Uses an explicit list of all current AND previously shipped names to clean stale files.
+#[cfg(feature = "gui")]
 fn clean_quill_commands() -> Result<(), String> {
     let dir = commands_dir();
     if !dir.exists() {
@@ -144,6 +165,7 @@ fn clean_quill_commands() -> Result<(), String> {
 }
 
 /// Extract bundled resources from the app to managed directories.
+#[cfg(feature = "gui")]
 fn deploy_files(app: &tauri::AppHandle) -> Result<(), String> {
     let resource_dir = app
         .path()
@@ -201,6 +223,7 @@ fn deploy_files(app: &tauri::AppHandle) -> Result<(), String> {
 // ── Local config ──
 
 /// Create ~/.config/quill/config.json for localhost if a local widget is detected.
+#[cfg(feature = "gui")]
 fn create_local_config() -> Result<(), String> {
     let secret_path = app_data_dir().join("auth_secret");
     if !secret_path.exists() {
@@ -260,9 +283,11 @@ fn create_local_config() -> Result<(), String> {
 
 // ── CLAUDE.md management ──
 
+#[cfg(feature = "gui")]
 const SECTION_HEADING: &str = "### Session History Search (Quill MCP)";
 
 /// Update the Quill MCP section in ~/.claude/CLAUDE.md from the deployed template.
+#[cfg(feature = "gui")]
 fn update_claude_md() -> Result<(), String> {
     let template_path = templates_dir().join("claude-md-section.md");
     if !template_path.exists() {
@@ -334,6 +359,7 @@ fn update_claude_md() -> Result<(), String> {
 // ── MCP server registration ──
 
 /// Merge a `quill` MCP server entry into ~/.claude.json.
+#[cfg(feature = "gui")]
 fn register_mcp_server() -> Result<(), String> {
     let claude_json_path = dirs::home_dir()
         .ok_or("Cannot determine home directory")?
@@ -378,9 +404,11 @@ fn register_mcp_server() -> Result<(), String> {
 
 // ── Hook registration ──
 
+#[cfg(feature = "gui")]
 const HOOK_MARKER: &str = "quill-setup";
 
 /// Merge all Quill hooks into ~/.claude/settings.json.
+#[cfg(feature = "gui")]
 fn register_hooks() -> Result<(), String> {
     let settings_path = dirs::home_dir()
         .ok_or("Cannot determine home directory")?
@@ -536,6 +564,7 @@ fn register_hooks() -> Result<(), String> {
 // ── MCP verification ──
 
 /// Check that the MCP server can run.
+#[cfg(feature = "gui")]
 fn verify_mcp() -> Result<(), String> {
     // Check if uv is on PATH
     let uv_check = Command::new("uv")
@@ -575,6 +604,7 @@ fn verify_mcp() -> Result<(), String> {
 // ── Legacy cleanup ──
 
 /// Remove old manually-deployed hooks and update settings.json.
+#[cfg(feature = "gui")]
 fn cleanup_legacy_hooks() -> Result<(), String> {
     let home = dirs::home_dir().ok_or("Cannot determine home directory")?;
     let hooks_dir = home.join(".claude").join("hooks");
@@ -648,6 +678,7 @@ fn cleanup_legacy_hooks() -> Result<(), String> {
 
 /// Set up Claude Code integration locally when the Quill widget runs on the same machine.
 /// Called on widget startup.
+#[cfg(feature = "gui")]
 pub fn setup_local(app: &tauri::AppHandle) -> Result<(), String> {
     // Step 1: Deploy files — fatal if this fails
     deploy_files(app)?;
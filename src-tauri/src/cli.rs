@@ -0,0 +1,1288 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::auth;
+use crate::config::http_client;
+use crate::models::{AuditQuery, IssuedApiToken, SavedSearchPayload};
+use crate::saved_searches;
+use crate::server::{DEFAULT_PORT, LISTEN_SETTINGS, ListenConfig, TlsMode};
+use crate::sessions::{SearchFilters, SearchResults, SessionIndex};
+use crate::storage::Storage;
+
+// ── Command-line client ──
+//
+// `quill <command>` reads the same SQLite database as the app. Session search
+// goes through the running app's HTTP API, because the app holds the tantivy
+// writer lock, and only opens the index directly when no server is reachable.
+
+const COMMANDS: &[&str] = &[
+    "usage",
+    "tokens",
+    "search",
+    "saved",
+    "rules",
+    "sessions",
+    "api-tokens",
+    "server",
+    "relay",
+    "audit",
+    "trash",
+    "backup",
+    "redact",
+    "help",
+];
+
+const HELP: &str = "\
+Usage: quill <command> [options]
+
+Commands:
+  usage                           Current utilization and forecast per bucket
+  tokens [--by total|host|project|session|model] [--range 7d]
+         [--host H] [--project CWD] [--model M]
+                                  Token usage and estimated cost
+  search <query> [--project P] [--host H] [--role user|assistant]
+         [--branch B] [--session ID] [--sort relevance|recency] [--limit N]
+                                  Full-text search over session transcripts;
+                                  the query takes field:value terms such as
+                                  project:, tool:, file:, after:-7d, plus
+                                  \"phrases\", -term, OR and ( )
+  saved                           Saved searches and their watch status
+  saved add <name> <query> [--project P] [--host H] [--role R]
+         [--branch B] [--session ID] [--from DATE] [--to DATE]
+                                  Save a search with its filters
+  saved run <name> [--sort relevance|recency] [--limit N]
+                                  Run a saved search
+  saved watch|unwatch <name>      Notify in the app when new messages match
+  saved remove <name>             Delete a saved search
+  rules list [--state S]          Learned rules
+  sessions show <id>              Token totals and messages for one session
+  api-tokens list                 Per-host API tokens for the server
+  api-tokens issue <name> [--host H] --scopes tokens:write,...|*
+                                  Create a token (printed once)
+  api-tokens rotate <id>          Replace a token's secret
+  api-tokens revoke <id>          Disable a token
+  server                          Listener settings and TLS fingerprint
+  server set <bind|allow|tls|tls-cert|tls-key> <value>
+                                  Change a listener setting (applies on restart)
+  relay                           Upstream and outbox backlog for relay mode
+  relay set <upstream|token|fingerprint> <value>
+                                  Change a relay setting; empty upstream disables it
+  audit [--kind api|command] [--action A] [--actor NAME] [--host H]
+        [--range 7d] [--limit N]  Audited API requests and destructive commands;
+                                  --format csv|json exports every match
+  audit set retention <days>      Days to keep audit entries (default 90, min 7)
+  trash                           Deleted host, project and session data batches
+  trash restore|purge <batch-id>  Undo a delete, or drop the batch for good
+  trash set retention <days>      Days before batches are purged (default 30)
+  backup                          Backups of the database, session index and secret
+  backup now                      Write a backup immediately
+  backup restore <name|path>      Replace local data with a backup (quit Quill first)
+  backup set <dir|interval_hours|keep> <value>
+                                  Change the backup location or schedule
+  redact                          Custom redaction patterns
+  redact add|remove <regex>       Add or drop a pattern (applies on restart)
+  redact run                      Re-redact stored data and the session index
+                                  (quit Quill first)
+
+Options:
+  --format table|json|csv         Output format (default: table)
+
+Environment:
+  QUILL_URL      Server used for search (default: http://127.0.0.1:$QUILL_PORT)
+  QUILL_SECRET   Bearer secret for QUILL_URL (default: the local auth secret)
+";
+
+/// True when the process arguments name a CLI command, so `main` can run the
+/// client instead of launching the window.
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    args.first()
+        .is_some_and(|a| COMMANDS.contains(&a.as_str()) || a == "--help" || a == "-h")
+}
+
+/// Runs a CLI command and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    match dispatch(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("quill: {e}");
+            1
+        }
+    }
+}
+
+fn dispatch(args: &[String]) -> Result<(), String> {
+    let Some((command, rest)) = args.split_first() else {
+        print!("{HELP}");
+        return Ok(());
+    };
+    let mut args = Args::parse(rest)?;
+    let format = Format::parse(args.take("format").as_deref())?;
+
+    match command.as_str() {
+        "usage" => {
+            args.finish()?;
+            cmd_usage(format)
+        }
+        "tokens" => cmd_tokens(&mut args, format),
+        "search" => cmd_search(&mut args, format),
+        "saved" => match args.positional().as_slice() {
+            [] => {
+                args.finish()?;
+                cmd_saved_list(format)
+            }
+            [sub, name, query @ ..] if sub == "add" && !query.is_empty() => {
+                let (name, query) = (name.clone(), query.join(" "));
+                cmd_saved_add(&name, &query, &mut args)
+            }
+            [sub, name] if sub == "run" => {
+                let name = name.clone();
+                cmd_saved_run(&name, &mut args, format)
+            }
+            [sub, name] if sub == "watch" || sub == "unwatch" => {
+                let (watch, name) = (sub == "watch", name.clone());
+                args.finish()?;
+                cmd_saved_watch(&name, watch)
+            }
+            [sub, name] if sub == "remove" => {
+                let name = name.clone();
+                args.finish()?;
+                cmd_saved_remove(&name)
+            }
+            _ => Err(
+                "usage: quill saved [add <name> <query>|run <name>|watch <name>|unwatch <name>|remove <name>]"
+                    .to_string(),
+            ),
+        },
+        "rules" => match args.positional().as_slice() {
+            [sub] if sub == "list" => cmd_rules_list(&mut args, format),
+            _ => Err("usage: quill rules list [--state S]".to_string()),
+        },
+        "sessions" => match args.positional().as_slice() {
+            [sub, id] if sub == "show" => {
+                let id = id.clone();
+                args.finish()?;
+                cmd_sessions_show(&id, format)
+            }
+            _ => Err("usage: quill sessions show <id>".to_string()),
+        },
+        "api-tokens" => match args.positional().as_slice() {
+            [sub] if sub == "list" => {
+                args.finish()?;
+                cmd_api_tokens_list(format)
+            }
+            [sub, name] if sub == "issue" => {
+                let name = name.clone();
+                cmd_api_tokens_issue(&name, &mut args, format)
+            }
+            [sub, id] if sub == "rotate" || sub == "revoke" => {
+                let id: i64 = id.parse().map_err(|_| format!("invalid token id '{id}'"))?;
+                args.finish()?;
+                if sub == "rotate" {
+                    let storage = open_storage()?;
+                    print_issued(&auth::rotate_api_token(&storage, id)?, format)
+                } else {
+                    open_storage()?.revoke_api_token(id)?;
+                    println!("Revoked API token {id}");
+                    Ok(())
+                }
+            }
+            _ => {
+                Err("usage: quill api-tokens list|issue <name>|rotate <id>|revoke <id>".to_string())
+            }
+        },
+        "server" => match args.positional().as_slice() {
+            [] => {
+                args.finish()?;
+                cmd_server_show(format)
+            }
+            [sub, key, value] if sub == "set" => {
+                let (key, value) = (key.clone(), value.clone());
+                args.finish()?;
+                cmd_server_set(&key, &value)
+            }
+            _ => Err("usage: quill server [set <key> <value>]".to_string()),
+        },
+        "relay" => match args.positional().as_slice() {
+            [] => {
+                args.finish()?;
+                cmd_relay_show(format)
+            }
+            [sub, key, value] if sub == "set" => {
+                let (key, value) = (key.clone(), value.clone());
+                args.finish()?;
+                cmd_relay_set(&key, &value)
+            }
+            _ => Err("usage: quill relay [set <key> <value>]".to_string()),
+        },
+        "audit" => match args.positional().as_slice() {
+            [] => cmd_audit(&mut args, format),
+            [sub, key, value] if sub == "set" && key == "retention" => {
+                let value = value.clone();
+                args.finish()?;
+                cmd_audit_set_retention(&value)
+            }
+            _ => Err("usage: quill audit [set retention <days>]".to_string()),
+        },
+        "backup" => match args.positional().as_slice() {
+            [] => {
+                args.finish()?;
+                render(
+                    &crate::backup::list(&open_storage()?)?,
+                    BACKUP_COLUMNS,
+                    format,
+                )
+            }
+            [sub] if sub == "now" => {
+                args.finish()?;
+                let info = crate::backup::create(&open_storage()?, None)?;
+                render(&[info], BACKUP_COLUMNS, format)
+            }
+            [sub, source] if sub == "restore" => {
+                let source = source.clone();
+                args.finish()?;
+                cmd_backup_restore(&source, format)
+            }
+            [sub, key, value] if sub == "set" => {
+                let (key, value) = (key.clone(), value.clone());
+                args.finish()?;
+                cmd_backup_set(&key, &value)
+            }
+            _ => Err("usage: quill backup [now|restore <name|path>|set <key> <value>]".to_string()),
+        },
+        "redact" => match args.positional().as_slice() {
+            [] => {
+                args.finish()?;
+                cmd_redact_list(format)
+            }
+            [sub, pattern] if sub == "add" || sub == "remove" => {
+                let (sub, pattern) = (sub.clone(), pattern.clone());
+                args.finish()?;
+                cmd_redact_edit(&sub, &pattern)
+            }
+            [sub] if sub == "run" => {
+                args.finish()?;
+                cmd_redact_run(format)
+            }
+            _ => Err("usage: quill redact [add <regex>|remove <regex>|run]".to_string()),
+        },
+        "trash" => match args.positional().as_slice() {
+            [] => {
+                args.finish()?;
+                cmd_trash_list(format)
+            }
+            [sub, id] if sub == "restore" || sub == "purge" => {
+                let (sub, id) = (sub.clone(), id.clone());
+                args.finish()?;
+                cmd_trash_apply(&sub, &id, format)
+            }
+            [sub, key, value] if sub == "set" && key == "retention" => {
+                let value = value.clone();
+                args.finish()?;
+                cmd_trash_set_retention(&value)
+            }
+            _ => Err(
+                "usage: quill trash [restore <batch-id>|purge <batch-id>|set retention <days>]"
+                    .to_string(),
+            ),
+        },
+        _ => {
+            print!("{HELP}");
+            Ok(())
+        }
+    }
+}
+
+// ── Argument parsing ──
+
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    /// Splits `--key value` / `--key=value` options from positional arguments.
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(key) = arg.strip_prefix("--") else {
+                positional.push(arg.clone());
+                continue;
+            };
+            let (key, value) = match key.split_once('=') {
+                Some((k, v)) => (k.to_string(), v.to_string()),
+                None => {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| format!("option --{key} needs a value"))?;
+                    (key.to_string(), value.clone())
+                }
+            };
+            options.insert(key, value);
+        }
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    fn take(&mut self, key: &str) -> Option<String> {
+        self.options.remove(key)
+    }
+
+    fn positional(&self) -> Vec<String> {
+        self.positional.clone()
+    }
+
+    /// Rejects options the command did not consume.
+    fn finish(&self) -> Result<(), String> {
+        match self.options.keys().next() {
+            Some(key) => Err(format!("unknown option --{key}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a range like `7d`, `2w` or `24h` into whole days (at least 1).
+fn parse_range_days(range: &str) -> Result<i32, String> {
+    let invalid = || format!("invalid range '{range}' (expected e.g. 24h, 7d, 2w)");
+    let unit = range.chars().last().ok_or_else(invalid)?;
+    let n: i32 = range[..range.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let days = match unit {
+        'h' => (n + 23) / 24,
+        'd' => n,
+        'w' => n * 7,
+        _ => return Err(invalid()),
+    };
+    Ok(days.clamp(1, 365))
+}
+
+// ── Commands ──
+
+fn open_storage() -> Result<Storage, String> {
+    Storage::init()
+}
+
+fn cmd_usage(format: Format) -> Result<(), String> {
+    let storage = open_storage()?;
+    let forecasts = storage.get_usage_forecasts(None)?;
+    if forecasts.is_empty() {
+        return Err("no usage data recorded yet".to_string());
+    }
+    render(
+        &forecasts,
+        &[
+            ("BUCKET", "label"),
+            ("USED %", "current"),
+            ("%/HOUR", "rate_per_hour"),
+            ("RESETS", "resets_at"),
+            ("EXHAUSTED", "exhausted_at"),
+        ],
+        format,
+    )
+}
+
+fn cmd_tokens(args: &mut Args, format: Format) -> Result<(), String> {
+    let by = args.take("by").unwrap_or_else(|| "total".to_string());
+    let days = parse_range_days(&args.take("range").unwrap_or_else(|| "7d".to_string()))?;
+    let host = args.take("host");
+    let project = args.take("project");
+    let model = args.take("model");
+    args.finish()?;
+
+    let storage = open_storage()?;
+    let (host, project, model) = (host.as_deref(), project.as_deref(), model.as_deref());
+    match by.as_str() {
+        "total" => {
+            let stats = storage.get_token_stats(days, host, project, model)?;
+            render(
+                &[stats],
+                &[
+                    ("TOTAL", "total_tokens"),
+                    ("INPUT", "total_input"),
+                    ("OUTPUT", "total_output"),
+                    ("CACHE WRITE", "total_cache_creation"),
+                    ("CACHE READ", "total_cache_read"),
+                    ("TURNS", "turn_count"),
+                    ("COST USD", "cost_usd"),
+                ],
+                format,
+            )
+        }
+        "host" => {
+            let rows = storage.get_host_breakdown(days, model)?;
+            render(
+                &rows,
+                &[
+                    ("HOST", "hostname"),
+                    ("TOKENS", "total_tokens"),
+                    ("TURNS", "turn_count"),
+                    ("COST USD", "cost_usd"),
+                    ("LAST ACTIVE", "last_active"),
+                ],
+                format,
+            )
+        }
+        "project" => {
+            let mut rows = storage.get_project_breakdown(days, model)?;
+            if let Some(h) = host {
+                rows.retain(|r| r.hostname == h);
+            }
+            if let Some(p) = project {
+                rows.retain(|r| r.project.starts_with(p));
+            }
+            render(
+                &rows,
+                &[
+                    ("PROJECT", "project"),
+                    ("HOST", "hostname"),
+                    ("TOKENS", "total_tokens"),
+                    ("TURNS", "turn_count"),
+                    ("SESSIONS", "session_count"),
+                    ("COST USD", "cost_usd"),
+                    ("LAST ACTIVE", "last_active"),
+                ],
+                format,
+            )
+        }
+        "session" => {
+            let rows = storage.get_session_breakdown(days, host, model)?;
+            render(
+                &rows,
+                &[
+                    ("SESSION", "session_id"),
+                    ("HOST", "hostname"),
+                    ("PROJECT", "project"),
+                    ("TOKENS", "total_tokens"),
+                    ("TURNS", "turn_count"),
+                    ("COST USD", "cost_usd"),
+                    ("LAST ACTIVE", "last_active"),
+                ],
+                format,
+            )
+        }
+        "model" => {
+            let rows = storage.get_model_breakdown(days, host, project)?;
+            render(
+                &rows,
+                &[
+                    ("MODEL", "model"),
+                    ("TOKENS", "total_tokens"),
+                    ("TURNS", "turn_count"),
+                    ("SESSIONS", "session_count"),
+                    ("COST USD", "cost_usd"),
+                    ("LAST ACTIVE", "last_active"),
+                ],
+                format,
+            )
+        }
+        other => Err(format!(
+            "invalid --by '{other}' (expected total, host, project, session or model)"
+        )),
+    }
+}
+
+fn cmd_search(args: &mut Args, format: Format) -> Result<(), String> {
+    let query = args.positional().join(" ");
+    let filters = take_search_filters(args);
+    let sort = args.take("sort").unwrap_or_else(|| "relevance".to_string());
+    let limit = take_limit(args)?;
+    args.finish()?;
+
+    let results = search(&query, &filters, &sort, limit)?;
+    render(&results.hits, SEARCH_HIT_COLUMNS, format)
+}
+
+const SEARCH_HIT_COLUMNS: &[(&str, &str)] = &[
+    ("TIME", "timestamp"),
+    ("PROJECT", "project"),
+    ("ROLE", "role"),
+    ("SESSION", "session_id"),
+    ("TEXT", "content"),
+];
+
+fn take_search_filters(args: &mut Args) -> SearchFilters {
+    SearchFilters {
+        project: args.take("project"),
+        host: args.take("host"),
+        role: args.take("role"),
+        git_branch: args.take("branch"),
+        session_id: args.take("session"),
+        date_from: args.take("from"),
+        date_to: args.take("to"),
+    }
+}
+
+fn take_limit(args: &mut Args) -> Result<usize, String> {
+    match args.take("limit") {
+        Some(v) => v.parse().map_err(|_| format!("invalid --limit '{v}'")),
+        None => Ok(20),
+    }
+}
+
+fn cmd_saved_list(format: Format) -> Result<(), String> {
+    let searches = open_storage()?.list_saved_searches()?;
+    render(
+        &searches,
+        &[
+            ("ID", "id"),
+            ("NAME", "name"),
+            ("QUERY", "query"),
+            ("WATCH", "watch"),
+            ("MATCHES", "match_count"),
+            ("LAST MATCH", "last_matched_at"),
+        ],
+        format,
+    )
+}
+
+fn cmd_saved_add(name: &str, query: &str, args: &mut Args) -> Result<(), String> {
+    let filters = take_search_filters(args);
+    args.finish()?;
+
+    let payload = SavedSearchPayload {
+        name: name.to_string(),
+        query: query.to_string(),
+        filters,
+        watch: false,
+    };
+    let id = saved_searches::save(&open_storage()?, None, &payload)?;
+    println!("Saved search {id} '{name}'");
+    Ok(())
+}
+
+/// Runs the stored query and filters the same way `quill search` does.
+fn cmd_saved_run(name: &str, args: &mut Args, format: Format) -> Result<(), String> {
+    let sort = args.take("sort").unwrap_or_else(|| "relevance".to_string());
+    let limit = take_limit(args)?;
+    args.finish()?;
+
+    let saved = saved_searches::find(&open_storage()?, name)?;
+    let results = search(&saved.query, &saved.filters, &sort, limit)?;
+    render(&results.hits, SEARCH_HIT_COLUMNS, format)
+}
+
+/// Watches are read on every index update, so this applies without a restart.
+fn cmd_saved_watch(name: &str, watch: bool) -> Result<(), String> {
+    let storage = open_storage()?;
+    let saved = saved_searches::find(&storage, name)?;
+    let payload = SavedSearchPayload {
+        name: saved.name,
+        query: saved.query,
+        filters: saved.filters,
+        watch,
+    };
+    saved_searches::save(&storage, Some(saved.id), &payload)?;
+    if watch {
+        println!("Watching '{}' for new matches", payload.name);
+    } else {
+        println!("Stopped watching '{}'", payload.name);
+    }
+    Ok(())
+}
+
+fn cmd_saved_remove(name: &str) -> Result<(), String> {
+    let storage = open_storage()?;
+    let saved = saved_searches::find(&storage, name)?;
+    storage.delete_saved_search(saved.id)?;
+    println!("Removed saved search '{}'", saved.name);
+    Ok(())
+}
+
+fn cmd_rules_list(args: &mut Args, format: Format) -> Result<(), String> {
+    let state = args.take("state");
+    args.finish()?;
+
+    let storage = open_storage()?;
+    let mut rules = storage.get_learned_rules()?;
+    if let Some(s) = state {
+        rules.retain(|r| r.state == s);
+    }
+    render(
+        &rules,
+        &[
+            ("NAME", "name"),
+            ("STATE", "state"),
+            ("CONFIDENCE", "confidence"),
+            ("OBSERVATIONS", "observation_count"),
+            ("DOMAIN", "domain"),
+            ("PROJECT", "project"),
+            ("UPDATED", "updated_at"),
+        ],
+        format,
+    )
+}
+
+fn cmd_sessions_show(session_id: &str, format: Format) -> Result<(), String> {
+    let storage = open_storage()?;
+    let summary = storage.get_session_summary(session_id)?;
+
+    let filters = SearchFilters {
+        session_id: Some(session_id.to_string()),
+        ..Default::default()
+    };
+    let mut messages = search("", &filters, "recency", 100)?.hits;
+    messages.reverse();
+
+    if summary.is_none() && messages.is_empty() {
+        return Err(format!("session '{session_id}' not found"));
+    }
+
+    let columns = [
+        ("TIME", "timestamp"),
+        ("ROLE", "role"),
+        ("TOOLS", "tools_used"),
+        ("TEXT", "content"),
+    ];
+    match format {
+        Format::Json => print_json(&serde_json::json!({
+            "session": summary,
+            "messages": messages,
+        })),
+        Format::Csv => render(&messages, &columns, format),
+        Format::Table => {
+            if let Some(s) = &summary {
+                println!("Session   {}", s.session_id);
+                println!("Host      {}", s.hostname);
+                println!("Project   {}", s.project.as_deref().unwrap_or("-"));
+                println!("Tokens    {} over {} turns", s.total_tokens, s.turn_count);
+                println!("Cost      ${:.2}", s.cost_usd);
+                println!("Active    {} .. {}", s.first_seen, s.last_active);
+                println!();
+            }
+            render(&messages, &columns, format)
+        }
+    }
+}
+
+const API_TOKEN_COLUMNS: &[(&str, &str)] = &[
+    ("ID", "id"),
+    ("NAME", "name"),
+    ("HOST", "hostname"),
+    ("SCOPES", "scopes"),
+    ("TOKEN", "token_prefix"),
+    ("LAST USED", "last_used_at"),
+    ("REVOKED", "revoked_at"),
+];
+
+fn cmd_api_tokens_list(format: Format) -> Result<(), String> {
+    let tokens = open_storage()?.list_api_tokens()?;
+    render(&tokens, API_TOKEN_COLUMNS, format)
+}
+
+fn cmd_api_tokens_issue(name: &str, args: &mut Args, format: Format) -> Result<(), String> {
+    let host = args.take("host");
+    let scopes: Vec<String> = args
+        .take("scopes")
+        .map(|s| {
+            s.split(',')
+                .map(|scope| scope.trim().to_string())
+                .filter(|scope| !scope.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    args.finish()?;
+
+    let storage = open_storage()?;
+    print_issued(
+        &auth::issue_api_token(&storage, name, host.as_deref(), &scopes)?,
+        format,
+    )
+}
+
+fn print_issued(issued: &IssuedApiToken, format: Format) -> Result<(), String> {
+    match format {
+        Format::Json => print_json(issued),
+        Format::Csv => {
+            let mut row =
+                serde_json::to_value(&issued.info).map_err(|e| format!("Serialize error: {e}"))?;
+            row["token"] = Value::String(issued.token.clone());
+            let mut columns = API_TOKEN_COLUMNS.to_vec();
+            columns.push(("SECRET", "token"));
+            render(&[row], &columns, format)
+        }
+        Format::Table => {
+            // Printed outside the table so the token is never truncated
+            render(
+                std::slice::from_ref(&issued.info),
+                API_TOKEN_COLUMNS,
+                format,
+            )?;
+            println!();
+            println!("Token: {}", issued.token);
+            println!("Store it now; it cannot be shown again.");
+            Ok(())
+        }
+    }
+}
+
+fn cmd_server_show(format: Format) -> Result<(), String> {
+    let storage = open_storage()?;
+    let config = ListenConfig::load(&storage)?;
+    let (tls, cert) = match &config.tls {
+        TlsMode::Off => ("off", None),
+        TlsMode::SelfSigned => ("self-signed", Some(crate::tls::self_signed_paths()?.0)),
+        TlsMode::Files { cert, .. } => ("files", Some(cert.clone())),
+    };
+    // Generates the self-signed certificate if the server hasn't yet, so the
+    // fingerprint can be shared before the first remote connection
+    let fingerprint = config.tls_identity()?.map(|identity| identity.fingerprint);
+
+    let info = serde_json::json!({
+        "bind": config.addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+        "allow": config.allow.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+        "tls": tls,
+        "certificate": cert.map(|c| c.display().to_string()),
+        "fingerprint": fingerprint,
+    });
+    if format == Format::Json {
+        return print_json(&info);
+    }
+    render(
+        &[info],
+        &[
+            ("BIND", "bind"),
+            ("ALLOW", "allow"),
+            ("TLS", "tls"),
+            ("CERTIFICATE", "certificate"),
+        ],
+        format,
+    )?;
+    if let (Some(fp), Format::Table) = (&fingerprint, format) {
+        println!();
+        println!("Fingerprint (SHA-256): {fp}");
+    }
+    Ok(())
+}
+
+fn cmd_server_set(key: &str, value: &str) -> Result<(), String> {
+    let setting = format!("server.{}", key.replace('-', "_"));
+    if !LISTEN_SETTINGS.iter().any(|(k, _)| *k == setting) {
+        return Err(format!(
+            "unknown server setting '{key}' (expected bind, allow, tls, tls-cert or tls-key)"
+        ));
+    }
+    let value = value.trim();
+    let storage = open_storage()?;
+    if setting == "server.tls_cert" || setting == "server.tls_key" {
+        // The pair is set one key at a time, so only check the file here
+        if !value.is_empty() && !std::path::Path::new(value).is_file() {
+            return Err(format!("{value} is not a file"));
+        }
+        storage.set_setting(&setting, value)?;
+    } else {
+        // Reject values the server would refuse to start with
+        let previous = storage.get_setting(&setting)?;
+        storage.set_setting(&setting, value)?;
+        if let Err(e) = ListenConfig::load(&storage) {
+            storage.set_setting(&setting, previous.as_deref().unwrap_or(""))?;
+            return Err(e);
+        }
+    }
+    println!("Set {setting}; restart Quill or quill-daemon to apply");
+    Ok(())
+}
+
+fn cmd_audit(args: &mut Args, format: Format) -> Result<(), String> {
+    let kind = args.take("kind");
+    if kind
+        .as_deref()
+        .is_some_and(|k| k != "api" && k != "command")
+    {
+        return Err("--kind must be api or command".to_string());
+    }
+    let days = args
+        .take("range")
+        .map(|r| parse_range_days(&r))
+        .transpose()?;
+    // Tables show the latest entries; csv and json export everything
+    let limit = match args.take("limit") {
+        Some(n) => Some(n.parse().map_err(|_| format!("invalid limit '{n}'"))?),
+        None if format == Format::Table => Some(50),
+        None => None,
+    };
+    let query = AuditQuery {
+        kind,
+        action: args.take("action"),
+        actor: args.take("actor"),
+        hostname: args.take("host"),
+        days,
+        limit,
+    };
+    args.finish()?;
+
+    let entries = open_storage()?.get_audit_log(&query)?;
+    if format == Format::Json {
+        return print_json(&entries);
+    }
+    render(
+        &entries,
+        &[
+            ("TIME", "timestamp"),
+            ("KIND", "kind"),
+            ("ACTION", "action"),
+            ("ACTOR", "actor"),
+            ("HOST", "hostname"),
+            ("CLIENT", "client_addr"),
+            ("STATUS", "status"),
+            ("BYTES", "bytes"),
+            ("DETAIL", "detail"),
+            ("ERROR", "error"),
+        ],
+        format,
+    )
+}
+
+fn cmd_audit_set_retention(value: &str) -> Result<(), String> {
+    let days: i64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid retention '{value}' (expected a number of days)"))?;
+    crate::audit::set_retention_days(&open_storage()?, days)?;
+    println!("Audit entries are now kept for {days} days");
+    Ok(())
+}
+
+const TRASH_COLUMNS: &[(&str, &str)] = &[
+    ("BATCH", "id"),
+    ("KIND", "kind"),
+    ("TARGET", "target"),
+    ("DELETED", "deleted_at"),
+    ("SNAPSHOTS", "snapshot_count"),
+    ("HOURLY", "hourly_count"),
+];
+
+fn cmd_trash_list(format: Format) -> Result<(), String> {
+    render(
+        &open_storage()?.list_trash_batches()?,
+        TRASH_COLUMNS,
+        format,
+    )
+}
+
+fn cmd_trash_apply(action: &str, batch_id: &str, format: Format) -> Result<(), String> {
+    let storage = open_storage()?;
+    let batch = if action == "restore" {
+        storage.restore_trash_batch(batch_id)?
+    } else {
+        storage.purge_trash_batch(batch_id)?
+    };
+    if format == Format::Json {
+        return print_json(&batch);
+    }
+    render(&[batch], TRASH_COLUMNS, format)
+}
+
+fn cmd_trash_set_retention(value: &str) -> Result<(), String> {
+    let days: i64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid retention '{value}' (expected a number of days)"))?;
+    crate::trash::set_retention_days(&open_storage()?, days)?;
+    println!("Trashed data is now purged after {days} days");
+    Ok(())
+}
+
+const BACKUP_COLUMNS: &[(&str, &str)] = &[
+    ("NAME", "name"),
+    ("CREATED", "created_at"),
+    ("SCHEMA", "schema_version"),
+    ("INDEX FILES", "index_files"),
+    ("SECRET", "has_secret"),
+    ("BYTES", "size_bytes"),
+];
+
+fn cmd_backup_restore(source: &str, format: Format) -> Result<(), String> {
+    let source = {
+        let storage = open_storage()?;
+        ensure_quill_stopped(&storage)?;
+        crate::backup::resolve(&storage, source)?
+    };
+    let info = crate::backup::restore(&source)?;
+    if format == Format::Table {
+        println!(
+            "Restored {}; the replaced files are in pre-restore in the data directory",
+            info.name
+        );
+        return Ok(());
+    }
+    render(&[info], BACKUP_COLUMNS, format)
+}
+
+/// Restoring and re-redacting replace files the app keeps open, so refuse
+/// while something holds the server's listen address.
+fn ensure_quill_stopped(storage: &Storage) -> Result<(), String> {
+    for addr in ListenConfig::load(storage)?.addrs {
+        if let Err(e) = std::net::TcpListener::bind(addr)
+            && e.kind() == std::io::ErrorKind::AddrInUse
+        {
+            return Err(format!(
+                "{addr} is in use, so Quill appears to be running; quit the app and quill-daemon first"
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn cmd_backup_set(key: &str, value: &str) -> Result<(), String> {
+    let setting = format!("backup.{key}");
+    if !crate::backup::BACKUP_SETTINGS.contains(&setting.as_str()) {
+        return Err(format!(
+            "unknown backup setting '{key}' (expected dir, interval_hours or keep)"
+        ));
+    }
+    let value = value.trim();
+    crate::backup::validate_setting(&setting, value)?;
+    open_storage()?.set_setting(&setting, value)?;
+    println!("Set {setting}");
+    Ok(())
+}
+
+fn cmd_redact_list(format: Format) -> Result<(), String> {
+    let patterns = crate::redact::patterns(&open_storage()?)?;
+    if format == Format::Json {
+        return print_json(&patterns);
+    }
+    let rows: Vec<Value> = patterns
+        .iter()
+        .map(|p| serde_json::json!({ "pattern": p }))
+        .collect();
+    render(&rows, &[("PATTERN", "pattern")], format)
+}
+
+fn cmd_redact_edit(action: &str, pattern: &str) -> Result<(), String> {
+    let storage = open_storage()?;
+    let mut patterns = crate::redact::patterns(&storage)?;
+    if action == "add" {
+        if patterns.iter().any(|p| p == pattern) {
+            return Err(format!("pattern '{pattern}' already exists"));
+        }
+        patterns.push(pattern.to_string());
+    } else {
+        let before = patterns.len();
+        patterns.retain(|p| p != pattern);
+        if patterns.len() == before {
+            return Err(format!("no pattern '{pattern}'"));
+        }
+    }
+    crate::redact::set_patterns(&storage, &patterns)?;
+    println!(
+        "Set {}; restart Quill or quill-daemon to apply, then run `quill redact run` for existing data",
+        crate::redact::PATTERNS_SETTING
+    );
+    Ok(())
+}
+
+/// Rewrites the database and the session index, so it needs the tantivy
+/// writer lock the app holds.
+fn cmd_redact_run(format: Format) -> Result<(), String> {
+    let storage = open_storage()?;
+    ensure_quill_stopped(&storage)?;
+    crate::redact::load_patterns(&storage);
+    let index = SessionIndex::open_or_create(&crate::session_index_dir())?;
+    let summary = crate::redact::redact_existing(&storage, Some(&index))?;
+    if format == Format::Table {
+        println!(
+            "Redacted {} observations, {} tool actions, {} outbox entries and {} indexed messages",
+            summary.observations, summary.tool_actions, summary.outbox, summary.messages
+        );
+        return Ok(());
+    }
+    render(&[summary], REDACTION_COLUMNS, format)
+}
+
+const REDACTION_COLUMNS: &[(&str, &str)] = &[
+    ("OBSERVATIONS", "observations"),
+    ("TOOL ACTIONS", "tool_actions"),
+    ("OUTBOX", "outbox"),
+    ("MESSAGES", "messages"),
+];
+
+fn cmd_relay_show(format: Format) -> Result<(), String> {
+    let status = crate::relay::status(&open_storage()?)?;
+    if format == Format::Json {
+        return print_json(&status);
+    }
+    render(
+        &[status],
+        &[
+            ("UPSTREAM", "upstream"),
+            ("PENDING", "pending"),
+            ("OLDEST", "oldest_queued_at"),
+            ("LAST ERROR", "last_error"),
+            ("PAUSED", "paused"),
+        ],
+        format,
+    )
+}
+
+fn cmd_relay_set(key: &str, value: &str) -> Result<(), String> {
+    let setting = format!("relay.{key}");
+    if !crate::relay::RELAY_SETTINGS
+        .iter()
+        .any(|(k, _)| *k == setting)
+    {
+        return Err(format!(
+            "unknown relay setting '{key}' (expected upstream, token or fingerprint)"
+        ));
+    }
+    let value = value.trim();
+    if !value.is_empty() {
+        crate::relay::validate_setting(&setting, value)?;
+    }
+    open_storage()?.set_setting(&setting, value)?;
+    println!("Set {setting}; restart Quill or quill-daemon to apply");
+    Ok(())
+}
+
+// ── Session search transport ──
+
+fn search(
+    query: &str,
+    filters: &SearchFilters,
+    sort: &str,
+    limit: usize,
+) -> Result<SearchResults, String> {
+    match search_via_api(query, filters, sort, limit) {
+        Ok(results) => Ok(results),
+        Err(ApiError::Unreachable) => {
+            let index = SessionIndex::open_or_create(&crate::session_index_dir())?;
+            index.search(query, filters, sort, 0, limit)
+        }
+        Err(ApiError::Failed(e)) => Err(e),
+    }
+}
+
+enum ApiError {
+    /// No server is listening; fall back to reading the index directly.
+    Unreachable,
+    Failed(String),
+}
+
+fn search_via_api(
+    query: &str,
+    filters: &SearchFilters,
+    sort: &str,
+    limit: usize,
+) -> Result<SearchResults, ApiError> {
+    let base = std::env::var("QUILL_URL").unwrap_or_else(|_| {
+        let port = std::env::var("QUILL_PORT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+        format!("http://127.0.0.1:{port}")
+    });
+    let secret = match std::env::var("QUILL_SECRET") {
+        Ok(s) => s,
+        Err(_) => auth::load_secret().map_err(ApiError::Failed)?,
+    };
+
+    let mut params: Vec<(&str, String)> = vec![
+        ("q", query.to_string()),
+        ("sort_by", sort.to_string()),
+        ("page_size", limit.to_string()),
+    ];
+    let optional = [
+        ("project", &filters.project),
+        ("host", &filters.host),
+        ("role", &filters.role),
+        ("git_branch", &filters.git_branch),
+        ("session_id", &filters.session_id),
+        ("date_from", &filters.date_from),
+        ("date_to", &filters.date_to),
+    ];
+    for (key, value) in optional {
+        if let Some(v) = value {
+            params.push((key, v.clone()));
+        }
+    }
+
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/api/v1/sessions/search", base.trim_end_matches('/')),
+        &params,
+    )
+    .map_err(|e| ApiError::Failed(format!("invalid QUILL_URL: {e}")))?;
+    crate::runtime::block_on(async move {
+        let resp = http_client()
+            .get(url)
+            .bearer_auth(secret)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_connect() {
+                    ApiError::Unreachable
+                } else {
+                    ApiError::Failed(format!("search request failed: {e}"))
+                }
+            })?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            // Query syntax errors come back as a 400 with a message to show
+            if status == reqwest::StatusCode::BAD_REQUEST
+                && let Ok(body) = resp.json::<Value>().await
+                && let Some(error) = body["error"].as_str()
+            {
+                return Err(ApiError::Failed(error.to_string()));
+            }
+            return Err(ApiError::Failed(format!(
+                "search request returned HTTP {status}"
+            )));
+        }
+        resp.json::<SearchResults>()
+            .await
+            .map_err(|e| ApiError::Failed(format!("invalid search response: {e}")))
+    })
+}
+
+// ── Output ──
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Format {
+    fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("table") {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            other => Err(format!(
+                "invalid --format '{other}' (expected table, json or csv)"
+            )),
+        }
+    }
+}
+
+/// Longest cell rendered in table output; JSON and CSV are never truncated.
+const MAX_CELL_WIDTH: usize = 60;
+
+/// Prints rows in the requested format. `columns` maps headers to the
+/// serialized field names shown in table and CSV output; JSON includes every
+/// field.
+fn render<T: Serialize>(
+    rows: &[T],
+    columns: &[(&str, &str)],
+    format: Format,
+) -> Result<(), String> {
+    if format == Format::Json {
+        return print_json(&rows);
+    }
+
+    let values: Vec<Value> = rows
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Serialize error: {e}"))?;
+    let cells: Vec<Vec<String>> = values
+        .iter()
+        .map(|v| {
+            columns
+                .iter()
+                .map(|(_, key)| cell_text(&v[*key], format))
+                .collect()
+        })
+        .collect();
+
+    let mut out = std::io::stdout().lock();
+    let result = if format == Format::Csv {
+        write_csv(&mut out, columns, &cells)
+    } else {
+        write_table(&mut out, columns, &cells)
+    };
+    result.map_err(|e| format!("Write error: {e}"))
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("Serialize error: {e}"))?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Tables round floats for reading; CSV keeps full precision.
+fn cell_text(value: &Value, format: Format) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) if n.is_f64() && format == Format::Table => {
+            format!("{:.2}", n.as_f64().unwrap_or_default())
+        }
+        other => other.to_string(),
+    }
+}
+
+fn write_csv(
+    out: &mut impl Write,
+    columns: &[(&str, &str)],
+    cells: &[Vec<String>],
+) -> std::io::Result<()> {
+    let escape = |s: &str| {
+        if s.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let header: Vec<String> = columns.iter().map(|(_, key)| escape(key)).collect();
+    writeln!(out, "{}", header.join(","))?;
+    for row in cells {
+        let line: Vec<String> = row.iter().map(|c| escape(c)).collect();
+        writeln!(out, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_table(
+    out: &mut impl Write,
+    columns: &[(&str, &str)],
+    cells: &[Vec<String>],
+) -> std::io::Result<()> {
+    // Collapse whitespace so multi-line message text stays on one row
+    let cells: Vec<Vec<String>> = cells
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|c| {
+                    let flat = c.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if flat.chars().count() > MAX_CELL_WIDTH {
+                        let cut: String = flat.chars().take(MAX_CELL_WIDTH - 1).collect();
+                        format!("{cut}…")
+                    } else {
+                        flat
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|(h, _)| h.chars().count()).collect();
+    for row in &cells {
+        for (w, c) in widths.iter_mut().zip(row) {
+            *w = (*w).max(c.chars().count());
+        }
+    }
+
+    let line = |values: Vec<&str>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{v:<w$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    writeln!(out, "{}", line(columns.iter().map(|(h, _)| *h).collect()))?;
+    for row in &cells {
+        writeln!(out, "{}", line(row.iter().map(String::as_str).collect()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use crate::events::EventBus;
+use crate::server;
+
+// ── Headless collector ──
+//
+// `quill-daemon` runs the parts of the app that other hosts depend on (storage,
+// the HTTP server, the session indexer, usage polling and alerts, hourly
+// aggregation, backups and the learning scheduler) without a webview, so an
+// always-on machine can collect token reports from every laptop. Build it with
+// `--no-default-features` where GTK and WebKit aren't installed.
+
+/// Entry point for the `quill-daemon` binary. Blocks until SIGINT/SIGTERM.
+pub fn run() {
+    if let Err(e) = init_logging() {
+        eprintln!("Failed to initialize logging: {e}");
+    }
+
+    let storage = crate::init_storage();
+    let secret = crate::load_server_secret();
+
+    crate::runtime::block_on(async move {
+        // No webview to forward to; events only reach in-process subscribers
+        let events = EventBus::new();
+        spawn_event_logger(&events);
+
+        let session_index = crate::open_session_index(&events);
+        crate::spawn_backup_scheduler(storage, session_index.clone());
+        crate::runtime::spawn(server::start_server(
+            storage,
+            secret,
+            events.clone(),
+            session_index,
+        ));
+        crate::spawn_hourly_maintenance(storage);
+        crate::spawn_usage_poller(events.clone());
+        crate::spawn_learning_scheduler(storage, events);
+
+        log::info!("Quill daemon started");
+        shutdown_signal().await;
+        log::info!("Quill daemon shutting down");
+    });
+}
+
+fn init_logging() -> Result<(), log::SetLoggerError> {
+    fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "[{}][{}][{}] {message}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                record.level(),
+                record.target(),
+            ))
+        })
+        .level(log::LevelFilter::Info)
+        .level_for("tantivy", log::LevelFilter::Warn)
+        .chain(std::io::stdout())
+        .apply()
+}
+
+/// Logs every backend event at debug level, standing in for the webview.
+fn spawn_event_logger(events: &EventBus) {
+    let mut rx = events.subscribe();
+    crate::runtime::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => log::debug!("Event {}: {}", event.name, event.payload),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    log::debug!("Event logger skipped {n} events");
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM: {e}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -0,0 +1,81 @@
+use serde::Serialize;
+#[cfg(feature = "gui")]
+use tauri::Emitter;
+#[cfg(feature = "gui")]
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::broadcast;
+
+/// Buffered events per subscriber before slow receivers start lagging.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// A backend event as seen by in-process subscribers.
+#[derive(Serialize, Clone, Debug)]
+pub struct BackendEvent {
+    pub name: String,
+    pub payload: serde_json::Value,
+}
+
+/// Fan-out for backend events. The desktop app forwards every event to the
+/// webview through its `AppHandle`; the headless daemon has no webview, so
+/// events only reach in-process subscribers such as the learning scheduler.
+#[derive(Clone)]
+pub struct EventBus {
+    #[cfg(feature = "gui")]
+    app: Option<tauri::AppHandle>,
+    tx: broadcast::Sender<BackendEvent>,
+}
+
+impl EventBus {
+    /// A bus without a webview, for the headless daemon.
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            #[cfg(feature = "gui")]
+            app: None,
+            tx,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn with_app(app: tauri::AppHandle) -> Self {
+        Self {
+            app: Some(app),
+            ..Self::new()
+        }
+    }
+
+    pub fn emit<S: Serialize>(&self, name: &str, payload: S) {
+        let payload = match serde_json::to_value(payload) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Failed to serialize '{name}' event: {e}");
+                return;
+            }
+        };
+        #[cfg(feature = "gui")]
+        if let Some(app) = &self.app {
+            let _ = app.emit(name, &payload);
+        }
+        // send() only fails when nobody is subscribed
+        let _ = self.tx.send(BackendEvent {
+            name: name.to_string(),
+            payload,
+        });
+    }
+
+    /// Shows a desktop notification. The headless daemon only logs it.
+    pub fn notify(&self, title: &str, body: &str) {
+        #[cfg(feature = "gui")]
+        if let Some(app) = &self.app {
+            if let Err(e) = app.notification().builder().title(title).body(body).show() {
+                log::warn!("Failed to show notification: {e}");
+            }
+            return;
+        }
+        log::info!("{title}: {body}");
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BackendEvent> {
+        self.tx.subscribe()
+    }
+}
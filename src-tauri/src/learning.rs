@@ -1,11 +1,11 @@
 use std::time::Instant;
 
+use crate::events::EventBus;
 use crate::models::{
     AnalysisOutput, LearningLogEvent, LearningRunPayload, RunPhase, StreamFindings,
 };
 use crate::prompt_utils::{compress_observation, sanitize_for_prompt};
 use crate::storage::Storage;
-use tauri::Emitter;
 
 /// Returns true if the rule name is safe for use as a filename.
 /// Only allows lowercase ASCII letters, digits, and hyphens.
@@ -26,7 +26,7 @@ async fn analyze_observations_stream(
     max_rules: usize,
     existing_rules_summary: String,
     existing_list: String,
-    app: tauri::AppHandle,
+    events: EventBus,
     run_id: i64,
 ) -> (Option<(StreamFindings, i64)>, Vec<String>) {
     let mut logs: Vec<String> = Vec::new();
@@ -35,7 +35,7 @@ async fn analyze_observations_stream(
 		($($arg:tt)*) => {{
 			let msg = format!($($arg)*);
 			log::debug!("{msg}");
-			let _ = app.emit("learning-log", &LearningLogEvent {
+			events.emit("learning-log", &LearningLogEvent {
 				run_id,
 				message: msg.clone(),
 			});
@@ -188,7 +188,7 @@ async fn analyze_git_stream(
     storage: &'static Storage,
     project_path: String,
     existing_rules_summary: String,
-    app: tauri::AppHandle,
+    events: EventBus,
     run_id: i64,
 ) -> (Option<StreamFindings>, Vec<String>) {
     let mut logs: Vec<String> = Vec::new();
@@ -197,7 +197,7 @@ async fn analyze_git_stream(
 		($($arg:tt)*) => {{
 			let msg = format!($($arg)*);
 			log::debug!("{msg}");
-			let _ = app.emit("learning-log", &LearningLogEvent {
+			events.emit("learning-log", &LearningLogEvent {
 				run_id,
 				message: msg.clone(),
 			});
@@ -285,14 +285,14 @@ async fn synthesize_findings(
     claude_md_context: &str,
     max_rules: usize,
     logs: &mut Vec<String>,
-    app: &tauri::AppHandle,
+    events: &EventBus,
     run_id: i64,
 ) -> Result<AnalysisOutput, String> {
     macro_rules! synth_log {
 		($($arg:tt)*) => {{
 			let msg = format!($($arg)*);
 			log::debug!("{msg}");
-			let _ = app.emit("learning-log", &LearningLogEvent {
+			events.emit("learning-log", &LearningLogEvent {
 				run_id,
 				message: msg.clone(),
 			});
@@ -413,7 +413,7 @@ async fn synthesize_findings(
 pub async fn spawn_analysis(
     storage: &'static Storage,
     trigger: &str,
-    app: &tauri::AppHandle,
+    events: &EventBus,
     micro: bool,
 ) -> Result<(), String> {
     // ── Phase 0: Setup ──────────────────────────────────────────────────
@@ -422,7 +422,7 @@ pub async fn spawn_analysis(
     let run_id = storage
         .create_learning_run(trigger)
         .map_err(|e| format!("Failed to create learning run: {e}"))?;
-    let _ = app.emit("learning-updated", ());
+    events.emit("learning-updated", ());
 
     let mut logs: Vec<String> = Vec::new();
     let mut phases: Vec<RunPhase> = Vec::new();
@@ -431,7 +431,7 @@ pub async fn spawn_analysis(
         ($($arg:tt)*) => {{
             let msg = format!($($arg)*);
             log::debug!("{msg}");
-            let _ = app.emit("learning-log", &LearningLogEvent {
+            events.emit("learning-log", &LearningLogEvent {
                 run_id,
                 message: msg.clone(),
             });
@@ -613,7 +613,7 @@ pub async fn spawn_analysis(
             max_rules,
             existing_rules_summary.clone(),
             existing_list.clone(),
-            app.clone(),
+            events.clone(),
             run_id,
         )
         .await;
@@ -669,17 +669,17 @@ pub async fn spawn_analysis(
                 max_rules,
                 existing_rules_summary.clone(),
                 existing_list.clone(),
-                app.clone(),
+                events.clone(),
                 run_id,
             ),
             analyze_git_stream(
                 storage,
                 project_path.clone(),
                 existing_rules_summary.clone(),
-                app.clone(),
+                events.clone(),
                 run_id,
             ),
-            gather_insights(app.clone(), run_id),
+            gather_insights(events.clone(), run_id),
         );
 
         // Destructure and merge logs
@@ -736,7 +736,7 @@ pub async fn spawn_analysis(
                 &claude_md_context,
                 max_rules,
                 &mut logs,
-                app,
+                events,
                 run_id,
             )
             .await
@@ -842,7 +842,7 @@ pub async fn spawn_analysis(
             source: Some(source_label.to_string()),
         },
         &mut logs,
-        app,
+        events,
     )?;
 
     // Process verdicts on existing rules
@@ -980,17 +980,14 @@ struct InsightsData {
 /// Gathers session insights by running `claude /insights --print` and parsing facet JSONs.
 /// Returns `None` on any failure (CLI error, no facets, parse errors).
 /// Returns owned logs alongside the result so it can run inside `tokio::join!`.
-async fn gather_insights(
-    app: tauri::AppHandle,
-    run_id: i64,
-) -> (Option<InsightsData>, Vec<String>) {
+async fn gather_insights(events: EventBus, run_id: i64) -> (Option<InsightsData>, Vec<String>) {
     let mut logs: Vec<String> = Vec::new();
 
     macro_rules! insight_log {
         ($($arg:tt)*) => {{
             let msg = format!($($arg)*);
             log::debug!("{msg}");
-            let _ = app.emit("learning-log", &LearningLogEvent {
+            events.emit("learning-log", &LearningLogEvent {
                 run_id,
                 message: msg.clone(),
             });
@@ -1144,7 +1141,7 @@ struct WriteRuleParams<'a> {
 fn write_rule_files(
     params: &WriteRuleParams<'_>,
     logs: &mut Vec<String>,
-    app: &tauri::AppHandle,
+    events: &EventBus,
 ) -> Result<(i64, i64), String> {
     let WriteRuleParams {
         rules,
@@ -1167,7 +1164,7 @@ fn write_rule_files(
                 &rule.name[..rule.name.len().min(50)]
             );
             log::debug!("{msg}");
-            let _ = app.emit("learning-log", &msg);
+            events.emit("learning-log", &msg);
             logs.push(msg);
             continue;
         }
@@ -1184,7 +1181,7 @@ fn write_rule_files(
         if !canonical_parent.starts_with(&canonical_dir) {
             let msg = format!("Skipped '{}': path traversal detected", rule.name);
             log::debug!("{msg}");
-            let _ = app.emit("learning-log", &msg);
+            events.emit("learning-log", &msg);
             logs.push(msg);
             continue;
         }
@@ -1241,7 +1238,7 @@ fn write_rule_files(
                 )
             };
             log::debug!("{msg}");
-            let _ = app.emit("learning-log", &msg);
+            events.emit("learning-log", &msg);
             logs.push(msg);
         } else {
             let msg = format!(
@@ -1249,7 +1246,7 @@ fn write_rule_files(
                 rule.name, rule.confidence, min_confidence
             );
             log::debug!("{msg}");
-            let _ = app.emit("learning-log", &msg);
+            events.emit("learning-log", &msg);
             logs.push(msg);
         }
     }
@@ -1,5 +1,13 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    quill_lib::run()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if quill_lib::cli::is_cli_invocation(&args) {
+        std::process::exit(quill_lib::cli::run(&args));
+    }
+    #[cfg(feature = "gui")]
+    quill_lib::run();
+    // Built without the desktop app, so the command-line client is all there is
+    #[cfg(not(feature = "gui"))]
+    std::process::exit(quill_lib::cli::run(&args));
 }
@@ -1,27 +1,46 @@
+#[cfg(feature = "gui")]
 use sha2::{Digest, Sha256};
-use std::path::{Path, PathBuf};
+#[cfg(feature = "gui")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "gui")]
 use tauri::Emitter;
 
-use crate::ai_client;
-use crate::models::{
-    ActionType, MemoryFile, MemoryFilesUpdatedEvent, MemoryOptimizerLogEvent,
-    MemoryOptimizerUpdatedEvent, OptimizationOutput,
-};
+#[cfg(feature = "gui")]
+use crate::models::MemoryFile;
+#[cfg(feature = "gui")]
 use crate::prompt_utils::{escape_for_prompt, safe_truncate};
+#[cfg(feature = "gui")]
+use crate::{
+    ai_client,
+    models::{
+        ActionType, MemoryFilesUpdatedEvent, MemoryOptimizerLogEvent, MemoryOptimizerUpdatedEvent,
+        OptimizationOutput,
+    },
+};
 
+#[cfg(feature = "gui")]
 type SuggestionRow = (i64, Option<String>, Option<Vec<String>>);
+#[cfg(feature = "gui")]
 use crate::storage::Storage;
 
+#[cfg(feature = "gui")]
 const MAX_DENIED: usize = 50;
 
 /// Total prompt budget in bytes (~1MB)
+#[cfg(feature = "gui")]
 const TOTAL_BUDGET_BYTES: usize = 1_040_000;
+#[cfg(feature = "gui")]
 const WEIGHT_MEMORY: f64 = 0.58;
+#[cfg(feature = "gui")]
 const WEIGHT_CLAUDEMD: f64 = 0.23;
+#[cfg(feature = "gui")]
 const WEIGHT_RULES: f64 = 0.12;
+#[cfg(feature = "gui")]
 const WEIGHT_INSTINCTS: f64 = 0.07;
 
 /// Compute dynamic budget allocation based on which sections have content.
+#[cfg(feature = "gui")]
 fn allocate_budgets(
     has_memory: bool,
     has_claude_md: bool,
@@ -74,6 +93,7 @@ fn project_path_to_slug(project_path: &str) -> String {
 /// step, try extending the current segment with `-` first (longer match), and
 /// only fall back to `/` (new segment) if the longer match doesn't lead to an
 /// existing directory.
+#[cfg(feature = "gui")]
 fn slug_to_path(slug: &str) -> String {
     let slug = slug.strip_prefix('-').unwrap_or(slug);
     let parts: Vec<&str> = slug.split('-').collect();
@@ -113,6 +133,7 @@ pub fn memory_dir(project_path: &str) -> PathBuf {
 }
 
 /// Parse frontmatter from a memory file. Returns (type, description) if found.
+#[cfg(feature = "gui")]
 fn parse_frontmatter(content: &str) -> (Option<String>, Option<String>) {
     if !content.starts_with("---") {
         return (None, None);
@@ -136,6 +157,7 @@ fn parse_frontmatter(content: &str) -> (Option<String>, Option<String>) {
 }
 
 /// Scan memory files for a project from disk.
+#[cfg(feature = "gui")]
 pub fn scan_memory_files(storage: &Storage, project_path: &str) -> Result<Vec<MemoryFile>, String> {
     let dir = memory_dir(project_path);
 
@@ -245,10 +267,12 @@ pub fn scan_memory_files(storage: &Storage, project_path: &str) -> Result<Vec<Me
     Ok(files)
 }
 
+#[cfg(feature = "gui")]
 fn read_file_optional(path: &Path) -> String {
     std::fs::read_to_string(path).unwrap_or_default()
 }
 
+#[cfg(feature = "gui")]
 struct GatheredContext {
     global_claude_md: String,
     project_claude_md: String,
@@ -256,6 +280,7 @@ struct GatheredContext {
     instincts: String,
 }
 
+#[cfg(feature = "gui")]
 fn gather_context(project_path: &str) -> GatheredContext {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
 
@@ -317,6 +342,7 @@ fn gather_context(project_path: &str) -> GatheredContext {
     }
 }
 
+#[cfg(feature = "gui")]
 fn collect_md_files(dir: &Path, out: &mut String) {
     let Ok(entries) = std::fs::read_dir(dir) else {
         return;
@@ -337,6 +363,7 @@ fn collect_md_files(dir: &Path, out: &mut String) {
     }
 }
 
+#[cfg(feature = "gui")]
 fn build_prompt(
     memory_files: &[&MemoryFile],
     context: &GatheredContext,
@@ -488,6 +515,7 @@ fn build_prompt(
 }
 
 /// Generate a unified diff between original and proposed content.
+#[cfg(feature = "gui")]
 fn generate_diff(original: &str, proposed: &str, filename: &str) -> String {
     use similar::TextDiff;
     let diff = TextDiff::from_lines(original, proposed);
@@ -504,6 +532,7 @@ fn generate_diff(original: &str, proposed: &str, filename: &str) -> String {
 
 /// Main entry point: run memory optimization for a project.
 /// The run record is created externally (by the Tauri command) so the caller has the run_id.
+#[cfg(feature = "gui")]
 pub async fn run_optimization_with_run(
     storage: &'static Storage,
     project_path: &str,
@@ -768,6 +797,7 @@ pub async fn run_optimization_with_run(
 /// Resolve a target file path with path traversal protection.
 /// CLAUDE.md targets get special handling; all others are validated
 /// to stay within the memory directory.
+#[cfg(feature = "gui")]
 fn resolve_target_path(
     target: &str,
     project_path: &str,
@@ -798,6 +828,7 @@ fn resolve_target_path(
 
 /// Read a target file's content, resolving CLAUDE.md paths specially.
 /// Returns None if the file does not exist or is unreadable.
+#[cfg(feature = "gui")]
 fn read_target_file(
     project_path: &str,
     target: Option<&str>,
@@ -811,6 +842,7 @@ fn read_target_file(
 /// Detect file-level conflicts between suggestions and assign group IDs.
 /// Two suggestions conflict if they reference any of the same files
 /// (via target_file or merge_sources). Connected components become groups.
+#[cfg(feature = "gui")]
 fn assign_conflict_groups(storage: &Storage, suggestions: &[SuggestionRow]) -> Result<(), String> {
     if suggestions.len() < 2 {
         return Ok(());
@@ -885,6 +917,7 @@ fn assign_conflict_groups(storage: &Storage, suggestions: &[SuggestionRow]) -> R
 /// Execute all suggestions in a group atomically.
 /// Validates staleness for all before executing any, then executes in order:
 /// flag → update → create → merge → delete.
+#[cfg(feature = "gui")]
 pub fn execute_suggestion_group(
     storage: &Storage,
     group_id: &str,
@@ -961,6 +994,7 @@ pub fn execute_suggestion_group(
 
 /// Execute a single suggestion's filesystem operation without staleness check.
 /// Used by group execution where staleness was already validated.
+#[cfg(feature = "gui")]
 fn execute_single_suggestion_unchecked(
     _storage: &Storage,
     suggestion: &crate::models::OptimizationSuggestion,
@@ -1070,6 +1104,7 @@ fn execute_single_suggestion_unchecked(
 }
 
 /// Generate a single MEMORY.md follow-up suggestion for an entire approved group.
+#[cfg(feature = "gui")]
 fn generate_group_memory_md_followup(
     storage: &Storage,
     suggestions: &[crate::models::OptimizationSuggestion],
@@ -1181,6 +1216,7 @@ fn generate_group_memory_md_followup(
 }
 
 /// Deny all pending suggestions in a group.
+#[cfg(feature = "gui")]
 pub fn deny_suggestion_group(storage: &Storage, group_id: &str) -> Result<(), String> {
     let suggestions = storage.get_suggestions_by_group(group_id)?;
     for s in &suggestions {
@@ -1192,6 +1228,7 @@ pub fn deny_suggestion_group(storage: &Storage, group_id: &str) -> Result<(), St
 }
 
 /// Execute an approved suggestion — performs the filesystem operation.
+#[cfg(feature = "gui")]
 pub fn execute_suggestion(
     storage: &Storage,
     suggestion_id: i64,
@@ -1456,6 +1493,7 @@ pub fn execute_suggestion(
 }
 
 /// Undo an approved suggestion — reverses the filesystem operation.
+#[cfg(feature = "gui")]
 pub fn undo_suggestion(
     storage: &Storage,
     suggestion_id: i64,
@@ -1563,6 +1601,7 @@ pub fn undo_suggestion(
 }
 
 /// Get list of known projects (from analytics + custom).
+#[cfg(feature = "gui")]
 pub fn get_known_projects(storage: &Storage) -> Result<Vec<crate::models::KnownProject>, String> {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
     let projects_dir = home.join(".claude").join("projects");
@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use parking_lot::Mutex;
+
+use crate::models::{MetricsSummary, TokenReportPayload};
+
+// ── Prometheus exposition for the token server ──
+//
+// Request and ingestion counters live in memory and reset when the process
+// restarts; everything else is read from SQLite and the session index on
+// each scrape.
+
+/// Distinct `project` labels on the ingestion counter. The cwd comes from
+/// clients, so later projects are counted under [`OTHER_PROJECT`].
+const MAX_PROJECTS: usize = 50;
+const OTHER_PROJECT: &str = "other";
+
+/// In-process counters updated by the HTTP server.
+#[derive(Default)]
+pub struct Metrics {
+    /// (method, route, status) -> count
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// (hostname, project basename, token type) -> tokens ingested via
+    /// /api/v1/tokens
+    tokens: Mutex<BTreeMap<(String, String, &'static str), u64>>,
+}
+
+impl Metrics {
+    pub fn record_request(&self, method: &str, route: &str, status: u16) {
+        *self
+            .requests
+            .lock()
+            .entry((method.to_string(), route.to_string(), status))
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_tokens(&self, payload: &TokenReportPayload) {
+        let project = project_label(payload.cwd.as_deref().unwrap_or_default());
+        let counts = [
+            ("input", payload.input_tokens),
+            ("output", payload.output_tokens),
+            ("cache_creation", payload.cache_creation_input_tokens),
+            ("cache_read", payload.cache_read_input_tokens),
+        ];
+        let mut tokens = self.tokens.lock();
+        let known: BTreeSet<&str> = tokens.keys().map(|(_, p, _)| p.as_str()).collect();
+        let project = if known.contains(project.as_str()) || known.len() < MAX_PROJECTS {
+            project
+        } else {
+            OTHER_PROJECT.to_string()
+        };
+        for (kind, n) in counts {
+            *tokens
+                .entry((payload.hostname.clone(), project.clone(), kind))
+                .or_insert(0) += n.max(0) as u64;
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self, summary: &MetricsSummary, index_docs: Option<u64>) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "quill_usage_utilization_percent",
+            "gauge",
+            "Bucket utilization from the most recent usage snapshot.",
+        );
+        for b in &summary.buckets {
+            sample(
+                &mut out,
+                "quill_usage_utilization_percent",
+                &[("bucket", &b.label)],
+                b.utilization,
+            );
+        }
+
+        header(
+            &mut out,
+            "quill_usage_resets_at_seconds",
+            "gauge",
+            "Unix time at which the bucket resets.",
+        );
+        for b in &summary.buckets {
+            let reset = b
+                .resets_at
+                .as_deref()
+                .and_then(|r| chrono::DateTime::parse_from_rfc3339(r).ok());
+            if let Some(r) = reset {
+                sample(
+                    &mut out,
+                    "quill_usage_resets_at_seconds",
+                    &[("bucket", &b.label)],
+                    r.timestamp() as f64,
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "quill_tokens_stored",
+            "gauge",
+            "All-time tokens stored per host and token type.",
+        );
+        for h in &summary.host_tokens {
+            let counts = [
+                ("input", h.input_tokens),
+                ("output", h.output_tokens),
+                ("cache_creation", h.cache_creation_input_tokens),
+                ("cache_read", h.cache_read_input_tokens),
+            ];
+            for (kind, n) in counts {
+                sample(
+                    &mut out,
+                    "quill_tokens_stored",
+                    &[("host", &h.hostname), ("type", kind)],
+                    n as f64,
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "quill_tokens_ingested_total",
+            "counter",
+            "Tokens received on /api/v1/tokens since the server started.",
+        );
+        for ((host, project, kind), n) in self.tokens.lock().iter() {
+            sample(
+                &mut out,
+                "quill_tokens_ingested_total",
+                &[("host", host), ("project", project), ("type", kind)],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "quill_observations",
+            "gauge",
+            "Stored tool-use observations.",
+        );
+        sample(
+            &mut out,
+            "quill_observations",
+            &[("state", "all")],
+            summary.observation_count as f64,
+        );
+        sample(
+            &mut out,
+            "quill_observations",
+            &[("state", "unanalyzed")],
+            summary.unanalyzed_observations as f64,
+        );
+
+        header(
+            &mut out,
+            "quill_learning_runs",
+            "gauge",
+            "Learning analysis runs by outcome.",
+        );
+        for (status, n) in &summary.learning_runs {
+            sample(
+                &mut out,
+                "quill_learning_runs",
+                &[("status", status)],
+                *n as f64,
+            );
+        }
+
+        if let Some(docs) = index_docs {
+            header(
+                &mut out,
+                "quill_session_index_documents",
+                "gauge",
+                "Messages in the session search index.",
+            );
+            sample(&mut out, "quill_session_index_documents", &[], docs as f64);
+        }
+
+        let requests = self.requests.lock();
+        header(
+            &mut out,
+            "quill_http_requests_total",
+            "counter",
+            "HTTP requests handled, by route and status.",
+        );
+        for ((method, route, status), n) in requests.iter() {
+            sample(
+                &mut out,
+                "quill_http_requests_total",
+                &[
+                    ("method", method),
+                    ("route", route),
+                    ("status", &status.to_string()),
+                ],
+                *n as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "quill_http_rate_limited_total",
+            "counter",
+            "HTTP requests rejected by the rate limiter, by route.",
+        );
+        for ((method, route, _), n) in requests.iter().filter(|((_, _, s), _)| *s == 429) {
+            sample(
+                &mut out,
+                "quill_http_rate_limited_total",
+                &[("method", method), ("route", route)],
+                *n as f64,
+            );
+        }
+
+        out
+    }
+}
+
+/// The last component of a cwd, which may come from a Windows host.
+fn project_label(cwd: &str) -> String {
+    cwd.trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let rendered: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{k}=\"{}\"", escape_label(v)))
+            .collect();
+        let _ = write!(out, "{{{}}}", rendered.join(","));
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+/// Escapes a label value per the exposition format (backslash, quote, newline).
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 // Payload received from hook scripts via HTTP API
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct TokenReportPayload {
     pub session_id: String,
     pub hostname: String,
@@ -11,10 +11,61 @@ pub struct TokenReportPayload {
     pub cache_read_input_tokens: i64,
     #[serde(default)]
     pub cwd: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    // Client-generated key (e.g. the API message id); repeats are ignored
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+// One turn inside a batched token report
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
+pub struct TokenBatchTurn {
+    pub session_id: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+// Many turns from one host in a single request
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
+pub struct TokenBatchPayload {
+    pub hostname: String,
+    pub turns: Vec<TokenBatchTurn>,
+}
+
+impl TokenBatchTurn {
+    pub fn into_report(self, hostname: &str) -> TokenReportPayload {
+        TokenReportPayload {
+            session_id: self.session_id,
+            hostname: hostname.to_string(),
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            cache_creation_input_tokens: self.cache_creation_input_tokens,
+            cache_read_input_tokens: self.cache_read_input_tokens,
+            cwd: self.cwd,
+            model: self.model,
+            idempotency_key: self.idempotency_key,
+        }
+    }
+}
+
+// Outcome of a batched token report
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct TokenBatchResult {
+    pub accepted: usize,
+    pub duplicates: usize,
 }
 
 // Time-series point for token charts
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct TokenDataPoint {
     pub timestamp: String,
     pub input_tokens: i64,
@@ -25,7 +76,7 @@ pub struct TokenDataPoint {
 }
 
 // Aggregate stats for token stats panel
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct TokenStats {
     pub total_input: i64,
     pub total_output: i64,
@@ -35,38 +86,66 @@ pub struct TokenStats {
     pub turn_count: i64,
     pub avg_input_per_turn: f64,
     pub avg_output_per_turn: f64,
+    pub cost_usd: f64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct UsageBucket {
     pub label: String,
     pub utilization: f64,
     pub resets_at: Option<String>,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct UsageData {
     pub buckets: Vec<UsageBucket>,
     pub error: Option<String>,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct DataPoint {
     pub timestamp: String,
     pub utilization: f64,
 }
 
 // Host-level token breakdown
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct HostBreakdown {
     pub hostname: String,
     pub total_tokens: i64,
     pub turn_count: i64,
     pub last_active: String,
+    pub cost_usd: f64,
+}
+
+// Model-level token breakdown (model is None for turns reported without one)
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct ModelBreakdown {
+    pub model: Option<String>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+    pub total_tokens: i64,
+    pub turn_count: i64,
+    pub session_count: i64,
+    pub last_active: String,
+    pub cost_usd: f64,
+}
+
+// Outcome of backfilling token_snapshots from session transcripts
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct TokenImportResult {
+    pub hostname: String,
+    pub cutoff: String,
+    pub files_scanned: usize,
+    pub turns_imported: usize,
+    pub turns_skipped: usize,
 }
 
 // Per-project token totals (grouped by session cwd)
-#[derive(Serialize, Clone, Debug)]
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct ProjectTokens {
     pub project: String,
     pub total_tokens: i64,
@@ -74,7 +153,8 @@ pub struct ProjectTokens {
 }
 
 // Aggregate session stats (unlimited, for analytics)
-#[derive(Serialize, Clone, Debug)]
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SessionStats {
     pub avg_duration_seconds: f64,
     pub avg_tokens: f64,
@@ -83,7 +163,7 @@ pub struct SessionStats {
 }
 
 // Session-level token breakdown
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SessionBreakdown {
     pub session_id: String,
     pub hostname: String,
@@ -92,10 +172,11 @@ pub struct SessionBreakdown {
     pub first_seen: String,
     pub last_active: String,
     pub project: Option<String>,
+    pub cost_usd: f64,
 }
 
 // Project-level token breakdown (grouped by cwd + hostname)
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct ProjectBreakdown {
     pub project: String,
     pub hostname: String,
@@ -103,9 +184,46 @@ pub struct ProjectBreakdown {
     pub turn_count: i64,
     pub session_count: i64,
     pub last_active: String,
+    pub cost_usd: f64,
 }
 
-#[derive(Serialize, Clone, Debug)]
+// Per-model USD rates (per million tokens) as edited from the frontend
+#[cfg(feature = "gui")]
+#[derive(Deserialize, Clone, Debug, schemars::JsonSchema)]
+pub struct ModelPricingPayload {
+    pub model_pattern: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    pub cache_write_per_mtok: f64,
+    pub cache_read_per_mtok: f64,
+}
+
+// Stored pricing row. model_pattern matches as a substring of the model id; "*" is the fallback.
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct ModelPricing {
+    pub model_pattern: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    pub cache_write_per_mtok: f64,
+    pub cache_read_per_mtok: f64,
+    pub updated_at: String,
+}
+
+// Estimated API cost per model across raw snapshots and hourly aggregates
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct ModelCost {
+    pub model: Option<String>,
+    pub pricing_pattern: Option<String>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+    pub total_tokens: i64,
+    pub turn_count: i64,
+    pub cost_usd: f64,
+}
+
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct BucketStats {
     pub label: String,
     pub current: f64,
@@ -117,10 +235,343 @@ pub struct BucketStats {
     pub sample_count: i64,
 }
 
+// Projected exhaustion of a usage bucket at the current pace
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct UsageForecast {
+    pub label: String,
+    pub current: f64,
+    pub rate_per_hour: f64,
+    pub resets_at: Option<String>,
+    pub exhausted_at: Option<String>,
+    pub minutes_before_reset: Option<i64>,
+    pub sample_count: i64,
+    pub summary: String,
+}
+
+// --- Alerting models ---
+
+// Alert rule as created or edited from the frontend
+#[cfg(any(feature = "gui", test))]
+#[derive(Deserialize, Clone, Debug, schemars::JsonSchema)]
+pub struct AlertRulePayload {
+    pub name: String,
+    pub bucket_label: String,
+    pub threshold: f64,
+    #[serde(default)]
+    pub min_hours_to_reset: Option<f64>,
+    #[serde(default)]
+    pub max_hours_to_reset: Option<f64>,
+    #[serde(default = "default_true")]
+    pub notify_desktop: bool,
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[cfg(any(feature = "gui", test))]
+fn default_true() -> bool {
+    true
+}
+
+// Stored alert rule returned to frontend
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct AlertRule {
+    pub id: i64,
+    pub name: String,
+    pub bucket_label: String,
+    pub threshold: f64,
+    pub min_hours_to_reset: Option<f64>,
+    pub max_hours_to_reset: Option<f64>,
+    pub notify_desktop: bool,
+    pub webhook_url: Option<String>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+// A single alert firing. Also the `usage-alert` event and webhook body.
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct AlertFiring {
+    pub id: i64,
+    pub rule_id: i64,
+    pub rule_name: String,
+    pub bucket_label: String,
+    pub utilization: f64,
+    pub threshold: f64,
+    pub resets_at: Option<String>,
+    pub window_key: String,
+    pub message: String,
+    pub fired_at: String,
+}
+
+// --- Budget models ---
+
+// Token or cost budget as created or edited from the frontend.
+// scope_type is "project" (cwd prefix) or "host"; period is daily/weekly/monthly;
+// metric is "tokens" or "cost" (USD).
+#[cfg(feature = "gui")]
+#[derive(Deserialize, Clone, Debug, schemars::JsonSchema)]
+pub struct BudgetPayload {
+    pub name: String,
+    pub scope_type: String,
+    pub scope_value: String,
+    pub period: String,
+    pub metric: String,
+    pub limit_value: f64,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+// Stored budget
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct Budget {
+    pub id: i64,
+    pub name: String,
+    pub scope_type: String,
+    pub scope_value: String,
+    pub period: String,
+    pub metric: String,
+    pub limit_value: f64,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+// Budget with burn-down for the current period
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct BudgetStatus {
+    pub budget: Budget,
+    pub period_start: String,
+    pub period_end: String,
+    pub consumed: f64,
+    pub percent: f64,
+    pub projected: f64,
+    pub projected_overrun: f64,
+    // When the limit was reached, or will be at the current rate before the
+    // period ends. None for a budget already over its limit whose crossing
+    // wasn't recorded, e.g. because it was disabled at the time.
+    pub exhausted_at: Option<String>,
+}
+
+// Payload of the `budget-threshold` event
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct BudgetThresholdEvent {
+    pub budget_id: i64,
+    pub name: String,
+    pub threshold: i64,
+    pub percent: f64,
+    pub consumed: f64,
+    pub limit_value: f64,
+    pub metric: String,
+    pub period_start: String,
+}
+
+// --- API token models ---
+
+// Per-host API token as listed in the frontend. Only a hash of the token is
+// stored; token_prefix is its first characters, for identification.
+// The scope "*" grants full access.
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub hostname: Option<String>,
+    pub scopes: Vec<String>,
+    pub token_prefix: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+// Returned when a token is issued or rotated; the plaintext is not shown again
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct IssuedApiToken {
+    pub token: String,
+    pub info: ApiToken,
+}
+
+// --- Audit models ---
+
+// One authenticated API request or destructive app command
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: String,
+    /// "api" or "command"
+    pub kind: String,
+    /// "METHOD /route" for requests, the command name otherwise
+    pub action: String,
+    /// API token name, "shared secret", or "app" for commands
+    pub actor: String,
+    pub token_id: Option<i64>,
+    /// Hostname the API token is bound to
+    pub hostname: Option<String>,
+    pub client_addr: Option<String>,
+    /// HTTP status of a request
+    pub status: Option<i64>,
+    /// Request body size as received
+    pub bytes: Option<i64>,
+    /// Command arguments and result as JSON
+    pub detail: Option<String>,
+    /// Why a command failed
+    pub error: Option<String>,
+}
+
+// Filters for listing and exporting the audit log
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct AuditQuery {
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// Substring of the action, e.g. "tokens" or "delete_"
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub actor: Option<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub days: Option<i32>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+// --- Trash models ---
+
+// Rows removed by one delete command, kept until restored or purged
+#[derive(Serialize, Clone, Debug)]
+pub struct TrashBatch {
+    /// Deletion time plus a random suffix, e.g. "20261018T041424Z-3fa9c1"
+    pub id: String,
+    /// "host", "project" or "session"
+    pub kind: String,
+    /// Hostname, cwd or session id that was deleted
+    pub target: String,
+    pub deleted_at: String,
+    pub snapshot_count: i64,
+    pub hourly_count: i64,
+}
+
+// --- Backup models ---
+
+// One backup on disk
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackupInfo {
+    /// Directory name, e.g. "quill-20261018T041424Z"
+    pub name: String,
+    pub path: String,
+    pub created_at: String,
+    /// Database schema_version when the backup was taken
+    pub schema_version: i32,
+    pub quill_version: String,
+    /// Files copied from the session index; 0 if there was none
+    pub index_files: usize,
+    pub has_secret: bool,
+    pub size_bytes: u64,
+}
+
+// --- Redaction models ---
+
+// Stored items rewritten by a re-redaction pass
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RedactionSummary {
+    pub observations: usize,
+    pub tool_actions: usize,
+    /// Relay payloads still waiting to be forwarded
+    pub outbox: usize,
+    /// Session index messages
+    pub messages: usize,
+}
+
+// --- Saved search models ---
+
+// A saved search as created or edited. `query` uses the session search syntax.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SavedSearchPayload {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub filters: crate::sessions::SearchFilters,
+    #[serde(default)]
+    pub watch: bool,
+}
+
+// Stored saved search
+#[derive(Serialize, Clone, Debug)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    pub filters: crate::sessions::SearchFilters,
+    /// Checked against newly indexed messages after every index update
+    pub watch: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Matches a watch has reported and still remembers
+    pub match_count: i64,
+    pub last_matched_at: Option<String>,
+    /// When the watch was turned on or last changed; older messages are
+    /// never reported
+    pub watch_since: Option<String>,
+}
+
+// New messages a watch matched, emitted as `saved-search-matched`
+#[derive(Serialize, Clone, Debug)]
+pub struct SavedSearchMatch {
+    pub search_id: i64,
+    pub name: String,
+    /// New matches in this update; `hits` holds at most the newest 20
+    pub total: usize,
+    pub hits: Vec<crate::sessions::SearchHit>,
+}
+
+// --- Relay models ---
+
+// Accepted payload waiting to be forwarded to the upstream Quill
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub route: String,
+    pub body: String,
+    pub attempts: i64,
+    pub created_at: String,
+}
+
+// Relay mode state shown by `quill relay`
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct RelayStatus {
+    pub upstream: Option<String>,
+    pub pending: i64,
+    pub oldest_queued_at: Option<String>,
+    pub last_error: Option<String>,
+    /// Why forwarding stopped until the relay settings change, if it did
+    pub paused: Option<String>,
+}
+
+// --- Metrics models ---
+
+// All-time token totals for one host (token_snapshots plus token_hourly)
+#[derive(Clone, Debug)]
+pub struct HostTokenTotals {
+    pub hostname: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+}
+
+// Point-in-time values read from SQLite for the /metrics endpoint
+#[derive(Clone, Debug)]
+pub struct MetricsSummary {
+    pub buckets: Vec<UsageBucket>,
+    pub host_tokens: Vec<HostTokenTotals>,
+    pub observation_count: i64,
+    pub unanalyzed_observations: i64,
+    pub learning_runs: Vec<(String, i64)>,
+}
+
 // --- Learning system models ---
 
 // Payload received from observation hook scripts via HTTP API
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct ObservationPayload {
     pub session_id: String,
     pub hook_phase: String,
@@ -134,7 +585,7 @@ pub struct ObservationPayload {
 }
 
 // Payload from session-end hook
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SessionEndPayload {
     pub session_id: String,
     #[serde(default)]
@@ -144,7 +595,7 @@ pub struct SessionEndPayload {
 }
 
 // Payload to record a learning run result
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearningRunPayload {
     pub trigger_mode: String,
     pub observations_analyzed: i64,
@@ -163,7 +614,7 @@ pub struct LearningRunPayload {
 }
 
 // Payload to record learned rule metadata from /learn skill
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearnedRulePayload {
     pub name: String,
     #[serde(default)]
@@ -188,7 +639,7 @@ fn default_confidence() -> f64 {
 }
 
 // Learning run record returned to frontend/API
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearningRun {
     pub id: i64,
     pub trigger_mode: String,
@@ -204,7 +655,7 @@ pub struct LearningRun {
 }
 
 // Learned rule record returned to frontend
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearnedRule {
     pub name: String,
     pub domain: Option<String>,
@@ -221,14 +672,16 @@ pub struct LearnedRule {
 }
 
 // Tool frequency count for status strip
-#[derive(Serialize, Clone, Debug)]
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct ToolCount {
     pub tool_name: String,
     pub count: i64,
 }
 
 // Learning settings
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg(feature = "gui")]
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearningSettings {
     pub enabled: bool,
     pub trigger_mode: String,
@@ -238,10 +691,12 @@ pub struct LearningSettings {
     pub min_confidence: f64,
 }
 
+#[cfg(feature = "gui")]
 fn default_min_confidence() -> f64 {
     0.95
 }
 
+#[cfg(feature = "gui")]
 impl Default for LearningSettings {
     fn default() -> Self {
         Self {
@@ -255,7 +710,7 @@ impl Default for LearningSettings {
 }
 
 // Learning status for GET /api/v1/learning/status
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearningStatus {
     pub observation_count: i64,
     pub unanalyzed_count: i64,
@@ -266,14 +721,14 @@ pub struct LearningStatus {
 // --- Session indexing HTTP payloads ---
 
 /// Notify that a session JSONL file has been created/updated
-#[derive(Deserialize)]
+#[derive(Deserialize, schemars::JsonSchema)]
 pub struct SessionNotifyPayload {
     pub session_id: String,
     pub jsonl_path: String,
 }
 
 /// A single message pushed via the HTTP API
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct SessionMessagePayload {
     pub uuid: String,
     #[serde(rename = "type")]
@@ -286,10 +741,13 @@ pub struct SessionMessagePayload {
     pub tools_used: Vec<String>,
     #[serde(default)]
     pub files_modified: Vec<String>,
+    /// Byte offset in the sender's transcript just past this message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
 }
 
 /// Batch of messages pushed via the HTTP API
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, schemars::JsonSchema)]
 pub struct SessionMessagesPayload {
     pub host: String,
     pub session_id: String,
@@ -297,6 +755,33 @@ pub struct SessionMessagesPayload {
     #[serde(default)]
     pub git_branch: String,
     pub messages: Vec<SessionMessagePayload>,
+    /// Byte offset in the sender's transcript this batch reads up to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+}
+
+/// First line of a streamed NDJSON upload; every later line is a message
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct SessionStreamHeader {
+    pub host: String,
+    pub session_id: String,
+    pub project: String,
+    #[serde(default)]
+    pub git_branch: String,
+    /// Byte offset in the sender's transcript the whole upload reads up to
+    #[serde(default)]
+    pub offset: Option<u64>,
+}
+
+/// How far a remote session has been received, so the sender only uploads the rest
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
+pub struct SessionSyncState {
+    pub host: String,
+    pub session_id: String,
+    pub last_uuid: Option<String>,
+    pub offset: u64,
+    pub message_count: i64,
+    pub updated_at: Option<String>,
 }
 
 // Haiku analysis output item (parsed from JSON)
@@ -356,7 +841,7 @@ pub struct StreamPattern {
 
 // Cached git history snapshot, one per project.
 // `created_at` is DB-populated via DEFAULT, not passed from Rust on insert.
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct GitSnapshot {
     pub project: String,
     pub commit_hash: String,
@@ -365,7 +850,7 @@ pub struct GitSnapshot {
 }
 
 // Phase progress tracking for multi-stream learning runs
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct RunPhase {
     pub name: String,
     pub status: String,
@@ -395,7 +880,7 @@ impl StreamFindings {
 }
 
 // Tagged learning log event for real-time frontend streaming
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LearningLogEvent {
     pub run_id: i64,
     pub message: String,
@@ -449,7 +934,7 @@ pub struct OptimizationOutput {
 
 /// A memory file record returned to frontend
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct MemoryFile {
     pub id: i64,
     pub project_path: String,
@@ -465,7 +950,7 @@ pub struct MemoryFile {
 
 /// An optimization suggestion returned to frontend
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct OptimizationSuggestion {
     pub id: i64,
     pub run_id: i64,
@@ -487,7 +972,7 @@ pub struct OptimizationSuggestion {
 
 /// An optimization run record returned to frontend
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct OptimizationRun {
     pub id: i64,
     pub project_path: String,
@@ -502,7 +987,7 @@ pub struct OptimizationRun {
 
 /// A known project for the memory optimizer
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct KnownProject {
     pub path: String,
     pub name: String,
@@ -513,14 +998,14 @@ pub struct KnownProject {
 
 /// Event payload for memory optimizer log messages
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct MemoryOptimizerLogEvent {
     pub message: String,
 }
 
 /// Event payload for memory optimizer status changes
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct MemoryOptimizerUpdatedEvent {
     pub run_id: i64,
     pub status: String,
@@ -528,7 +1013,7 @@ pub struct MemoryOptimizerUpdatedEvent {
 
 /// Event payload for memory files changed
 #[allow(dead_code)]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct MemoryFilesUpdatedEvent {
     pub project_path: String,
 }
@@ -536,7 +1021,7 @@ pub struct MemoryFilesUpdatedEvent {
 // --- Code change stats models ---
 
 /// Aggregate code change stats for a time range
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct CodeStats {
     pub lines_added: i64,
     pub lines_removed: i64,
@@ -547,7 +1032,7 @@ pub struct CodeStats {
 }
 
 /// Per-language breakdown of code changes
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct LanguageBreakdown {
     pub language: String,
     pub lines: i64,
@@ -555,7 +1040,7 @@ pub struct LanguageBreakdown {
 }
 
 /// Time-bucketed code change data point for charts
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct CodeStatsHistoryPoint {
     pub timestamp: String,
     pub lines_added: i64,
@@ -564,7 +1049,8 @@ pub struct CodeStatsHistoryPoint {
 }
 
 /// Per-session code change stats
-#[derive(Serialize, Clone, Debug)]
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SessionCodeStats {
     pub lines_added: i64,
     pub lines_removed: i64,
@@ -572,7 +1058,7 @@ pub struct SessionCodeStats {
 }
 
 /// Aggregate response time stats for a time range
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct ResponseTimeStats {
     pub avg_response_secs: f64,
     pub peak_response_secs: f64,
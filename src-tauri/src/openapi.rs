@@ -0,0 +1,617 @@
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use schemars::JsonSchema;
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use serde_json::{Map, Value, json};
+
+use crate::models::{
+    BucketStats, CodeStats, CodeStatsHistoryPoint, DataPoint, HostBreakdown, LearnedRulePayload,
+    LearningRun, LearningRunPayload, LearningStatus, ModelBreakdown, ModelCost, ObservationPayload,
+    ProjectBreakdown, ResponseTimeStats, SessionBreakdown, SessionEndPayload,
+    SessionMessagePayload, SessionMessagesPayload, SessionNotifyPayload, SessionStreamHeader,
+    SessionSyncState, TokenBatchPayload, TokenBatchResult, TokenDataPoint, TokenReportPayload,
+    TokenStats, UsageForecast,
+};
+use crate::server::{RANGES, TOKEN_HISTORY_RANGES};
+use crate::sessions::{SearchFacets, SearchResults, SessionContext};
+
+// ── OpenAPI document for the token server ──
+//
+// Request and response schemas come from the `JsonSchema` derives on the
+// models, so field names can't drift from what the handlers accept. The
+// operations themselves are listed by hand; `check_routes` compares them with
+// the router so a new route can't ship undocumented.
+
+struct Param {
+    name: &'static str,
+    description: &'static str,
+    required: bool,
+    schema: Value,
+}
+
+fn param(name: &'static str, description: &'static str) -> Param {
+    Param {
+        name,
+        description,
+        required: false,
+        schema: json!({"type": "string"}),
+    }
+}
+
+fn required(name: &'static str, description: &'static str) -> Param {
+    Param {
+        required: true,
+        ..param(name, description)
+    }
+}
+
+fn integer(name: &'static str, description: &'static str) -> Param {
+    Param {
+        schema: json!({"type": "integer"}),
+        ..param(name, description)
+    }
+}
+
+fn range(allowed: &[&str]) -> Param {
+    Param {
+        schema: json!({"type": "string", "enum": allowed, "default": "24h"}),
+        ..param("range", "Time window")
+    }
+}
+
+fn days() -> Param {
+    Param {
+        schema: json!({"type": "integer", "minimum": 1, "maximum": 3650, "default": 7}),
+        ..param("days", "Number of days to cover")
+    }
+}
+
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    /// API token scope, or None for unauthenticated routes
+    scope: Option<&'static str>,
+    params: Vec<Param>,
+    /// (content type, schema) of the request body
+    body: Option<(&'static str, Value)>,
+    /// (content type, schema) of the 200 response
+    response: (&'static str, Value),
+}
+
+fn op(
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    scope: Option<&'static str>,
+) -> Operation {
+    Operation {
+        method,
+        path,
+        summary,
+        scope,
+        params: Vec::new(),
+        body: None,
+        response: ("text/plain", json!({"type": "string"})),
+    }
+}
+
+impl Operation {
+    fn params(mut self, params: Vec<Param>) -> Self {
+        self.params = params;
+        self
+    }
+
+    fn body(mut self, schema: Value) -> Self {
+        self.body = Some(("application/json", schema));
+        self
+    }
+
+    /// Newline-delimited JSON body; `schema` describes one line.
+    fn ndjson(mut self, schema: Value) -> Self {
+        self.body = Some(("application/x-ndjson", schema));
+        self
+    }
+
+    fn json(mut self, schema: Value) -> Self {
+        self.response = ("application/json", schema);
+        self
+    }
+
+    fn content(mut self, content_type: &'static str) -> Self {
+        self.response = (content_type, json!({"type": "string"}));
+        self
+    }
+
+    fn to_value(&self) -> Value {
+        let (content_type, response_schema) = &self.response;
+        let mut responses = Map::new();
+        responses.insert(
+            "200".into(),
+            json!({
+                "description": "Success",
+                "content": {*content_type: {"schema": response_schema}},
+            }),
+        );
+        if self.body.is_some() || !self.params.is_empty() {
+            responses.insert("400".into(), json!({"description": "Invalid request"}));
+        }
+
+        let mut out = Map::new();
+        out.insert("summary".into(), json!(self.summary));
+        match self.scope {
+            Some(scope) => {
+                out.insert(
+                    "description".into(),
+                    json!(format!(
+                        "Requires the shared secret or an API token with the `{scope}` scope."
+                    )),
+                );
+                out.insert("x-quill-scope".into(), json!(scope));
+                responses.insert(
+                    "401".into(),
+                    json!({"description": "Missing or invalid bearer token"}),
+                );
+                responses.insert(
+                    "403".into(),
+                    json!({"description": "Token lacks the scope or is bound to another host"}),
+                );
+            }
+            None => {
+                out.insert("security".into(), json!([]));
+            }
+        }
+
+        if !self.params.is_empty() {
+            let params: Vec<Value> = self
+                .params
+                .iter()
+                .map(|p| {
+                    json!({
+                        "name": p.name,
+                        "in": "query",
+                        "description": p.description,
+                        "required": p.required,
+                        "schema": p.schema,
+                    })
+                })
+                .collect();
+            out.insert("parameters".into(), Value::Array(params));
+        }
+        if let Some((content_type, body)) = &self.body {
+            out.insert(
+                "requestBody".into(),
+                json!({
+                    "required": true,
+                    "content": {*content_type: {"schema": body}},
+                }),
+            );
+        }
+        out.insert("responses".into(), Value::Object(responses));
+        Value::Object(out)
+    }
+}
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    generator.subschema_for::<T>().to_value()
+}
+
+fn list<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    generator.subschema_for::<Vec<T>>().to_value()
+}
+
+/// Every documented operation, in router order.
+fn operations(g: &mut SchemaGenerator) -> Vec<Operation> {
+    vec![
+        op("GET", "/api/v1/health", "Health check", None),
+        op("GET", "/api/v1/openapi.json", "This document", None).json(json!({"type": "object"})),
+        op(
+            "POST",
+            "/api/v1/tokens",
+            "Record one turn of token usage",
+            Some("tokens:write"),
+        )
+        .body(schema::<TokenReportPayload>(g)),
+        op(
+            "POST",
+            "/api/v1/tokens/batch",
+            "Record up to 500 turns from one host",
+            Some("tokens:write"),
+        )
+        .body(schema::<TokenBatchPayload>(g))
+        .json(schema::<TokenBatchResult>(g)),
+        op(
+            "GET",
+            "/api/v1/usage/forecast",
+            "Projected bucket exhaustion vs. reset time",
+            Some("usage:read"),
+        )
+        .params(vec![param("bucket", "Only this bucket")])
+        .json(list::<UsageForecast>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/usage/history",
+            "Utilization history of one bucket",
+            Some("usage:read"),
+        )
+        .params(vec![required("bucket", "Bucket label"), range(RANGES)])
+        .json(list::<DataPoint>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/usage/stats",
+            "Utilization statistics of one bucket",
+            Some("usage:read"),
+        )
+        .params(vec![required("bucket", "Bucket label"), days()])
+        .json(schema::<BucketStats>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/tokens/history",
+            "Token usage over time",
+            Some("usage:read"),
+        )
+        .params(vec![
+            range(TOKEN_HISTORY_RANGES),
+            param("hostname", "Only this host"),
+            param("session_id", "Only this session"),
+            param("cwd", "Only this project directory"),
+            param("model", "Model id substring, case-insensitive"),
+        ])
+        .json(list::<TokenDataPoint>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/tokens/stats",
+            "Token usage totals",
+            Some("usage:read"),
+        )
+        .params(vec![
+            days(),
+            param("hostname", "Only this host"),
+            param("cwd", "Only this project directory"),
+            param("model", "Model id substring, case-insensitive"),
+        ])
+        .json(schema::<TokenStats>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/hosts",
+            "Token usage per host",
+            Some("usage:read"),
+        )
+        .params(vec![
+            days(),
+            param("model", "Model id substring, case-insensitive"),
+        ])
+        .json(list::<HostBreakdown>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/projects",
+            "Token usage per project",
+            Some("usage:read"),
+        )
+        .params(vec![
+            days(),
+            param("model", "Model id substring, case-insensitive"),
+        ])
+        .json(list::<ProjectBreakdown>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/sessions",
+            "Token usage per session",
+            Some("usage:read"),
+        )
+        .params(vec![
+            days(),
+            param("hostname", "Only this host"),
+            param("model", "Model id substring, case-insensitive"),
+        ])
+        .json(list::<SessionBreakdown>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/models",
+            "Token usage per model",
+            Some("usage:read"),
+        )
+        .params(vec![
+            days(),
+            param("hostname", "Only this host"),
+            param("cwd", "Only this project directory"),
+        ])
+        .json(list::<ModelBreakdown>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/costs",
+            "Estimated API cost per model",
+            Some("usage:read"),
+        )
+        .params(vec![days(), param("hostname", "Only this host")])
+        .json(list::<ModelCost>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/code",
+            "Lines of code changed",
+            Some("usage:read"),
+        )
+        .params(vec![range(RANGES)])
+        .json(schema::<CodeStats>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/code/history",
+            "Lines of code changed over time",
+            Some("usage:read"),
+        )
+        .params(vec![range(RANGES)])
+        .json(list::<CodeStatsHistoryPoint>(g)),
+        op(
+            "GET",
+            "/api/v1/analytics/response-times",
+            "Response and idle times",
+            Some("usage:read"),
+        )
+        .params(vec![range(RANGES)])
+        .json(schema::<ResponseTimeStats>(g)),
+        op(
+            "POST",
+            "/api/v1/learning/observations",
+            "Store a tool-use observation",
+            Some("learning:write"),
+        )
+        .body(schema::<ObservationPayload>(g)),
+        op(
+            "GET",
+            "/api/v1/learning/observations",
+            "Recent observations",
+            Some("learning:read"),
+        )
+        .params(vec![integer(
+            "limit",
+            "At most this many (default 100, max 500)",
+        )])
+        .json(json!({"type": "array", "items": {"type": "object"}})),
+        op(
+            "POST",
+            "/api/v1/learning/session-end",
+            "Notify that a session ended",
+            Some("learning:write"),
+        )
+        .body(schema::<SessionEndPayload>(g)),
+        op(
+            "GET",
+            "/api/v1/learning/status",
+            "Learning system status",
+            Some("learning:read"),
+        )
+        .json(schema::<LearningStatus>(g)),
+        op(
+            "POST",
+            "/api/v1/learning/runs",
+            "Record a learning analysis run",
+            Some("learning:write"),
+        )
+        .body(schema::<LearningRunPayload>(g))
+        .json(json!({
+            "type": "object",
+            "properties": {"id": {"type": "integer"}},
+            "required": ["id"],
+        })),
+        op(
+            "GET",
+            "/api/v1/learning/runs",
+            "Learning run history",
+            Some("learning:read"),
+        )
+        .params(vec![integer(
+            "limit",
+            "At most this many (default 20, max 100)",
+        )])
+        .json(list::<LearningRun>(g)),
+        op(
+            "POST",
+            "/api/v1/learning/rules",
+            "Store a discovered rule",
+            Some("learning:write"),
+        )
+        .body(schema::<LearnedRulePayload>(g)),
+        op(
+            "POST",
+            "/api/v1/sessions/notify",
+            "Index a session JSONL file on this machine",
+            Some("sessions:write"),
+        )
+        .body(schema::<SessionNotifyPayload>(g)),
+        op(
+            "POST",
+            "/api/v1/sessions/messages",
+            "Index messages sent by a remote host",
+            Some("sessions:write"),
+        )
+        .body(schema::<SessionMessagesPayload>(g)),
+        op(
+            "POST",
+            "/api/v1/sessions/messages/stream",
+            "Stream a session's messages as NDJSON: a header line, then one message per line",
+            Some("sessions:write"),
+        )
+        .ndjson(json!({
+            "anyOf": [
+                schema::<SessionStreamHeader>(g),
+                schema::<SessionMessagePayload>(g),
+            ],
+        }))
+        .json(schema::<SessionSyncState>(g)),
+        op(
+            "GET",
+            "/api/v1/sessions/sync",
+            "How far a pushed session has been received",
+            Some("sessions:write"),
+        )
+        .params(vec![
+            required("host", "Host the session was pushed from"),
+            required("session_id", "Session ID"),
+        ])
+        .json(schema::<SessionSyncState>(g)),
+        op(
+            "GET",
+            "/api/v1/sessions/search",
+            "Full-text search of session messages",
+            Some("search:read"),
+        )
+        .params(vec![
+            param(
+                "q",
+                "Query text; supports `project:`, `host:`, `role:`, `branch:`, `session:`, `tool:`, `file:`, `command:`, `after:`/`before:` (e.g. `-7d`), quoted phrases, `-`/`NOT`, `OR` and parentheses",
+            ),
+            integer("page", "Zero-based page (default 0)"),
+            integer("page_size", "Hits per page (default 10, max 100)"),
+            param("project", "Only this project"),
+            param("host", "Only this host"),
+            param("role", "Only `user` or `assistant` messages"),
+            param("git_branch", "Only this branch"),
+            param("session_id", "Only this session"),
+            param("date_from", "Earliest timestamp (RFC 3339)"),
+            param("date_to", "Latest timestamp (RFC 3339)"),
+            param("sort_by", "`relevance` (default) or `date`"),
+        ])
+        .json(schema::<SearchResults>(g)),
+        op(
+            "GET",
+            "/api/v1/sessions/context",
+            "Messages around a search hit",
+            Some("search:read"),
+        )
+        .params(vec![
+            required("session_id", "Session of the hit"),
+            required("message_id", "Message of the hit"),
+            integer("window", "Messages on each side (default 5)"),
+        ])
+        .json(schema::<SessionContext>(g)),
+        op(
+            "GET",
+            "/api/v1/sessions/facets",
+            "Projects and hosts with message counts",
+            Some("search:read"),
+        )
+        .json(schema::<SearchFacets>(g)),
+        op(
+            "GET",
+            "/api/v1/events",
+            "Live event stream (Server-Sent Events)",
+            Some("events:read"),
+        )
+        .params(vec![param(
+            "events",
+            "Comma-separated event names to receive",
+        )])
+        .content("text/event-stream"),
+        op(
+            "GET",
+            "/metrics",
+            "Prometheus metrics",
+            Some("metrics:read"),
+        )
+        .content("text/plain; version=0.0.4"),
+    ]
+}
+
+fn build() -> Value {
+    let mut generator = SchemaSettings::draft2020_12()
+        .with(|s| {
+            s.definitions_path = "/components/schemas".into();
+            s.meta_schema = None;
+        })
+        .into_generator();
+
+    let mut paths = Map::new();
+    for operation in operations(&mut generator) {
+        let item = paths
+            .entry(operation.path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .expect("path item is an object");
+        item.insert(operation.method.to_lowercase(), operation.to_value());
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Quill token server",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "HTTP API used by Quill hook scripts, the MCP server and remote reporters.",
+        },
+        "security": [{"bearer": []}],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearer": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The shared secret or a per-host API token (`qt_...`).",
+                },
+            },
+            "schemas": generator.take_definitions(true),
+        },
+    })
+}
+
+/// The OpenAPI 3.1 document, built on first use.
+pub fn document() -> &'static Value {
+    static DOCUMENT: OnceLock<Value> = OnceLock::new();
+    DOCUMENT.get_or_init(build)
+}
+
+/// Compares the router's (method, path) pairs with the documented operations.
+/// Returns a description of every route missing from either side.
+pub fn check_routes(routes: &[(&str, &str)]) -> Result<(), String> {
+    let mut generator = SchemaGenerator::default();
+    let documented: BTreeSet<(String, String)> = operations(&mut generator)
+        .iter()
+        .map(|o| (o.method.to_string(), o.path.to_string()))
+        .collect();
+    let served: BTreeSet<(String, String)> = routes
+        .iter()
+        .map(|(m, p)| (m.to_string(), p.to_string()))
+        .collect();
+
+    let mut problems = Vec::new();
+    for (method, path) in served.difference(&documented) {
+        problems.push(format!("{method} {path} has no OpenAPI entry"));
+    }
+    for (method, path) in documented.difference(&served) {
+        problems.push(format!("{method} {path} is documented but not routed"));
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_served_route_is_documented() {
+        assert_eq!(crate::server::check_openapi_routes(), Ok(()));
+    }
+
+    #[test]
+    fn missing_and_extra_routes_are_reported() {
+        let operations = operations(&mut SchemaGenerator::default());
+        let mut served: Vec<(&str, &str)> = operations
+            .iter()
+            .map(|o| (o.method, o.path))
+            .filter(|&(_, path)| path != "/api/v1/health")
+            .collect();
+        served.push(("GET", "/api/v1/undocumented"));
+        let err = check_routes(&served).unwrap_err();
+        assert!(
+            err.contains("GET /api/v1/undocumented has no OpenAPI entry"),
+            "{err}"
+        );
+        assert!(
+            err.contains("GET /api/v1/health is documented but not routed"),
+            "{err}"
+        );
+    }
+}
@@ -233,7 +233,7 @@ pub fn get_installed_plugins() -> Result<Vec<InstalledPlugin>, String> {
         }
     }
 
-    plugins.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    plugins.sort_by_key(|p| p.name.to_lowercase());
     Ok(plugins)
 }
 
@@ -299,7 +299,7 @@ pub fn get_marketplaces() -> Result<Vec<Marketplace>, String> {
         });
     }
 
-    marketplaces.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    marketplaces.sort_by_key(|m| m.name.to_lowercase());
     Ok(marketplaces)
 }
 
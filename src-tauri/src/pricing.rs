@@ -0,0 +1,48 @@
+use crate::models::ModelPricing;
+
+/// Pattern used for turns whose model is unknown or matches no other row.
+pub const FALLBACK_PATTERN: &str = "*";
+
+/// Default USD rates per million tokens, seeded into `model_pricing` on first
+/// run: (pattern, input, output, cache write, cache read). Patterns are matched
+/// as substrings of the model id and the longest match wins, so dated releases
+/// like `claude-opus-4-5-20251101` pick up the `opus-4-5` row over `opus`.
+pub const DEFAULT_PRICING: &[(&str, f64, f64, f64, f64)] = &[
+    (FALLBACK_PATTERN, 3.0, 15.0, 3.75, 0.30),
+    ("opus", 15.0, 75.0, 18.75, 1.50),
+    ("opus-4-5", 5.0, 25.0, 6.25, 0.50),
+    ("opus-4-6", 5.0, 25.0, 6.25, 0.50),
+    ("sonnet", 3.0, 15.0, 3.75, 0.30),
+    ("haiku", 1.0, 5.0, 1.25, 0.10),
+    ("3-5-haiku", 0.80, 4.0, 1.0, 0.08),
+    ("3-haiku", 0.25, 1.25, 0.30, 0.03),
+];
+
+/// Picks the pricing row for a model id, falling back to the `*` row.
+pub fn rate_for<'a>(table: &'a [ModelPricing], model: Option<&str>) -> Option<&'a ModelPricing> {
+    let model = model.map(|m| m.to_lowercase()).unwrap_or_default();
+    table
+        .iter()
+        .filter(|p| p.model_pattern != FALLBACK_PATTERN && !model.is_empty())
+        .filter(|p| model.contains(&p.model_pattern))
+        .max_by_key(|p| p.model_pattern.len())
+        .or_else(|| table.iter().find(|p| p.model_pattern == FALLBACK_PATTERN))
+}
+
+/// Estimated USD cost of a set of token counts at the given rate.
+pub fn cost_usd(
+    rate: Option<&ModelPricing>,
+    input: i64,
+    output: i64,
+    cache_creation: i64,
+    cache_read: i64,
+) -> f64 {
+    let Some(rate) = rate else {
+        return 0.0;
+    };
+    (input as f64 * rate.input_per_mtok
+        + output as f64 * rate.output_per_mtok
+        + cache_creation as f64 * rate.cache_write_per_mtok
+        + cache_read as f64 * rate.cache_read_per_mtok)
+        / 1_000_000.0
+}
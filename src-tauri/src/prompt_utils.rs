@@ -19,6 +19,7 @@ pub fn sanitize_for_prompt(s: &str) -> String {
 /// NOTE: This replaces `sanitize_for_prompt` for the memory optimizer.
 /// The learning system still uses `sanitize_for_prompt` which strips
 /// brackets/backticks/newlines — a separate migration.
+#[cfg(feature = "gui")]
 pub fn escape_for_prompt(s: &str) -> String {
     s.replace('&', "&amp;").replace('<', "&lt;")
 }
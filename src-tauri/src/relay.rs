@@ -0,0 +1,326 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::models::{RelayStatus, TokenBatchPayload, TokenBatchTurn, TokenReportPayload};
+use crate::storage::Storage;
+
+// ── Relay mode ──
+//
+// A relay stores everything it ingests like any other instance, and also
+// queues token reports, observations and pushed session messages in the
+// `relay_outbox` table. A background task posts them, oldest first, to the
+// same routes on an upstream Quill, so a team hub can collect what personal
+// instances see. Entries stay queued until the upstream accepts them, so a
+// laptop that was offline catches up when it reconnects.
+
+/// (setting key, environment override) pairs read by `RelayConfig::load`.
+pub const RELAY_SETTINGS: &[(&str, &str)] = &[
+    ("relay.upstream", "QUILL_RELAY_UPSTREAM"),
+    ("relay.token", "QUILL_RELAY_TOKEN"),
+    ("relay.fingerprint", "QUILL_RELAY_FINGERPRINT"),
+];
+
+/// Why forwarding stopped, when the upstream refused the relay token. Set by
+/// the relay task and cleared when it starts again.
+const PAUSED_SETTING: &str = "relay.paused";
+
+pub const TOKENS_ROUTE: &str = "/api/v1/tokens/batch";
+pub const OBSERVATIONS_ROUTE: &str = "/api/v1/learning/observations";
+pub const SESSION_MESSAGES_ROUTE: &str = "/api/v1/sessions/messages";
+
+/// Oldest entries are dropped beyond this, so an upstream that never comes
+/// back can't fill the disk.
+const MAX_OUTBOX_ROWS: i64 = 100_000;
+const BATCH_SIZE: i64 = 100;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct RelayConfig {
+    /// Base URL of the upstream server, without a trailing slash
+    pub upstream: String,
+    pub token: String,
+    pub fingerprint: Option<String>,
+}
+
+impl RelayConfig {
+    /// Reads `relay.*` settings, with `QUILL_RELAY_*` environment variables
+    /// taking precedence. Returns None when no upstream is set.
+    pub fn load(storage: &Storage) -> Result<Option<Self>, String> {
+        let setting = |key: &str| -> Result<Option<String>, String> {
+            let env = RELAY_SETTINGS
+                .iter()
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, var)| std::env::var(var).ok());
+            let value = match env {
+                Some(v) => Some(v),
+                None => storage.get_setting(key)?,
+            };
+            Ok(value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()))
+        };
+
+        let Some(upstream) = setting("relay.upstream")? else {
+            return Ok(None);
+        };
+        validate_setting("relay.upstream", &upstream)?;
+        let token = setting("relay.token")?
+            .ok_or_else(|| "relay.upstream is set but relay.token is not".to_string())?;
+        let fingerprint = setting("relay.fingerprint")?;
+        if let Some(fp) = &fingerprint {
+            validate_setting("relay.fingerprint", fp)?;
+        }
+
+        Ok(Some(Self {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            token,
+            fingerprint,
+        }))
+    }
+
+    fn client(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+        if let Some(fp) = &self.fingerprint {
+            builder = builder.tls_backend_preconfigured(crate::tls::pinned_client_config(fp)?);
+        }
+        builder
+            .build()
+            .map_err(|e| format!("failed to build relay HTTP client: {e}"))
+    }
+}
+
+/// Checks a single non-empty `relay.*` value on its own, so the settings can
+/// be filled in one at a time.
+pub fn validate_setting(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "relay.upstream" => {
+            let url = reqwest::Url::parse(value)
+                .map_err(|e| format!("invalid relay.upstream '{value}': {e}"))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!(
+                    "relay.upstream must be an http or https URL, got '{value}'"
+                ));
+            }
+            Ok(())
+        }
+        "relay.fingerprint" => crate::tls::pinned_client_config(value).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+/// Queues a payload the server has accepted, with secrets redacted. Failures
+/// are logged rather than returned, since the data is already stored locally.
+pub fn enqueue(storage: &Storage, route: &str, payload: &impl Serialize) {
+    let mut value = match serde_json::to_value(payload) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Failed to serialize relay payload for {route}: {e}");
+            return;
+        }
+    };
+    // Redacted like everything stored locally, so secrets don't leave the machine
+    crate::redact::redact_json(&mut value);
+    let body = value.to_string();
+    if let Err(e) = storage.enqueue_outbox(route, &body, MAX_OUTBOX_ROWS) {
+        log::error!("Failed to queue relay payload for {route}: {e}");
+    }
+}
+
+/// Queues newly stored turns as one batch per host, in the order the hosts
+/// first appear. Turns reported without an idempotency key get one, so a
+/// retried forward can't double count.
+pub fn enqueue_token_reports(storage: &Storage, reports: &[&TokenReportPayload]) {
+    let mut batches: Vec<TokenBatchPayload> = Vec::new();
+    for r in reports {
+        let turn = TokenBatchTurn {
+            session_id: r.session_id.clone(),
+            input_tokens: r.input_tokens,
+            output_tokens: r.output_tokens,
+            cache_creation_input_tokens: r.cache_creation_input_tokens,
+            cache_read_input_tokens: r.cache_read_input_tokens,
+            cwd: r.cwd.clone(),
+            model: r.model.clone(),
+            idempotency_key: Some(
+                r.idempotency_key
+                    .clone()
+                    .unwrap_or_else(|| format!("relay-{:032x}", rand::random::<u128>())),
+            ),
+        };
+        match batches.iter_mut().find(|b| b.hostname == r.hostname) {
+            Some(batch) => batch.turns.push(turn),
+            None => batches.push(TokenBatchPayload {
+                hostname: r.hostname.clone(),
+                turns: vec![turn],
+            }),
+        }
+    }
+    for batch in &batches {
+        enqueue(storage, TOKENS_ROUTE, batch);
+    }
+}
+
+pub fn status(storage: &Storage) -> Result<RelayStatus, String> {
+    let upstream = RelayConfig::load(storage)?.map(|c| c.upstream);
+    let (pending, oldest_queued_at, last_error) = storage.get_outbox_summary()?;
+    let paused = storage
+        .get_setting(PAUSED_SETTING)?
+        .filter(|v| !v.is_empty());
+    Ok(RelayStatus {
+        upstream,
+        pending,
+        oldest_queued_at,
+        last_error,
+        paused,
+    })
+}
+
+enum Outcome {
+    Delivered,
+    /// The upstream rejected the payload itself; retrying won't help.
+    Rejected(String),
+    /// The upstream refused the relay token. Nothing can be delivered until
+    /// the relay settings change.
+    Unauthorized(String),
+    /// Network error, rate limit or server error.
+    Retry(String),
+}
+
+/// Why [`drain`] stopped before the outbox was empty.
+enum Stalled {
+    /// Retry after a backoff
+    Failed(String),
+    /// Stop forwarding; see [`Outcome::Unauthorized`]
+    Unauthorized(String),
+}
+
+async fn forward(
+    client: &reqwest::Client,
+    config: &RelayConfig,
+    route: &str,
+    body: String,
+) -> Outcome {
+    let response = client
+        .post(format!("{}{route}", config.upstream))
+        .bearer_auth(&config.token)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await;
+    let response = match response {
+        Ok(r) => r,
+        Err(e) => return Outcome::Retry(format!("request failed: {e}")),
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        return Outcome::Delivered;
+    }
+    let detail = response.text().await.unwrap_or_default();
+    let error = format!("{status}: {}", detail.trim());
+    match status.as_u16() {
+        400 | 404 | 413 | 422 => Outcome::Rejected(error),
+        401 | 403 => Outcome::Unauthorized(error),
+        _ => Outcome::Retry(error),
+    }
+}
+
+/// Forwards the outbox until it is empty or the upstream fails. Returns what
+/// stopped it, if anything.
+async fn drain(
+    storage: &'static Storage,
+    client: &reqwest::Client,
+    config: &RelayConfig,
+) -> Result<(), Stalled> {
+    loop {
+        let entries = tokio::task::block_in_place(|| storage.get_outbox(BATCH_SIZE))
+            .map_err(Stalled::Failed)?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        for entry in entries {
+            match forward(client, config, &entry.route, entry.body).await {
+                Outcome::Delivered => {
+                    tokio::task::block_in_place(|| storage.delete_outbox_entry(entry.id))
+                        .map_err(Stalled::Failed)?;
+                }
+                Outcome::Rejected(e) => {
+                    log::warn!(
+                        "Upstream rejected relayed {} from {}; dropping it: {e}",
+                        entry.route,
+                        entry.created_at
+                    );
+                    tokio::task::block_in_place(|| storage.delete_outbox_entry(entry.id))
+                        .map_err(Stalled::Failed)?;
+                }
+                Outcome::Unauthorized(e) => {
+                    tokio::task::block_in_place(|| storage.fail_outbox_entry(entry.id, &e))
+                        .map_err(Stalled::Failed)?;
+                    return Err(Stalled::Unauthorized(e));
+                }
+                Outcome::Retry(e) => {
+                    // Keep order: nothing newer is sent until this one goes through
+                    tokio::task::block_in_place(|| storage.fail_outbox_entry(entry.id, &e))
+                        .map_err(Stalled::Failed)?;
+                    return Err(Stalled::Failed(e));
+                }
+            }
+        }
+    }
+}
+
+/// Forwards queued payloads for as long as the process runs, backing off
+/// exponentially while the upstream is unreachable. Stops for good, marking
+/// the relay paused, when the upstream refuses the token.
+pub async fn run(storage: &'static Storage, config: RelayConfig) {
+    let client = match config.client() {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Relay disabled: {e}");
+            return;
+        }
+    };
+    log::info!("Relaying ingested data to {}", config.upstream);
+    if let Err(e) = storage.set_setting(PAUSED_SETTING, "") {
+        log::warn!("Failed to clear relay pause: {e}");
+    }
+
+    let mut failures: u32 = 0;
+    loop {
+        let delay = match drain(storage, &client, &config).await {
+            Ok(()) => {
+                if failures > 0 {
+                    log::info!("Relay upstream reachable again; outbox drained");
+                }
+                failures = 0;
+                POLL_INTERVAL
+            }
+            Err(Stalled::Unauthorized(e)) => {
+                log::error!(
+                    "Relay to {} paused: the upstream refused relay.token ({e}). \
+                     Set a valid token and restart to resume",
+                    config.upstream
+                );
+                let reason = format!("upstream refused relay.token ({e})");
+                if let Err(e) = storage.set_setting(PAUSED_SETTING, &reason) {
+                    log::warn!("Failed to record relay pause: {e}");
+                }
+                return;
+            }
+            Err(Stalled::Failed(e)) => {
+                failures = failures.saturating_add(1);
+                let delay = POLL_INTERVAL
+                    .saturating_mul(2u32.saturating_pow(failures - 1))
+                    .min(MAX_BACKOFF);
+                log::warn!(
+                    "Relay to {} failed ({e}); retrying in {}s",
+                    config.upstream,
+                    delay.as_secs()
+                );
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
@@ -0,0 +1,41 @@
+use std::future::Future;
+
+// ── Async runtime ──
+//
+// The desktop app runs background tasks on Tauri's tokio runtime. Without
+// the `gui` feature there is no Tauri, so the daemon and CLI keep a runtime
+// of their own.
+
+#[cfg(feature = "gui")]
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tauri::async_runtime::spawn(future);
+}
+
+#[cfg(feature = "gui")]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tauri::async_runtime::block_on(future)
+}
+
+#[cfg(not(feature = "gui"))]
+static RUNTIME: std::sync::LazyLock<tokio::runtime::Runtime> = std::sync::LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the tokio runtime")
+});
+
+#[cfg(not(feature = "gui"))]
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    RUNTIME.spawn(future);
+}
+
+#[cfg(not(feature = "gui"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.block_on(future)
+}
@@ -0,0 +1,183 @@
+use chrono::DateTime;
+
+use crate::events::EventBus;
+use crate::models::{SavedSearch, SavedSearchMatch, SavedSearchPayload};
+use crate::search_query;
+use crate::sessions::SessionIndex;
+use crate::storage::Storage;
+
+// ── Saved searches ──
+//
+// A saved search names a query in the session search syntax plus
+// `SearchFilters`. One marked as a watch is re-run after every index update
+// (notify hook, message uploads, the watcher and the startup scan) against
+// only the messages that update indexed. Matches it hasn't reported before
+// are emitted as `saved-search-matched` and shown as a desktop notification.
+// A watch only reports messages written after its `watch_since` mark, and
+// the first scan of an empty index isn't checked at all, so building or
+// rebuilding the index doesn't replay history as notifications.
+
+pub const MATCHED_EVENT: &str = "saved-search-matched";
+/// Hits carried in one event; `total` still counts all of them.
+const MAX_EVENT_HITS: usize = 20;
+/// Matches looked at per watch and update, newest first.
+const MAX_CHECKED_HITS: usize = 100;
+/// Reported message ids remembered per watch, so a re-indexed message isn't
+/// reported twice.
+const MAX_REMEMBERED_MATCHES: usize = 1000;
+
+/// Checks the payload and that its query parses.
+pub fn validate(payload: &SavedSearchPayload) -> Result<(), String> {
+    if payload.name.trim().is_empty() {
+        return Err("Saved search name cannot be empty".to_string());
+    }
+    if payload.query.trim().is_empty() {
+        return Err("Saved search query cannot be empty".to_string());
+    }
+    search_query::parse(&payload.query)?;
+    if let Some(role) = &payload.filters.role
+        && role != "user"
+        && role != "assistant"
+    {
+        return Err(format!("Invalid role filter '{role}'"));
+    }
+    Ok(())
+}
+
+pub fn save(
+    storage: &Storage,
+    id: Option<i64>,
+    payload: &SavedSearchPayload,
+) -> Result<i64, String> {
+    validate(payload)?;
+    storage.save_search(id, payload)
+}
+
+/// Finds a saved search by name, or by id when `key` is a number.
+pub fn find(storage: &Storage, key: &str) -> Result<SavedSearch, String> {
+    let searches = storage.list_saved_searches()?;
+    let id = key.parse::<i64>().ok();
+    searches
+        .into_iter()
+        .find(|s| s.name == key || Some(s.id) == id)
+        .ok_or_else(|| format!("No saved search '{key}'"))
+}
+
+/// Runs every watch against messages an index update just committed. Errors
+/// are logged, since indexing itself succeeded.
+pub fn check_watches(
+    storage: &Storage,
+    idx: &SessionIndex,
+    events: &EventBus,
+    message_ids: &[String],
+) {
+    if message_ids.is_empty() {
+        return;
+    }
+    let watches: Vec<SavedSearch> = match storage.list_saved_searches() {
+        Ok(searches) => searches.into_iter().filter(|s| s.watch).collect(),
+        Err(e) => {
+            log::warn!("Failed to load saved searches: {e}");
+            return;
+        }
+    };
+    if watches.is_empty() {
+        return;
+    }
+    // The reader reloads on its own only after a delay
+    if let Err(e) = idx.reader.reload() {
+        log::warn!("Failed to reload session index for watches: {e}");
+        return;
+    }
+
+    for watch in &watches {
+        match check_watch(storage, idx, watch, message_ids) {
+            Ok(Some(matched)) => {
+                log::info!(
+                    "Saved search '{}' matched {} new messages",
+                    watch.name,
+                    matched.total
+                );
+                events.notify(
+                    &format!("Quill: {}", watch.name),
+                    &notification_body(&matched),
+                );
+                events.emit(MATCHED_EVENT, &matched);
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to check saved search '{}': {e}", watch.name),
+        }
+    }
+}
+
+fn check_watch(
+    storage: &Storage,
+    idx: &SessionIndex,
+    watch: &SavedSearch,
+    message_ids: &[String],
+) -> Result<Option<SavedSearchMatch>, String> {
+    let mut results =
+        idx.search_messages(&watch.query, &watch.filters, message_ids, MAX_CHECKED_HITS)?;
+    if let Some(since) = &watch.watch_since {
+        let since = DateTime::parse_from_rfc3339(since)
+            .map_err(|e| format!("Invalid watch_since '{since}': {e}"))?;
+        // Re-indexed history, and messages without a timestamp, predate it
+        results
+            .hits
+            .retain(|h| DateTime::parse_from_rfc3339(&h.timestamp).is_ok_and(|t| t >= since));
+    }
+    if results.hits.is_empty() {
+        return Ok(None);
+    }
+    let matched: Vec<(String, String)> = results
+        .hits
+        .iter()
+        .map(|h| (h.message_id.clone(), h.session_id.clone()))
+        .collect();
+    let new = storage.record_saved_search_matches(watch.id, &matched, MAX_REMEMBERED_MATCHES)?;
+    let mut hits: Vec<_> = results
+        .hits
+        .into_iter()
+        .filter(|h| new.contains(&h.message_id))
+        .collect();
+    if hits.is_empty() {
+        return Ok(None);
+    }
+    let total = hits.len();
+    hits.truncate(MAX_EVENT_HITS);
+    Ok(Some(SavedSearchMatch {
+        search_id: watch.id,
+        name: watch.name.clone(),
+        total,
+        hits,
+    }))
+}
+
+fn notification_body(matched: &SavedSearchMatch) -> String {
+    let count = if matched.total == 1 {
+        "1 new match".to_string()
+    } else {
+        format!("{} new matches", matched.total)
+    };
+    // Tool-only messages have no text, so fall back to what they ran
+    let excerpt = matched.hits.first().and_then(|h| {
+        [
+            &h.content,
+            &h.commands_run,
+            &h.code_changes,
+            &h.files_modified,
+        ]
+        .into_iter()
+        .find_map(|text| text.lines().find(|l| !l.trim().is_empty()))
+    });
+    match excerpt {
+        Some(line) => {
+            let mut excerpt: String = line.trim().chars().take(100).collect();
+            if excerpt.len() < line.trim().len() {
+                excerpt.push('…');
+            }
+            format!("{count}: {excerpt}")
+        }
+        None => count,
+    }
+}
@@ -0,0 +1,649 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+
+// ── Search query language ──
+//
+// One string for everything the session search can filter on, e.g.
+//
+//   project:quill branch:main role:assistant after:-7d tool:Bash
+//   file:src/storage.rs "migration" -test (cargo OR npm)
+//
+// Terms are ANDed; `OR` (binds looser than AND), `-` or `NOT`, and
+// parentheses combine them. Quoted text is a phrase, and `field:"a b"`
+// quotes a value. A word whose prefix isn't a known field is searched as
+// text, so `http://...` still works; a near miss such as `projet:` is an
+// error rather than a silent text search.
+
+/// A parsed query, resolved to absolute times.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+    /// Words or a phrase, searched across the message text and tool fields
+    Text(String),
+    Field(QueryField, String),
+    /// At or after this epoch second
+    After(i64),
+    /// Before this epoch second
+    Before(i64),
+    Not(Box<Clause>),
+    And(Vec<Clause>),
+    Or(Vec<Clause>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryField {
+    Project,
+    Host,
+    Role,
+    Branch,
+    Session,
+    Tool,
+    File,
+    Command,
+}
+
+/// Field names, including the index field names the old parser accepted.
+const FIELDS: &[&str] = &[
+    "project",
+    "host",
+    "role",
+    "branch",
+    "git_branch",
+    "session",
+    "session_id",
+    "tool",
+    "tools_used",
+    "file",
+    "files_modified",
+    "command",
+    "cmd",
+    "commands_run",
+    "after",
+    "before",
+];
+
+const DATE_FORMS: &str =
+    "a date like 2026-10-01, a relative time like -7d, -12h or -2w, today or yesterday";
+
+/// Parses a query. Blank input gives `None`, which matches everything.
+pub fn parse(query: &str) -> Result<Option<Clause>, String> {
+    parse_at(query, Local::now()).map_err(|e| format!("Invalid query: {e}"))
+}
+
+fn parse_at(query: &str, now: DateTime<Local>) -> Result<Option<Clause>, String> {
+    let tokens = lex(query)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        now,
+    };
+    let clause = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        // parse_or only stops early at a ')' it didn't open
+        return Err(format!("unmatched ')' at column {}", token.column));
+    }
+    Ok(Some(clause))
+}
+
+// ── Lexer ──
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Open,
+    Close,
+    Minus,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Phrase(String),
+    Field(String, String),
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: Kind,
+    /// 1-based character column, for error messages
+    column: usize,
+}
+
+fn lex(query: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let kind = match c {
+            '(' => {
+                depth += 1;
+                i += 1;
+                Kind::Open
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                i += 1;
+                Kind::Close
+            }
+            '"' => {
+                let (text, end) = read_quoted(&chars, i)?;
+                i = end;
+                Kind::Phrase(text)
+            }
+            '-' if chars
+                .get(i + 1)
+                .is_some_and(|n| !n.is_whitespace() && *n != ')') =>
+            {
+                i += 1;
+                Kind::Minus
+            }
+            _ => {
+                let start = i;
+                // A ')' inside a word closes a group only if one is open,
+                // so `main()` stays one word
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && chars[i] != '"'
+                    && !(chars[i] == ')' && depth > 0)
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                match word.as_str() {
+                    "AND" => Kind::And,
+                    "OR" => Kind::Or,
+                    "NOT" => Kind::Not,
+                    _ => match field_prefix(&word) {
+                        Some(name) => {
+                            let value = &word[name.len() + 1..];
+                            if !value.is_empty() {
+                                Kind::Field(name, value.to_string())
+                            } else if chars.get(i) == Some(&'"') {
+                                let (text, end) = read_quoted(&chars, i)?;
+                                i = end;
+                                Kind::Field(name, text)
+                            } else {
+                                return Err(format!(
+                                    "'{name}:' at column {column} needs a value, e.g. {name}:{}",
+                                    example_value(&name)
+                                ));
+                            }
+                        }
+                        None => {
+                            check_unknown_field(&word, column)?;
+                            Kind::Word(word)
+                        }
+                    },
+                }
+            }
+        };
+        tokens.push(Token { kind, column });
+    }
+    Ok(tokens)
+}
+
+/// Reads a quoted string starting at the opening quote. Returns the text and
+/// the index after the closing quote. `\"` and `\\` are escapes.
+fn read_quoted(chars: &[char], open: usize) -> Result<(String, usize), String> {
+    let mut text = String::new();
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((text, i + 1)),
+            '\\' if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err(format!("unclosed quote at column {}", open + 1))
+}
+
+/// The field name if `word` starts with `<known field>:`, lowercased.
+fn field_prefix(word: &str) -> Option<String> {
+    let (name, _) = word.split_once(':')?;
+    let name = name.to_ascii_lowercase();
+    FIELDS.contains(&name.as_str()).then_some(name)
+}
+
+/// Rejects `name:value` where `name` is an edit away from a field (two for
+/// longer names), since that is almost always a typo. Anything further off,
+/// like `todo:` or `test:`, is searched as text.
+fn check_unknown_field(word: &str, column: usize) -> Result<(), String> {
+    let Some((name, value)) = word.split_once(':') else {
+        return Ok(());
+    };
+    if name.len() < 3 || value.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Ok(());
+    }
+    let name = name.to_ascii_lowercase();
+    let closest = FIELDS
+        .iter()
+        .map(|f| (edit_distance(&name, f), *f))
+        .min()
+        .filter(|(d, _)| *d <= if name.len() >= 6 { 2 } else { 1 });
+    match closest {
+        Some((_, field)) => Err(format!(
+            "unknown field '{name}:' at column {column}; did you mean '{field}:'? Quote the term to search for it as text"
+        )),
+        None => Ok(()),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev + usize::from(ca != *cb);
+            prev = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn example_value(field: &str) -> &'static str {
+    match field {
+        "project" => "quill",
+        "host" => "laptop",
+        "role" => "assistant",
+        "branch" | "git_branch" => "main",
+        "session" | "session_id" => "<session-id>",
+        "tool" | "tools_used" => "Bash",
+        "file" | "files_modified" => "src/main.rs",
+        "after" | "before" => "-7d",
+        _ => "cargo",
+    }
+}
+
+// ── Parser ──
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    now: DateTime<Local>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    /// `and ("OR" and)*`
+    fn parse_or(&mut self) -> Result<Clause, String> {
+        let mut terms = vec![self.parse_and()?];
+        while let Some(token) = self.peek()
+            && token.kind == Kind::Or
+        {
+            let column = token.column;
+            self.pos += 1;
+            if matches!(
+                self.peek().map(|t| &t.kind),
+                None | Some(Kind::Close | Kind::Or)
+            ) {
+                return Err(format!("'OR' at column {column} needs a term after it"));
+            }
+            terms.push(self.parse_and()?);
+        }
+        Ok(flatten(terms, Clause::Or))
+    }
+
+    /// `unary+`, with optional `AND` between terms
+    fn parse_and(&mut self) -> Result<Clause, String> {
+        let mut terms = Vec::new();
+        while let Some(token) = self.peek() {
+            match token.kind {
+                Kind::Close | Kind::Or => break,
+                Kind::And => {
+                    let column = token.column;
+                    self.pos += 1;
+                    if terms.is_empty() {
+                        return Err(format!("'AND' at column {column} needs a term before it"));
+                    }
+                    if matches!(
+                        self.peek().map(|t| &t.kind),
+                        None | Some(Kind::Close | Kind::Or | Kind::And)
+                    ) {
+                        return Err(format!("'AND' at column {column} needs a term after it"));
+                    }
+                }
+                _ => terms.push(self.parse_unary()?),
+            }
+        }
+        if terms.is_empty() {
+            return Err(match self.peek() {
+                Some(t) if t.kind == Kind::Or => {
+                    format!("'OR' at column {} needs a term before it", t.column)
+                }
+                Some(t) => format!("unexpected ')' at column {}", t.column),
+                None => "query ends where a term was expected".to_string(),
+            });
+        }
+        Ok(flatten(terms, Clause::And))
+    }
+
+    /// `("-" | "NOT") unary | primary`
+    fn parse_unary(&mut self) -> Result<Clause, String> {
+        let token = self.peek().expect("caller checked for a token");
+        if matches!(token.kind, Kind::Minus | Kind::Not) {
+            let (op, column) = (
+                if token.kind == Kind::Minus {
+                    "-"
+                } else {
+                    "NOT"
+                },
+                token.column,
+            );
+            self.pos += 1;
+            if matches!(
+                self.peek().map(|t| &t.kind),
+                None | Some(Kind::Close | Kind::Or | Kind::And)
+            ) {
+                return Err(format!("'{op}' at column {column} needs a term after it"));
+            }
+            return Ok(Clause::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Clause, String> {
+        let now = self.now;
+        let token = self.next().expect("caller checked for a token");
+        let column = token.column;
+        match token.kind.clone() {
+            Kind::Open => {
+                if self.peek().is_some_and(|t| t.kind == Kind::Close) {
+                    return Err(format!("empty parentheses at column {column}"));
+                }
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(t) if t.kind == Kind::Close => Ok(inner),
+                    _ => Err(format!("'(' at column {column} is never closed")),
+                }
+            }
+            Kind::Word(text) | Kind::Phrase(text) => Ok(Clause::Text(text)),
+            Kind::Field(name, value) => field_clause(&name, &value, column, now),
+            // parse_and and parse_unary consume these before calling here
+            Kind::Close | Kind::Minus | Kind::And | Kind::Or | Kind::Not => {
+                Err(format!("unexpected token at column {column}"))
+            }
+        }
+    }
+}
+
+/// Collapses a single term, so `a` isn't wrapped in a one-item AND.
+fn flatten(mut terms: Vec<Clause>, wrap: fn(Vec<Clause>) -> Clause) -> Clause {
+    if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        wrap(terms)
+    }
+}
+
+fn field_clause(
+    name: &str,
+    value: &str,
+    column: usize,
+    now: DateTime<Local>,
+) -> Result<Clause, String> {
+    let field = match name {
+        "project" => QueryField::Project,
+        "host" => QueryField::Host,
+        "role" => {
+            if value != "user" && value != "assistant" {
+                return Err(format!(
+                    "role: at column {column} must be user or assistant, not '{value}'"
+                ));
+            }
+            QueryField::Role
+        }
+        "branch" | "git_branch" => QueryField::Branch,
+        "session" | "session_id" => QueryField::Session,
+        "tool" | "tools_used" => QueryField::Tool,
+        "file" | "files_modified" => QueryField::File,
+        "command" | "cmd" | "commands_run" => QueryField::Command,
+        "after" | "before" => {
+            let secs = parse_time(value, now).ok_or_else(|| {
+                format!("{name}: at column {column} expects {DATE_FORMS}, not '{value}'")
+            })?;
+            return Ok(if name == "after" {
+                Clause::After(secs)
+            } else {
+                Clause::Before(secs)
+            });
+        }
+        _ => unreachable!("lex only produces fields from FIELDS"),
+    };
+    Ok(Clause::Field(field, value.to_string()))
+}
+
+/// Resolves a date to epoch seconds. Calendar dates are local midnight;
+/// relative times count back from `now`.
+fn parse_time(value: &str, now: DateTime<Local>) -> Option<i64> {
+    let midnight = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(|dt| dt.timestamp())
+    };
+    match value {
+        "today" => return midnight(now.date_naive()),
+        "yesterday" => return midnight(now.date_naive().pred_opt()?),
+        _ => {}
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return midnight(date);
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.timestamp());
+    }
+    let relative = value.strip_prefix('-').unwrap_or(value);
+    let unit = relative.chars().last()?;
+    let n: i64 = relative[..relative.len() - unit.len_utf8()].parse().ok()?;
+    let span = match unit {
+        'h' => Duration::try_hours(n)?,
+        'd' => Duration::try_days(n)?,
+        'w' => Duration::try_weeks(n)?,
+        _ => return None,
+    };
+    Some((now - span).timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap()
+    }
+
+    fn parsed(query: &str) -> Clause {
+        parse_at(query, now()).unwrap().unwrap()
+    }
+
+    fn error(query: &str) -> String {
+        parse_at(query, now()).unwrap_err()
+    }
+
+    fn text(s: &str) -> Clause {
+        Clause::Text(s.to_string())
+    }
+
+    fn field(f: QueryField, s: &str) -> Clause {
+        Clause::Field(f, s.to_string())
+    }
+
+    #[test]
+    fn blank_query_matches_everything() {
+        assert_eq!(parse_at("   ", now()), Ok(None));
+    }
+
+    #[test]
+    fn field_terms() {
+        assert_eq!(
+            parsed("project:quill role:assistant tool:Bash"),
+            Clause::And(vec![
+                field(QueryField::Project, "quill"),
+                field(QueryField::Role, "assistant"),
+                field(QueryField::Tool, "Bash"),
+            ])
+        );
+        assert_eq!(
+            parsed(r#"branch:"feature x" git_branch:main"#),
+            Clause::And(vec![
+                field(QueryField::Branch, "feature x"),
+                field(QueryField::Branch, "main"),
+            ])
+        );
+        assert_eq!(
+            parsed("file:src/storage.rs"),
+            field(QueryField::File, "src/storage.rs")
+        );
+        // Not a field, so searched as text
+        assert_eq!(parsed("todo:fix"), text("todo:fix"));
+        assert_eq!(parsed("http://localhost"), text("http://localhost"));
+        // A ')' with no group open is part of the word
+        assert_eq!(parsed("main()"), text("main()"));
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(
+            parsed("cargo -test"),
+            Clause::And(vec![text("cargo"), Clause::Not(Box::new(text("test")))])
+        );
+        assert_eq!(
+            parsed("NOT role:user"),
+            Clause::Not(Box::new(field(QueryField::Role, "user")))
+        );
+        // A lone hyphen is a word, not negation
+        assert_eq!(
+            parsed("a - b"),
+            Clause::And(vec![text("a"), text("-"), text("b")])
+        );
+    }
+
+    #[test]
+    fn or_of_two_terms() {
+        assert_eq!(parsed("a OR b"), Clause::Or(vec![text("a"), text("b")]));
+    }
+
+    #[test]
+    fn or_binds_looser_than_implicit_and() {
+        assert_eq!(
+            parsed("a b OR c"),
+            Clause::Or(vec![Clause::And(vec![text("a"), text("b")]), text("c")])
+        );
+        assert_eq!(
+            parsed("a AND b OR c d"),
+            Clause::Or(vec![
+                Clause::And(vec![text("a"), text("b")]),
+                Clause::And(vec![text("c"), text("d")]),
+            ])
+        );
+        assert_eq!(
+            parsed("a (b OR c)"),
+            Clause::And(vec![text("a"), Clause::Or(vec![text("b"), text("c")])])
+        );
+    }
+
+    #[test]
+    fn quoted_phrases() {
+        assert_eq!(parsed(r#""run the migration""#), text("run the migration"));
+        assert_eq!(parsed(r#""say \"hi\"""#), text(r#"say "hi""#));
+    }
+
+    #[test]
+    fn relative_dates() {
+        let now = now();
+        assert_eq!(
+            parsed("after:7d"),
+            Clause::After((now - Duration::days(7)).timestamp())
+        );
+        assert_eq!(
+            parsed("after:-2w"),
+            Clause::After((now - Duration::weeks(2)).timestamp())
+        );
+        assert_eq!(
+            parsed("before:-12h"),
+            Clause::Before((now - Duration::hours(12)).timestamp())
+        );
+        let midnight = Local.with_ymd_and_hms(2026, 10, 14, 0, 0, 0).unwrap();
+        assert_eq!(
+            parsed("after:yesterday"),
+            Clause::After(midnight.timestamp())
+        );
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        assert_eq!(error(r#"cargo "build"#), "unclosed quote at column 7");
+        assert_eq!(error(r#"branch:"main"#), "unclosed quote at column 8");
+    }
+
+    #[test]
+    fn unknown_field_is_an_error() {
+        assert_eq!(
+            error("projet:quill"),
+            "unknown field 'projet:' at column 1; did you mean 'project:'? \
+             Quote the term to search for it as text"
+        );
+        assert_eq!(
+            error("a hostt:laptop"),
+            "unknown field 'hostt:' at column 3; did you mean 'host:'? \
+             Quote the term to search for it as text"
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        assert_eq!(
+            error("role:admin"),
+            "role: at column 1 must be user or assistant, not 'admin'"
+        );
+        assert_eq!(
+            error("after:7y"),
+            format!("after: at column 1 expects {DATE_FORMS}, not '7y'")
+        );
+        assert_eq!(
+            error("project:"),
+            "'project:' at column 1 needs a value, e.g. project:quill"
+        );
+    }
+
+    #[test]
+    fn dangling_operators_are_errors() {
+        assert_eq!(error("a OR"), "'OR' at column 3 needs a term after it");
+        assert_eq!(error("OR a"), "'OR' at column 1 needs a term before it");
+        assert_eq!(error("(a b"), "'(' at column 1 is never closed");
+        assert_eq!(error("(a))"), "unmatched ')' at column 4");
+        assert_eq!(error("()"), "empty parentheses at column 1");
+    }
+
+    #[test]
+    fn parse_prefixes_errors() {
+        assert_eq!(
+            parse(r#""open"#).unwrap_err(),
+            "Invalid query: unclosed quote at column 1"
+        );
+    }
+}
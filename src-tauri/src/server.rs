@@ -1,29 +1,46 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::Instant;
 
 use axum::{
-    Json, Router,
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
-    routing::{get, post},
+    Extension, Json, Router,
+    extract::{ConnectInfo, DefaultBodyLimit, MatchedPath, Request, State},
+    handler::Handler,
+    http::Method,
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{MethodFilter, MethodRouter, on},
 };
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use ipnet::IpNet;
 use parking_lot::Mutex;
 use subtle::ConstantTimeEq;
-use tokio::net::TcpListener;
-
-use tauri::Emitter;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
 
+use crate::events::EventBus;
+use crate::metrics::Metrics;
 use crate::models::{
-    LearnedRulePayload, LearningRunPayload, ObservationPayload, SessionEndPayload,
-    SessionMessagesPayload, SessionNotifyPayload, TokenReportPayload,
+    AuditEntry, LearnedRulePayload, LearningRunPayload, ObservationPayload, SessionEndPayload,
+    SessionMessagePayload, SessionMessagesPayload, SessionNotifyPayload, SessionStreamHeader,
+    TokenBatchPayload, TokenBatchResult, TokenReportPayload,
 };
 use crate::sessions;
 use crate::storage::Storage;
 
-const DEFAULT_PORT: u16 = 19876;
+pub const DEFAULT_PORT: u16 = 19876;
 const MAX_REQUESTS: usize = 100;
+const MAX_TURNS_PER_BATCH: usize = 500;
+const MAX_TRACKED_CLIENTS: usize = 1024;
 const RATE_WINDOW_SECS: u64 = 60;
 const MAX_STRING_LEN: usize = 256;
 const MAX_CWD_LEN: usize = 4096;
@@ -36,36 +53,170 @@ const MAX_SESSION_MSG_REQUESTS: usize = 100;
 const MAX_PATH_LEN: usize = 4096;
 const MAX_CONTENT_LEN: usize = 1_000_000;
 const MAX_MESSAGES_PER_BATCH: usize = 500;
+/// Largest batch body, as sent or after gzip decompression
+const MAX_DECODED_BODY: usize = 32 * 1024 * 1024;
+/// Limits on one streamed NDJSON upload, after decompression
+const MAX_STREAM_BYTES: usize = 512 * 1024 * 1024;
+const MAX_STREAM_LINE: usize = 2 * MAX_CONTENT_LEN;
+const MAX_STREAMED_MESSAGES: usize = 100_000;
+/// Decompressed bytes read from a streamed upload at a time
+const STREAM_READ_PIECE: usize = 64 * 1024;
+const MAX_EVENT_STREAMS: usize = 16;
+/// How long a new connection may take to send its first byte and finish TLS
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
 struct ServerState {
     storage: &'static Storage,
     secret: String,
-    rate_limiter: Mutex<VecDeque<Instant>>,
+    /// Token report windows, keyed by API token or client address
+    rate_limiter: Mutex<HashMap<String, VecDeque<Instant>>>,
     obs_rate_limiter: Mutex<VecDeque<Instant>>,
     session_rate_limiter: Mutex<VecDeque<Instant>>,
-    app_handle: tauri::AppHandle,
+    events: EventBus,
     session_index: Option<Arc<sessions::SessionIndex>>,
+    metrics: Metrics,
+    event_streams: AtomicUsize,
+    /// Whether accepted payloads are queued for an upstream Quill
+    relay: bool,
+}
+
+/// Identity behind an authenticated request.
+enum Caller {
+    /// The shared secret written by the desktop app: full access, any host.
+    Shared,
+    /// A per-host API token, optionally bound to one hostname. `forwards` is
+    /// set when it holds the relay scope, letting an unbound token report for
+    /// any host.
+    Token {
+        id: i64,
+        hostname: Option<String>,
+        forwards: bool,
+    },
 }
 
-fn check_auth(headers: &HeaderMap, secret: &str) -> bool {
+impl Caller {
+    /// Whether this caller may submit data at all: unbound tokens need the
+    /// relay scope, or they could file reports as any machine.
+    fn may_report(&self) -> bool {
+        match self {
+            Caller::Shared => true,
+            Caller::Token {
+                hostname, forwards, ..
+            } => hostname.is_some() || *forwards,
+        }
+    }
+
+    /// Whether this caller may submit data on behalf of `hostname`.
+    fn may_report_as(&self, hostname: &str) -> bool {
+        match self {
+            Caller::Token {
+                hostname: Some(bound),
+                ..
+            } => bound == hostname,
+            _ => self.may_report(),
+        }
+    }
+}
+
+type AuthError = (StatusCode, &'static str);
+
+/// Credential a request presented, before any scope check.
+#[derive(Clone)]
+enum Credential {
+    Shared,
+    Token(crate::models::ApiToken),
+}
+
+/// Outcome of [`authenticate`], which `audit_requests` runs once per request
+/// and hands to the handler as a request extension.
+#[derive(Clone)]
+struct Authentication(Result<Credential, AuthError>);
+
+/// Identifies the bearer token as the shared secret or an active API token.
+fn authenticate(state: &ServerState, headers: &HeaderMap) -> Result<Credential, AuthError> {
+    const UNAUTHORIZED: AuthError = (StatusCode::UNAUTHORIZED, "Unauthorized");
+
     let token = match headers.get("authorization").and_then(|v| v.to_str().ok()) {
         Some(v) if v.starts_with("Bearer ") => &v[7..],
-        _ => return false,
+        _ => return Err(UNAUTHORIZED),
     };
 
     // Constant-time comparison via the `subtle` crate.
     // For equal-length inputs ct_eq iterates all bytes via XOR.
     // Length mismatch returns false immediately, but our secret is a
     // fixed-length hex string so length is not sensitive.
-    token.as_bytes().ct_eq(secret.as_bytes()).into()
+    if bool::from(token.as_bytes().ct_eq(state.secret.as_bytes())) {
+        return Ok(Credential::Shared);
+    }
+
+    // API tokens are looked up by hash, so the comparison never sees plaintext
+    match state
+        .storage
+        .find_api_token(&crate::auth::hash_api_token(token))
+    {
+        Ok(Some(t)) => Ok(Credential::Token(t)),
+        Ok(None) => Err(UNAUTHORIZED),
+        Err(e) => {
+            log::error!("Failed to look up API token: {e}");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"))
+        }
+    }
 }
 
-fn check_rate_limit(rate_limiter: &Mutex<VecDeque<Instant>>) -> bool {
-    let mut window = rate_limiter.lock();
+/// Checks that an authenticated request may use `scope`: the shared secret
+/// always may, an API token only when it was granted `scope` or `*`.
+fn authorize(state: &ServerState, auth: &Authentication, scope: &str) -> Result<Caller, AuthError> {
+    let api_token = match &auth.0 {
+        Ok(Credential::Shared) => return Ok(Caller::Shared),
+        Ok(Credential::Token(t)) => t,
+        Err(e) => return Err(*e),
+    };
+    if !api_token
+        .scopes
+        .iter()
+        .any(|s| s == scope || s == crate::auth::ALL_SCOPES)
+    {
+        return Err((StatusCode::FORBIDDEN, "Token lacks required scope"));
+    }
+    if let Err(e) = tokio::task::block_in_place(|| state.storage.touch_api_token(api_token.id)) {
+        log::warn!("Failed to record API token use: {e}");
+    }
+    Ok(Caller::Token {
+        id: api_token.id,
+        hostname: api_token.hostname.clone(),
+        forwards: crate::auth::may_forward(&api_token.scopes),
+    })
+}
+
+/// Sliding-window limit on token reports, counted separately for each API
+/// token, or each client address for the shared secret, so one busy machine
+/// can't throttle the rest. Keys come from the credential rather than the
+/// reported hostname, which a client could vary to dodge the limit.
+fn check_client_rate_limit(
+    rate_limiter: &Mutex<HashMap<String, VecDeque<Instant>>>,
+    key: &str,
+) -> bool {
+    let mut windows = rate_limiter.lock();
 
     let now = Instant::now();
     let cutoff = now - std::time::Duration::from_secs(RATE_WINDOW_SECS);
 
+    // Forget idle clients so the map can't grow without bound, then the least
+    // recently seen one if all are still active
+    if windows.len() >= MAX_TRACKED_CLIENTS && !windows.contains_key(key) {
+        windows.retain(|_, w| w.back().is_some_and(|t| *t >= cutoff));
+        if windows.len() >= MAX_TRACKED_CLIENTS
+            && let Some(oldest) = windows
+                .iter()
+                .min_by_key(|(_, w)| w.back().copied())
+                .map(|(k, _)| k.clone())
+        {
+            windows.remove(&oldest);
+        }
+    }
+
+    let window = windows.entry(key.to_string()).or_default();
+
     // Remove expired entries from the front
     while window.front().is_some_and(|t| *t < cutoff) {
         window.pop_front();
@@ -79,123 +230,693 @@ fn check_rate_limit(rate_limiter: &Mutex<VecDeque<Instant>>) -> bool {
     true
 }
 
+// --- Listener configuration ---
+
+/// Setting keys for the listener, each overridable by an environment variable.
+pub const LISTEN_SETTINGS: &[(&str, &str)] = &[
+    ("server.bind", "QUILL_BIND"),
+    ("server.allow", "QUILL_ALLOW"),
+    ("server.tls", "QUILL_TLS"),
+    ("server.tls_cert", "QUILL_TLS_CERT"),
+    ("server.tls_key", "QUILL_TLS_KEY"),
+];
+
+pub enum TlsMode {
+    Off,
+    /// Certificate generated on first run under the app data directory.
+    SelfSigned,
+    /// User-supplied PEM certificate chain and private key.
+    Files {
+        cert: PathBuf,
+        key: PathBuf,
+    },
+}
+
+/// Where and how the token server accepts connections.
+pub struct ListenConfig {
+    pub addrs: Vec<SocketAddr>,
+    /// Networks allowed to connect; empty allows everyone. Loopback is
+    /// always allowed so local hooks keep working.
+    pub allow: Vec<IpNet>,
+    pub tls: TlsMode,
+}
+
+impl ListenConfig {
+    /// Reads `server.*` settings, with `QUILL_*` environment variables taking
+    /// precedence. `server.bind` is a comma-separated list of addresses with
+    /// an optional port (default `0.0.0.0` on `QUILL_PORT`), `server.allow`
+    /// a comma-separated list of CIDRs, and `server.tls` either `off` or
+    /// `self-signed`. Setting `server.tls_cert` and `server.tls_key` enables
+    /// TLS with that certificate.
+    pub fn load(storage: &Storage) -> Result<Self, String> {
+        let setting = |key: &str| -> Result<Option<String>, String> {
+            let env = LISTEN_SETTINGS
+                .iter()
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, var)| std::env::var(var).ok());
+            let value = match env {
+                Some(v) => Some(v),
+                None => storage.get_setting(key)?,
+            };
+            Ok(value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()))
+        };
+
+        let port: u16 = std::env::var("QUILL_PORT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+
+        let bind = setting("server.bind")?.unwrap_or_else(|| "0.0.0.0".to_string());
+        let mut addrs = Vec::new();
+        for entry in bind.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let addr = entry
+                .parse::<SocketAddr>()
+                .or_else(|_| entry.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, port)))
+                .map_err(|_| format!("invalid bind address '{entry}'"))?;
+            addrs.push(addr);
+        }
+        if addrs.is_empty() {
+            return Err("server.bind lists no addresses".to_string());
+        }
+
+        let allow = match setting("server.allow")? {
+            Some(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|e| !e.is_empty())
+                .map(|entry| {
+                    entry
+                        .parse::<IpNet>()
+                        .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|_| format!("invalid CIDR '{entry}' in server.allow"))
+                })
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+
+        let tls = match (setting("server.tls_cert")?, setting("server.tls_key")?) {
+            (Some(cert), Some(key)) => TlsMode::Files {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            },
+            (Some(_), None) | (None, Some(_)) => {
+                return Err("server.tls_cert and server.tls_key must be set together".to_string());
+            }
+            (None, None) => match setting("server.tls")?.as_deref() {
+                None | Some("off") => TlsMode::Off,
+                Some("self-signed") => TlsMode::SelfSigned,
+                Some(other) => {
+                    return Err(format!(
+                        "invalid server.tls '{other}' (expected off or self-signed)"
+                    ));
+                }
+            },
+        };
+
+        Ok(Self { addrs, allow, tls })
+    }
+
+    fn permits(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_loopback() || self.allow.is_empty() || self.allow.iter().any(|n| n.contains(&ip))
+    }
+
+    /// Loads (or generates) the certificate for the configured TLS mode.
+    pub fn tls_identity(&self) -> Result<Option<crate::tls::TlsIdentity>, String> {
+        match &self.tls {
+            TlsMode::Off => Ok(None),
+            TlsMode::Files { cert, key } => crate::tls::load_identity(cert, key).map(Some),
+            TlsMode::SelfSigned => {
+                // Name specific bind addresses in the certificate for clients that check them
+                let names: Vec<String> = self
+                    .addrs
+                    .iter()
+                    .filter(|a| !a.ip().is_unspecified())
+                    .map(|a| a.ip().to_string())
+                    .collect();
+                crate::tls::load_or_create_self_signed(&names).map(Some)
+            }
+        }
+    }
+}
+
+type ApiRoute = (Method, &'static str, MethodRouter<Arc<ServerState>>);
+
+fn route<H, T>(method: Method, path: &'static str, handler: H) -> ApiRoute
+where
+    H: Handler<T, Arc<ServerState>>,
+    T: 'static,
+{
+    let filter = MethodFilter::try_from(method.clone()).expect("standard HTTP method");
+    (method, path, on(filter, handler))
+}
+
+/// Raises axum's default 2 MB request body limit for a route that buffers
+/// larger bodies.
+fn with_body_limit((method, path, handler): ApiRoute, limit: usize) -> ApiRoute {
+    (method, path, handler.layer(DefaultBodyLimit::max(limit)))
+}
+
+/// Compares the routes [`api_routes`] serves with the OpenAPI document.
+pub fn check_openapi_routes() -> Result<(), String> {
+    let routes = api_routes();
+    let served: Vec<(&str, &str)> = routes
+        .iter()
+        .map(|(method, path, _)| (method.as_str(), *path))
+        .collect();
+    crate::openapi::check_routes(&served)
+}
+
+/// Every route the server answers. `openapi::operations` must list the same
+/// (method, path) pairs.
+fn api_routes() -> Vec<ApiRoute> {
+    vec![
+        route(Method::GET, "/api/v1/health", health),
+        route(Method::GET, "/api/v1/openapi.json", get_openapi),
+        route(Method::POST, "/api/v1/tokens", report_tokens),
+        route(Method::POST, "/api/v1/tokens/batch", report_token_batch),
+        route(Method::GET, "/api/v1/usage/forecast", get_usage_forecast),
+        route(
+            Method::GET,
+            "/api/v1/analytics/usage/history",
+            get_analytics_usage_history,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/usage/stats",
+            get_analytics_usage_stats,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/tokens/history",
+            get_analytics_token_history,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/tokens/stats",
+            get_analytics_token_stats,
+        ),
+        route(Method::GET, "/api/v1/analytics/hosts", get_analytics_hosts),
+        route(
+            Method::GET,
+            "/api/v1/analytics/projects",
+            get_analytics_projects,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/sessions",
+            get_analytics_sessions,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/models",
+            get_analytics_models,
+        ),
+        route(Method::GET, "/api/v1/analytics/costs", get_analytics_costs),
+        route(
+            Method::GET,
+            "/api/v1/analytics/code",
+            get_analytics_code_stats,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/code/history",
+            get_analytics_code_history,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/analytics/response-times",
+            get_analytics_response_times,
+        ),
+        route(
+            Method::POST,
+            "/api/v1/learning/observations",
+            post_observation,
+        ),
+        route(
+            Method::GET,
+            "/api/v1/learning/observations",
+            get_observations,
+        ),
+        route(
+            Method::POST,
+            "/api/v1/learning/session-end",
+            post_session_end,
+        ),
+        route(Method::GET, "/api/v1/learning/status", get_learning_status),
+        route(Method::POST, "/api/v1/learning/runs", post_learning_run),
+        route(Method::GET, "/api/v1/learning/runs", get_learning_runs),
+        route(Method::POST, "/api/v1/learning/rules", post_learned_rule),
+        route(Method::POST, "/api/v1/sessions/notify", post_session_notify),
+        with_body_limit(
+            route(
+                Method::POST,
+                "/api/v1/sessions/messages",
+                post_session_messages,
+            ),
+            MAX_DECODED_BODY,
+        ),
+        route(
+            Method::POST,
+            "/api/v1/sessions/messages/stream",
+            post_session_messages_stream,
+        ),
+        route(Method::GET, "/api/v1/sessions/sync", get_session_sync),
+        route(Method::GET, "/api/v1/sessions/search", get_session_search),
+        route(
+            Method::GET,
+            "/api/v1/sessions/context",
+            get_session_context_api,
+        ),
+        route(Method::GET, "/api/v1/sessions/facets", get_session_facets),
+        route(Method::GET, "/api/v1/events", get_event_stream),
+        route(Method::GET, "/metrics", get_metrics),
+    ]
+}
+
 pub async fn start_server(
     storage: &'static Storage,
     secret: String,
-    app_handle: tauri::AppHandle,
+    events: EventBus,
     session_index: Option<Arc<sessions::SessionIndex>>,
 ) {
-    let port: u16 = std::env::var("QUILL_PORT")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(DEFAULT_PORT);
+    let config = match ListenConfig::load(storage) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Invalid token server configuration: {e}");
+            return;
+        }
+    };
+    // Never fall back to plaintext when TLS was asked for but can't be set up
+    let tls = match config.tls_identity() {
+        Ok(identity) => identity,
+        Err(e) => {
+            log::error!("Failed to set up TLS for the token server: {e}");
+            return;
+        }
+    };
+
+    // A broken relay setup shouldn't stop local collection
+    let relay = match crate::relay::RelayConfig::load(storage) {
+        Ok(relay) => relay,
+        Err(e) => {
+            log::error!("Relay disabled, invalid configuration: {e}");
+            None
+        }
+    };
 
     let state = Arc::new(ServerState {
         storage,
         secret,
-        rate_limiter: Mutex::new(VecDeque::new()),
+        rate_limiter: Mutex::new(HashMap::new()),
         obs_rate_limiter: Mutex::new(VecDeque::new()),
         session_rate_limiter: Mutex::new(VecDeque::new()),
-        app_handle,
+        events,
         session_index,
+        metrics: Metrics::default(),
+        event_streams: AtomicUsize::new(0),
+        relay: relay.is_some(),
     });
+    if let Some(relay) = relay {
+        tokio::spawn(crate::relay::run(storage, relay));
+    }
+
+    // Adding a route without documenting it fails debug builds at startup
+    if let Err(e) = check_openapi_routes() {
+        debug_assert!(false, "OpenAPI document is out of date: {e}");
+        log::error!("OpenAPI document is out of date: {e}");
+    }
 
-    let app = Router::new()
-        .route("/api/v1/health", get(health))
-        .route("/api/v1/tokens", post(report_tokens))
-        .route("/api/v1/learning/observations", post(post_observation))
-        .route("/api/v1/learning/observations", get(get_observations))
-        .route("/api/v1/learning/session-end", post(post_session_end))
-        .route("/api/v1/learning/status", get(get_learning_status))
-        .route("/api/v1/learning/runs", post(post_learning_run))
-        .route("/api/v1/learning/runs", get(get_learning_runs))
-        .route("/api/v1/learning/rules", post(post_learned_rule))
-        .route("/api/v1/sessions/notify", post(post_session_notify))
-        .route("/api/v1/sessions/messages", post(post_session_messages))
-        .route("/api/v1/sessions/search", get(get_session_search))
-        .route("/api/v1/sessions/context", get(get_session_context_api))
-        .route("/api/v1/sessions/facets", get(get_session_facets))
+    let mut app = Router::new();
+    for (_, path, handler) in api_routes() {
+        app = app.route(path, handler);
+    }
+    let app = app
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_requests,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
         .with_state(state);
 
-    // Bind to 0.0.0.0 intentionally — remote hosts need to reach this server
-    let addr = format!("0.0.0.0:{port}");
-    let listener = match TcpListener::bind(&addr).await {
-        Ok(l) => l,
-        Err(e) => {
-            log::error!("Failed to bind token server on {addr}: {e}");
-            return;
+    let scheme = match &tls {
+        Some(identity) => {
+            log::info!(
+                "Token server TLS certificate fingerprint (SHA-256): {}",
+                identity.fingerprint
+            );
+            "https"
         }
+        None => "http",
     };
+    let acceptor = tls.map(|identity| TlsAcceptor::from(identity.config));
+    let config = Arc::new(config);
+
+    let mut listeners = Vec::new();
+    for addr in &config.addrs {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                log::info!("Token server listening on {scheme}://{addr}");
+                listeners.push(serve(
+                    listener,
+                    app.clone(),
+                    acceptor.clone(),
+                    config.clone(),
+                ));
+            }
+            Err(e) => log::error!("Failed to bind token server on {addr}: {e}"),
+        }
+    }
+    futures::future::join_all(listeners).await;
+}
 
-    log::info!("Token server listening on {addr}");
+/// Accepts connections until the listener fails. With TLS enabled, loopback
+/// clients may still speak plain HTTP so local hooks need no certificate;
+/// every other peer must complete a TLS handshake.
+async fn serve(
+    listener: TcpListener,
+    app: Router,
+    acceptor: Option<TlsAcceptor>,
+    config: Arc<ListenConfig>,
+) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Usually fd exhaustion; back off instead of spinning
+                log::warn!("Token server accept error: {e}");
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        if !config.permits(peer.ip()) {
+            log::debug!("Rejected connection from {peer}: not in server.allow");
+            continue;
+        }
 
-    if let Err(e) = axum::serve(listener, app).await {
-        log::error!("Token server error: {e}");
+        // Lets the audit log record the client address
+        let app = app.clone().layer(axum::Extension(ConnectInfo(peer)));
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            let service = TowerToHyperService::new(app);
+            let builder = auto::Builder::new(TokioExecutor::new());
+            // A peer that connects and stalls would otherwise hold its task
+            // and socket open forever
+            let plaintext = match &acceptor {
+                Some(_) => match tokio::time::timeout(
+                    HANDSHAKE_TIMEOUT,
+                    is_plaintext_loopback(&stream, peer),
+                )
+                .await
+                {
+                    Ok(plaintext) => plaintext,
+                    Err(_) => {
+                        log::debug!("Connection from {peer} sent nothing, closing");
+                        return;
+                    }
+                },
+                None => true,
+            };
+            let result = match acceptor {
+                Some(acceptor) if !plaintext => {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(tls)) => {
+                            builder
+                                .serve_connection_with_upgrades(TokioIo::new(tls), service)
+                                .await
+                        }
+                        Ok(Err(e)) => {
+                            log::debug!("TLS handshake with {peer} failed: {e}");
+                            return;
+                        }
+                        Err(_) => {
+                            log::debug!("TLS handshake with {peer} timed out");
+                            return;
+                        }
+                    }
+                }
+                _ => {
+                    builder
+                        .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                log::debug!("Connection from {peer} closed with error: {e}");
+            }
+        });
     }
 }
 
+/// True when a loopback peer opened with something other than a TLS
+/// handshake record (first byte 0x16).
+async fn is_plaintext_loopback(stream: &TcpStream, peer: SocketAddr) -> bool {
+    if !peer.ip().to_canonical().is_loopback() {
+        return false;
+    }
+    let mut first = [0u8; 1];
+    matches!(stream.peek(&mut first).await, Ok(1) if first[0] != 0x16)
+}
+
+async fn get_openapi() -> Json<&'static serde_json::Value> {
+    Json(crate::openapi::document())
+}
+
 async fn health() -> &'static str {
     "ok"
 }
 
-async fn report_tokens(
+/// Counts every routed request by method, matched route and response status.
+async fn track_requests(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
-    Json(payload): Json<TokenReportPayload>,
-) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    let resp = next.run(req).await;
+    state
+        .metrics
+        .record_request(&method, &route, resp.status().as_u16());
+    resp
+}
+
+/// Read-only routes polled by monitoring, recorded only when they fail.
+const UNAUDITED_SCRAPES: &[&str] = &["GET /metrics", "GET /api/v1/health"];
+
+/// Appends every authenticated request to the audit log with the caller,
+/// client address, status and the number of body bytes received, except
+/// successful [`UNAUDITED_SCRAPES`].
+async fn audit_requests(
+    State(state): State<Arc<ServerState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let auth = Authentication(tokio::task::block_in_place(|| {
+        authenticate(&state, req.headers())
+    }));
+    req.extensions_mut().insert(auth.clone());
+    // Unauthenticated requests are answered without touching any data
+    let Ok(credential) = auth.0 else {
+        return next.run(req).await;
+    };
+    let action = format!(
+        "{} {}",
+        req.method(),
+        req.extensions()
+            .get::<MatchedPath>()
+            .map(|p| p.as_str())
+            .unwrap_or_default()
+    );
+    let client_addr = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical().to_string());
+
+    // Count the body as the handler reads it, so streamed uploads are sized too
+    let received = Arc::new(AtomicU64::new(0));
+    let counter = received.clone();
+    let req = req.map(|body| {
+        axum::body::Body::from_stream(futures::TryStreamExt::inspect_ok(
+            body.into_data_stream(),
+            move |chunk| {
+                counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            },
+        ))
+    });
+    let resp = next.run(req).await;
+    // A scraper polling every few seconds would drown the log, so only its
+    // refusals are kept
+    if UNAUDITED_SCRAPES.contains(&action.as_str()) && resp.status().is_success() {
+        return resp;
     }
 
-    if !check_rate_limit(&state.rate_limiter) {
-        return (
-            StatusCode::TOO_MANY_REQUESTS,
-            "Rate limit exceeded".to_string(),
-        );
+    let (actor, token_id, hostname) = match credential {
+        Credential::Shared => ("shared secret".to_string(), None, None),
+        Credential::Token(t) => (t.name, Some(t.id), t.hostname),
+    };
+    let entry = AuditEntry {
+        id: 0,
+        timestamp: String::new(),
+        kind: "api".to_string(),
+        action,
+        actor,
+        token_id,
+        hostname,
+        client_addr,
+        status: Some(i64::from(resp.status().as_u16())),
+        bytes: Some(received.load(Ordering::Relaxed) as i64),
+        detail: None,
+        error: None,
+    };
+    tokio::task::block_in_place(|| crate::audit::record(state.storage, entry));
+    resp
+}
+
+async fn get_metrics(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+) -> Response {
+    if let Err((status, msg)) = authorize(&state, &auth, "metrics:read") {
+        return (status, msg).into_response();
     }
 
+    let summary = match tokio::task::block_in_place(|| state.storage.get_metrics_summary()) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("Failed to collect metrics: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response();
+        }
+    };
+    let index_docs = state
+        .session_index
+        .as_ref()
+        .map(|idx| idx.searcher().num_docs());
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&summary, index_docs),
+    )
+        .into_response()
+}
+
+/// Checks the fields of one reported turn. Hostname presence and length are
+/// checked by the caller, since batches carry it once.
+fn validate_token_report(payload: &TokenReportPayload) -> Result<(), &'static str> {
     if payload.session_id.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            "session_id is required".to_string(),
-        );
-    }
-    if payload.hostname.is_empty() {
-        return (StatusCode::BAD_REQUEST, "hostname is required".to_string());
+        return Err("session_id is required");
     }
     if payload.session_id.len() > MAX_STRING_LEN {
-        return (StatusCode::BAD_REQUEST, "session_id too long".to_string());
-    }
-    if payload.hostname.len() > MAX_STRING_LEN {
-        return (StatusCode::BAD_REQUEST, "hostname too long".to_string());
+        return Err("session_id too long");
     }
     if payload.cwd.as_ref().is_some_and(|c| c.len() > MAX_CWD_LEN) {
-        return (StatusCode::BAD_REQUEST, "cwd too long".to_string());
+        return Err("cwd too long");
+    }
+    if payload
+        .model
+        .as_ref()
+        .is_some_and(|m| m.len() > MAX_STRING_LEN)
+    {
+        return Err("model too long");
+    }
+    if payload
+        .idempotency_key
+        .as_ref()
+        .is_some_and(|k| k.is_empty() || k.len() > MAX_STRING_LEN)
+    {
+        return Err("idempotency_key must be 1 to 256 characters");
     }
     if payload.input_tokens < 0
         || payload.output_tokens < 0
         || payload.cache_creation_input_tokens < 0
         || payload.cache_read_input_tokens < 0
     {
-        return (
-            StatusCode::BAD_REQUEST,
-            "token counts must be non-negative".to_string(),
-        );
+        return Err("token counts must be non-negative");
     }
     if payload.input_tokens > MAX_TOKEN_VALUE
         || payload.output_tokens > MAX_TOKEN_VALUE
         || payload.cache_creation_input_tokens > MAX_TOKEN_VALUE
         || payload.cache_read_input_tokens > MAX_TOKEN_VALUE
     {
-        return (
-            StatusCode::BAD_REQUEST,
-            "token counts exceed maximum allowed value".to_string(),
-        );
+        return Err("token counts exceed maximum allowed value");
     }
+    Ok(())
+}
 
-    match state.storage.store_token_snapshot(&payload) {
-        Ok(()) => {
-            let _ = state.app_handle.emit("tokens-updated", ());
+/// Checks the hostname shared by a report, then the caller's rate limit.
+fn admit_token_report(
+    state: &ServerState,
+    caller: &Caller,
+    peer: SocketAddr,
+    hostname: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    if hostname.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "hostname is required"));
+    }
+    if hostname.len() > MAX_STRING_LEN {
+        return Err((StatusCode::BAD_REQUEST, "hostname too long"));
+    }
+    if !caller.may_report_as(hostname) {
+        return Err((StatusCode::FORBIDDEN, "hostname does not match API token"));
+    }
+    let key = match caller {
+        Caller::Token { id, .. } => format!("token:{id}"),
+        Caller::Shared => format!("addr:{}", peer.ip().to_canonical()),
+    };
+    if !check_client_rate_limit(&state.rate_limiter, &key) {
+        return Err((StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded"));
+    }
+    Ok(())
+}
+
+/// Records metrics and notifies listeners about newly stored turns.
+fn publish_token_reports(state: &ServerState, stored: &[&TokenReportPayload]) {
+    if stored.is_empty() {
+        return;
+    }
+    for payload in stored {
+        state.metrics.record_tokens(payload);
+        state.events.emit("token-reported", payload);
+    }
+    state.events.emit("tokens-updated", ());
+    crate::budgets::evaluate(state.storage, &state.events);
+    if state.relay {
+        crate::relay::enqueue_token_reports(state.storage, stored);
+    }
+}
+
+async fn report_tokens(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(payload): Json<TokenReportPayload>,
+) -> impl IntoResponse {
+    let caller = match authorize(&state, &auth, "tokens:write") {
+        Ok(c) => c,
+        Err((status, msg)) => return (status, msg.to_string()),
+    };
+    if let Err((status, msg)) = admit_token_report(&state, &caller, peer, &payload.hostname) {
+        return (status, msg.to_string());
+    }
+    if let Err(msg) = validate_token_report(&payload) {
+        return (StatusCode::BAD_REQUEST, msg.to_string());
+    }
+
+    // A repeated idempotency key is still a success for the client
+    match tokio::task::block_in_place(|| state.storage.store_token_snapshot(&payload)) {
+        Ok(inserted) => {
+            if inserted {
+                tokio::task::block_in_place(|| publish_token_reports(&state, &[&payload]));
+            }
             (StatusCode::OK, "ok".to_string())
         }
         Err(e) => {
@@ -208,6 +929,377 @@ async fn report_tokens(
     }
 }
 
+async fn report_token_batch(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(batch): Json<TokenBatchPayload>,
+) -> Response {
+    let caller = match authorize(&state, &auth, "tokens:write") {
+        Ok(c) => c,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+    if let Err((status, msg)) = admit_token_report(&state, &caller, peer, &batch.hostname) {
+        return (status, msg).into_response();
+    }
+    if batch.turns.is_empty() {
+        return (StatusCode::BAD_REQUEST, "turns is required").into_response();
+    }
+    if batch.turns.len() > MAX_TURNS_PER_BATCH {
+        return (StatusCode::BAD_REQUEST, "too many turns in batch").into_response();
+    }
+
+    let hostname = batch.hostname;
+    let reports: Vec<TokenReportPayload> = batch
+        .turns
+        .into_iter()
+        .map(|t| t.into_report(&hostname))
+        .collect();
+    for (i, report) in reports.iter().enumerate() {
+        if let Err(msg) = validate_token_report(report) {
+            return (StatusCode::BAD_REQUEST, format!("turns[{i}]: {msg}")).into_response();
+        }
+    }
+
+    let inserted = match tokio::task::block_in_place(|| {
+        state.storage.store_token_snapshots(&reports)
+    }) {
+        Ok(inserted) => inserted,
+        Err(e) => {
+            log::error!("Failed to store token batch: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response();
+        }
+    };
+    let stored: Vec<&TokenReportPayload> = reports
+        .iter()
+        .zip(&inserted)
+        .filter_map(|(r, new)| new.then_some(r))
+        .collect();
+    tokio::task::block_in_place(|| publish_token_reports(&state, &stored));
+
+    Json(TokenBatchResult {
+        accepted: stored.len(),
+        duplicates: reports.len() - stored.len(),
+    })
+    .into_response()
+}
+
+async fn get_usage_forecast(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> impl IntoResponse {
+    if let Err((status, msg)) = authorize(&state, &auth, "usage:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
+    }
+
+    let bucket = params.get("bucket").map(|b| b.as_str());
+    match tokio::task::block_in_place(|| state.storage.get_usage_forecasts(bucket)) {
+        Ok(forecasts) => (StatusCode::OK, Json(serde_json::json!(forecasts))),
+        Err(e) => {
+            log::error!("Failed to compute usage forecast: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": "Internal server error"})),
+            )
+        }
+    }
+}
+
+// --- Analytics endpoints ---
+//
+// Read-only mirrors of the analytics Tauri commands, so scripts and other
+// machines see the same numbers as the widget. Each route calls the same
+// Storage method as its command and returns its model struct as JSON.
+
+pub const RANGES: &[&str] = &["1h", "24h", "7d", "30d"];
+pub const TOKEN_HISTORY_RANGES: &[&str] = &["1h", "24h", "7d", "30d", "all"];
+const DEFAULT_DAYS: i32 = 7;
+const MAX_DAYS: i32 = 3650;
+
+/// Query parameters shared by the analytics routes; each route reads the
+/// ones its Storage method takes.
+#[derive(serde::Deserialize)]
+struct AnalyticsQuery {
+    range: Option<String>,
+    days: Option<i32>,
+    bucket: Option<String>,
+    hostname: Option<String>,
+    session_id: Option<String>,
+    cwd: Option<String>,
+    model: Option<String>,
+}
+
+impl AnalyticsQuery {
+    /// `range`, defaulting to 24h. Unknown values are rejected rather than
+    /// silently falling back the way the storage layer does.
+    fn range(&self, allowed: &[&str]) -> Result<String, String> {
+        match self.range.as_deref() {
+            None => Ok("24h".to_string()),
+            Some(r) if allowed.contains(&r) => Ok(r.to_string()),
+            Some(_) => Err(format!("range must be one of: {}", allowed.join(", "))),
+        }
+    }
+
+    fn days(&self) -> Result<i32, String> {
+        match self.days {
+            None => Ok(DEFAULT_DAYS),
+            Some(d) if (1..=MAX_DAYS).contains(&d) => Ok(d),
+            Some(_) => Err(format!("days must be between 1 and {MAX_DAYS}")),
+        }
+    }
+
+    /// Checks filter lengths and drops empty filters.
+    fn validate(&mut self) -> Result<(), String> {
+        for (name, value, max) in [
+            ("bucket", &mut self.bucket, MAX_STRING_LEN),
+            ("hostname", &mut self.hostname, MAX_STRING_LEN),
+            ("session_id", &mut self.session_id, MAX_STRING_LEN),
+            ("cwd", &mut self.cwd, MAX_CWD_LEN),
+            ("model", &mut self.model, MAX_STRING_LEN),
+        ] {
+            if value.as_ref().is_some_and(|v| v.len() > max) {
+                return Err(format!("{name} too long"));
+            }
+            if value.as_ref().is_some_and(|v| v.is_empty()) {
+                *value = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn json_error(status: StatusCode, msg: &str) -> Response {
+    (status, Json(serde_json::json!({"error": msg}))).into_response()
+}
+
+/// Authorizes an analytics request, validates its query and runs `query`
+/// against storage.
+fn analytics_response<T: serde::Serialize>(
+    state: &ServerState,
+    auth: &Authentication,
+    mut params: AnalyticsQuery,
+    query: impl FnOnce(&Storage, AnalyticsQuery) -> Result<Result<T, String>, String>,
+) -> Response {
+    if let Err((status, msg)) = authorize(state, auth, "usage:read") {
+        return json_error(status, msg);
+    }
+    if let Err(e) = params.validate() {
+        return json_error(StatusCode::BAD_REQUEST, &e);
+    }
+    // Outer error: bad parameter; inner error: query failure
+    match tokio::task::block_in_place(|| query(state.storage, params)) {
+        Err(e) => json_error(StatusCode::BAD_REQUEST, &e),
+        Ok(Ok(value)) => Json(value).into_response(),
+        Ok(Err(e)) => {
+            log::error!("Analytics query failed: {e}");
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+        }
+    }
+}
+
+async fn get_analytics_usage_history(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        let range = q.range(RANGES)?;
+        let bucket = q.bucket.ok_or("bucket is required")?;
+        Ok(s.get_usage_history(&bucket, &range))
+    })
+}
+
+async fn get_analytics_usage_stats(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        let days = q.days()?;
+        let bucket = q.bucket.ok_or("bucket is required")?;
+        Ok(s.get_usage_stats(&bucket, days))
+    })
+}
+
+async fn get_analytics_token_history(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        let range = q.range(TOKEN_HISTORY_RANGES)?;
+        Ok(s.get_token_history(
+            &range,
+            q.hostname.as_deref(),
+            q.session_id.as_deref(),
+            q.cwd.as_deref(),
+            q.model.as_deref(),
+        ))
+    })
+}
+
+async fn get_analytics_token_stats(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        let days = q.days()?;
+        Ok(s.get_token_stats(
+            days,
+            q.hostname.as_deref(),
+            q.cwd.as_deref(),
+            q.model.as_deref(),
+        ))
+    })
+}
+
+async fn get_analytics_hosts(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_host_breakdown(q.days()?, q.model.as_deref()))
+    })
+}
+
+async fn get_analytics_projects(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_project_breakdown(q.days()?, q.model.as_deref()))
+    })
+}
+
+async fn get_analytics_sessions(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_session_breakdown(q.days()?, q.hostname.as_deref(), q.model.as_deref()))
+    })
+}
+
+async fn get_analytics_models(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_model_breakdown(q.days()?, q.hostname.as_deref(), q.cwd.as_deref()))
+    })
+}
+
+async fn get_analytics_costs(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_cost_summary(q.days()?, q.hostname.as_deref()))
+    })
+}
+
+async fn get_analytics_code_stats(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_code_stats(&q.range(RANGES)?))
+    })
+}
+
+async fn get_analytics_code_history(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_code_stats_history(&q.range(RANGES)?))
+    })
+}
+
+async fn get_analytics_response_times(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<AnalyticsQuery>,
+) -> Response {
+    analytics_response(&state, &auth, params, |s, q| {
+        Ok(s.get_response_time_stats(&q.range(RANGES)?))
+    })
+}
+
+// --- Live event stream ---
+
+/// Releases an event stream slot when the client disconnects.
+struct StreamSlot(Arc<ServerState>);
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.event_streams.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Server-Sent Events stream of backend events. Each message's `event` field
+/// is the event name and `data` is `{"name": ..., "payload": ...}`. An optional
+/// `events` query parameter takes a comma-separated list of names to receive.
+async fn get_event_stream(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Response {
+    if let Err((status, msg)) = authorize(&state, &auth, "events:read") {
+        return (status, msg).into_response();
+    }
+
+    let open = state.event_streams.fetch_add(1, Ordering::SeqCst);
+    let slot = StreamSlot(state.clone());
+    if open >= MAX_EVENT_STREAMS {
+        return (StatusCode::TOO_MANY_REQUESTS, "Too many open event streams").into_response();
+    }
+
+    let wanted: Option<Vec<String>> = params.get("events").map(|v| {
+        v.split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect()
+    });
+    let rx = state.events.subscribe();
+
+    let stream = futures::stream::unfold((rx, slot), move |(mut rx, slot)| {
+        let wanted = wanted.clone();
+        async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if wanted.as_ref().is_some_and(|w| !w.contains(&event.name)) {
+                            continue;
+                        }
+                        let sse = Event::default().event(&event.name).json_data(&event);
+                        return Some((sse, (rx, slot)));
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        // Tell the client it missed events rather than silently dropping them
+                        let sse = Ok(Event::default().event("lagged").data(n.to_string()));
+                        return Some((sse, (rx, slot)));
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
 // --- Learning endpoints ---
 
 fn check_rate_limit_with_max(rate_limiter: &Mutex<VecDeque<Instant>>, max: usize) -> bool {
@@ -226,11 +1318,18 @@ fn check_rate_limit_with_max(rate_limiter: &Mutex<VecDeque<Instant>>, max: usize
 
 async fn post_observation(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     Json(payload): Json<ObservationPayload>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+    match authorize(&state, &auth, "learning:write") {
+        Ok(caller) if !caller.may_report() => {
+            return (
+                StatusCode::FORBIDDEN,
+                "API token is not bound to a host".to_string(),
+            );
+        }
+        Ok(_) => {}
+        Err((status, msg)) => return (status, msg.to_string()),
     }
     if !check_rate_limit_with_max(&state.obs_rate_limiter, MAX_OBS_REQUESTS) {
         return (
@@ -268,8 +1367,15 @@ async fn post_observation(
         return (StatusCode::BAD_REQUEST, "cwd too long".to_string());
     }
 
-    match state.storage.store_observation(&payload) {
-        Ok(()) => (StatusCode::OK, "ok".to_string()),
+    match tokio::task::block_in_place(|| state.storage.store_observation(&payload)) {
+        Ok(()) => {
+            if state.relay {
+                tokio::task::block_in_place(|| {
+                    crate::relay::enqueue(state.storage, crate::relay::OBSERVATIONS_ROUTE, &payload)
+                });
+            }
+            (StatusCode::OK, "ok".to_string())
+        }
         Err(e) => {
             log::error!("Failed to store observation: {e}");
             (
@@ -282,14 +1388,11 @@ async fn post_observation(
 
 async fn get_observations(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "learning:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
 
     let limit: i64 = params
@@ -298,7 +1401,7 @@ async fn get_observations(
         .unwrap_or(100)
         .min(500);
 
-    match state.storage.get_recent_observations(limit) {
+    match tokio::task::block_in_place(|| state.storage.get_recent_observations(limit)) {
         Ok(observations) => (StatusCode::OK, Json(serde_json::json!(observations))),
         Err(e) => {
             log::error!("Failed to get observations: {e}");
@@ -312,11 +1415,11 @@ async fn get_observations(
 
 async fn post_session_end(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     Json(payload): Json<SessionEndPayload>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+    if let Err((status, msg)) = authorize(&state, &auth, "learning:write") {
+        return (status, msg.to_string());
     }
     if payload.session_id.is_empty() || payload.session_id.len() > MAX_STRING_LEN {
         return (StatusCode::BAD_REQUEST, "Invalid session_id".to_string());
@@ -350,8 +1453,8 @@ async fn post_session_end(
         .unwrap_or_default();
 
     if enabled && (trigger_mode == "session-end" || trigger_mode.contains("session-end")) {
-        let _ = state
-            .app_handle
+        state
+            .events
             .emit("learning-session-end", &payload.session_id);
     }
 
@@ -360,16 +1463,13 @@ async fn post_session_end(
 
 async fn get_learning_status(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "learning:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
 
-    match state.storage.get_learning_status() {
+    match tokio::task::block_in_place(|| state.storage.get_learning_status()) {
         Ok(status) => (StatusCode::OK, Json(serde_json::json!(status))),
         Err(e) => {
             log::error!("Failed to get learning status: {e}");
@@ -383,14 +1483,11 @@ async fn get_learning_status(
 
 async fn post_learning_run(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     Json(payload): Json<LearningRunPayload>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "learning:write") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
     if payload.trigger_mode.is_empty() || payload.trigger_mode.len() > MAX_STRING_LEN {
         return (
@@ -399,9 +1496,9 @@ async fn post_learning_run(
         );
     }
 
-    match state.storage.store_learning_run(&payload) {
+    match tokio::task::block_in_place(|| state.storage.store_learning_run(&payload)) {
         Ok(id) => {
-            let _ = state.app_handle.emit("learning-updated", ());
+            state.events.emit("learning-updated", ());
             (StatusCode::OK, Json(serde_json::json!({"id": id})))
         }
         Err(e) => {
@@ -416,14 +1513,11 @@ async fn post_learning_run(
 
 async fn get_learning_runs(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "learning:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
 
     let limit: i64 = params
@@ -432,7 +1526,7 @@ async fn get_learning_runs(
         .unwrap_or(20)
         .min(100);
 
-    match state.storage.get_learning_runs(limit) {
+    match tokio::task::block_in_place(|| state.storage.get_learning_runs(limit)) {
         Ok(runs) => (StatusCode::OK, Json(serde_json::json!(runs))),
         Err(e) => {
             log::error!("Failed to get learning runs: {e}");
@@ -446,11 +1540,11 @@ async fn get_learning_runs(
 
 async fn post_learned_rule(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     Json(payload): Json<LearnedRulePayload>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+    if let Err((status, msg)) = authorize(&state, &auth, "learning:write") {
+        return (status, msg.to_string());
     }
     if payload.name.is_empty() || payload.name.len() > MAX_STRING_LEN {
         return (StatusCode::BAD_REQUEST, "Invalid name".to_string());
@@ -459,9 +1553,9 @@ async fn post_learned_rule(
         return (StatusCode::BAD_REQUEST, "Invalid file_path".to_string());
     }
 
-    match state.storage.store_learned_rule(&payload) {
+    match tokio::task::block_in_place(|| state.storage.store_learned_rule(&payload)) {
         Ok(()) => {
-            let _ = state.app_handle.emit("learning-updated", ());
+            state.events.emit("learning-updated", ());
             (StatusCode::OK, "ok".to_string())
         }
         Err(e) => {
@@ -478,11 +1572,11 @@ async fn post_learned_rule(
 
 async fn post_session_notify(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     Json(payload): Json<SessionNotifyPayload>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+    if let Err((status, msg)) = authorize(&state, &auth, "sessions:write") {
+        return (status, msg.to_string());
     }
     if !check_rate_limit_with_max(&state.session_rate_limiter, MAX_SESSION_NOTIFY_REQUESTS) {
         return (
@@ -505,12 +1599,6 @@ async fn post_session_notify(
         );
     }
 
-    // Extract messages from the JSONL file
-    let messages = sessions::extract_messages_from_jsonl(path);
-    if messages.is_empty() {
-        return (StatusCode::OK, "ok (no messages)".to_string());
-    }
-
     // Derive project name from parent directory
     let project_name = path
         .parent()
@@ -528,52 +1616,37 @@ async fn post_session_notify(
             );
         }
     };
-    let result = tokio::task::block_in_place(|| -> Result<usize, String> {
-        // Delete existing docs + tool_actions for this session before re-indexing
-        {
-            let writer = idx.writer.lock();
-            let term = tantivy::Term::from_field_text(idx.fields.session_id, &payload.session_id);
-            writer.delete_term(term);
+    // Only lines appended since the file was last indexed are parsed; earlier
+    // messages are re-indexed only when a new tool_result completes them
+    let result = tokio::task::block_in_place(|| {
+        let Some(update) = idx.index_file(path, &project_name, "local", Some(state.storage))?
+        else {
+            return Ok(None);
+        };
+        if update.added + update.updated > 0 || update.reset {
+            let mut writer = idx.writer.lock();
+            writer.commit().map_err(|e| format!("Commit index: {e}"))?;
         }
-        if let Err(e) = state
-            .storage
-            .delete_tool_actions_for_session(&payload.session_id)
-        {
-            log::warn!("Failed to delete old tool_actions: {e}");
-        }
-
-        let mut count = 0usize;
-        for msg in &messages {
-            idx.index_message(msg, &project_name, "local")?;
-            // Store tool actions in SQLite for this message
-            if !msg.tool_actions.is_empty()
-                && let Err(e) =
-                    state
-                        .storage
-                        .store_tool_actions(&msg.tool_actions, &msg.uuid, &msg.session_id)
-            {
-                log::warn!("Failed to store tool actions: {e}");
-            }
-            count += 1;
-        }
-
-        let mut writer = idx.writer.lock();
-        writer.commit().map_err(|e| format!("Commit index: {e}"))?;
-        Ok(count)
+        idx.save_state()?;
+        Ok::<_, String>(Some(update))
     });
 
     match result {
-        Ok(count) => {
-            // Re-index re-processes the whole session, so delete and re-populate response_times
-            if let Err(e) = state
-                .storage
-                .delete_response_times_for_session(&payload.session_id)
+        // Already indexed by the watcher or startup scan
+        Ok(None) => (StatusCode::OK, "ok (no messages)".to_string()),
+        Ok(Some(update)) => {
+            // A rewritten file is re-indexed from the start, so its response_times are too
+            if update.reset
+                && let Err(e) = state
+                    .storage
+                    .delete_response_times_for_session(&payload.session_id)
             {
                 log::warn!("Failed to delete old response_times: {e}");
             }
-            let rt_pairs: Vec<(&str, &str)> = messages
+            let rt_pairs: Vec<(&str, &str)> = update
+                .timeline
                 .iter()
-                .map(|m| (m.role.as_str(), m.timestamp.as_str()))
+                .map(|(role, timestamp)| (role.as_str(), timestamp.as_str()))
                 .collect();
             if let Err(e) = state
                 .storage
@@ -581,7 +1654,19 @@ async fn post_session_notify(
             {
                 log::warn!("Failed to store response times: {e}");
             }
-            let _ = state.app_handle.emit("sessions-index-updated", count);
+            let count = update.added + update.updated;
+            if count == 0 {
+                return (StatusCode::OK, "ok (no messages)".to_string());
+            }
+            state.events.emit("sessions-index-updated", count);
+            tokio::task::block_in_place(|| {
+                crate::saved_searches::check_watches(
+                    state.storage,
+                    &idx,
+                    &state.events,
+                    &update.message_ids,
+                )
+            });
             (StatusCode::OK, format!("ok ({count} messages indexed)"))
         }
         Err(e) => {
@@ -594,28 +1679,197 @@ async fn post_session_notify(
     }
 }
 
+/// Whether a request body is gzip-compressed. Encodings other than gzip and
+/// identity are refused.
+fn is_gzip(headers: &HeaderMap) -> Result<bool, (StatusCode, String)> {
+    let Some(value) = headers.get(header::CONTENT_ENCODING) else {
+        return Ok(false);
+    };
+    let encoding = value.to_str().unwrap_or("").trim().to_ascii_lowercase();
+    match encoding.as_str() {
+        "" | "identity" => Ok(false),
+        "gzip" | "x-gzip" => Ok(true),
+        _ => Err((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("Unsupported Content-Encoding '{encoding}'"),
+        )),
+    }
+}
+
+/// Returns the body with any gzip encoding undone, refusing more than
+/// `MAX_DECODED_BODY` bytes of output.
+fn decode_body<'a>(
+    headers: &HeaderMap,
+    body: &'a [u8],
+) -> Result<std::borrow::Cow<'a, [u8]>, (StatusCode, String)> {
+    if !is_gzip(headers)? {
+        return Ok(std::borrow::Cow::Borrowed(body));
+    }
+    let mut decoded = Vec::new();
+    std::io::Read::read_to_end(
+        &mut std::io::Read::take(
+            flate2::read::GzDecoder::new(body),
+            MAX_DECODED_BODY as u64 + 1,
+        ),
+        &mut decoded,
+    )
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid gzip body: {e}")))?;
+    if decoded.len() > MAX_DECODED_BODY {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Decompressed body too large".to_string(),
+        ));
+    }
+    Ok(std::borrow::Cow::Owned(decoded))
+}
+
+/// Checks the per-session fields shared by batched and streamed uploads.
+fn admit_session_upload(
+    caller: &Caller,
+    host: &str,
+    session_id: &str,
+    project: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    if session_id.is_empty() || session_id.len() > MAX_STRING_LEN {
+        return Err((StatusCode::BAD_REQUEST, "Invalid session_id"));
+    }
+    if host.is_empty() || host.len() > MAX_STRING_LEN {
+        return Err((StatusCode::BAD_REQUEST, "Invalid host"));
+    }
+    if !caller.may_report_as(host) {
+        return Err((StatusCode::FORBIDDEN, "host does not match API token"));
+    }
+    if project.is_empty() || project.len() > MAX_STRING_LEN {
+        return Err((StatusCode::BAD_REQUEST, "Invalid project"));
+    }
+    Ok(())
+}
+
+fn validate_session_message(msg: &SessionMessagePayload) -> Result<(), &'static str> {
+    if msg.uuid.is_empty() || msg.uuid.len() > MAX_STRING_LEN {
+        return Err("Invalid message uuid");
+    }
+    if msg.content.len() > MAX_CONTENT_LEN {
+        return Err("Message content too long");
+    }
+    Ok(())
+}
+
+/// Indexes one batch of pushed messages, replacing any already indexed under
+/// the same uuid so a resent range isn't duplicated, and checks watches
+/// against it. Then records response times, advances the session's sync
+/// state and queues the batch for relay.
+fn index_session_batch(
+    state: &ServerState,
+    idx: &sessions::SessionIndex,
+    batch: &SessionMessagesPayload,
+) -> Result<usize, String> {
+    if !batch.messages.is_empty() {
+        {
+            let writer = idx.writer.lock();
+            for msg in &batch.messages {
+                writer.delete_term(tantivy::Term::from_field_text(
+                    idx.fields.message_id,
+                    &msg.uuid,
+                ));
+            }
+        }
+        for msg in &batch.messages {
+            let extracted = sessions::ExtractedMessage {
+                uuid: msg.uuid.clone(),
+                session_id: batch.session_id.clone(),
+                role: msg.role.clone(),
+                content: msg.content.clone(),
+                timestamp: msg.timestamp.clone(),
+                git_branch: batch.git_branch.clone(),
+                tools_used: msg.tools_used.clone(),
+                files_modified: msg.files_modified.clone(),
+                code_changes: Vec::new(),
+                commands_run: Vec::new(),
+                tool_details: Vec::new(),
+                tool_actions: Vec::new(),
+            };
+            idx.index_message(&extracted, &batch.project, &batch.host)?;
+        }
+        idx.writer
+            .lock()
+            .commit()
+            .map_err(|e| format!("Commit index: {e}"))?;
+
+        let message_ids: Vec<String> = batch.messages.iter().map(|m| m.uuid.clone()).collect();
+        crate::saved_searches::check_watches(state.storage, idx, &state.events, &message_ids);
+    }
+
+    let rt_pairs: Vec<(&str, &str)> = batch
+        .messages
+        .iter()
+        .map(|m| (m.role.as_str(), m.timestamp.as_str()))
+        .collect();
+    if let Err(e) = state
+        .storage
+        .ingest_response_times(&batch.session_id, &rt_pairs)
+    {
+        log::warn!("Failed to store response times: {e}");
+    }
+
+    // A failed update only means the sender resends, which the uuid
+    // replacement above absorbs
+    let offset = batch
+        .offset
+        .or_else(|| batch.messages.iter().rev().find_map(|m| m.offset));
+    if let Err(e) = state.storage.advance_session_sync(
+        &batch.host,
+        &batch.session_id,
+        batch.messages.last().map(|m| m.uuid.as_str()),
+        offset,
+        batch.messages.len(),
+    ) {
+        log::warn!("Failed to update session sync state: {e}");
+    }
+
+    if state.relay && !batch.messages.is_empty() {
+        crate::relay::enqueue(state.storage, crate::relay::SESSION_MESSAGES_ROUTE, batch);
+    }
+    Ok(batch.messages.len())
+}
+
 async fn post_session_messages(
     State(state): State<Arc<ServerState>>,
     headers: HeaderMap,
-    Json(payload): Json<SessionMessagesPayload>,
+    Extension(auth): Extension<Authentication>,
+    body: axum::body::Bytes,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
-    }
+    let caller = match authorize(&state, &auth, "sessions:write") {
+        Ok(c) => c,
+        Err((status, msg)) => return (status, msg.to_string()),
+    };
     if !check_rate_limit_with_max(&state.session_rate_limiter, MAX_SESSION_MSG_REQUESTS) {
         return (
             StatusCode::TOO_MANY_REQUESTS,
             "Rate limit exceeded".to_string(),
         );
     }
-    if payload.session_id.is_empty() || payload.session_id.len() > MAX_STRING_LEN {
-        return (StatusCode::BAD_REQUEST, "Invalid session_id".to_string());
-    }
-    if payload.host.is_empty() || payload.host.len() > MAX_STRING_LEN {
-        return (StatusCode::BAD_REQUEST, "Invalid host".to_string());
-    }
-    if payload.project.is_empty() || payload.project.len() > MAX_STRING_LEN {
-        return (StatusCode::BAD_REQUEST, "Invalid project".to_string());
+    let body = match decode_body(&headers, &body) {
+        Ok(b) => b,
+        Err(e) => return e,
+    };
+    let payload: SessionMessagesPayload = match serde_json::from_slice(&body) {
+        Ok(p) => p,
+        Err(e) => {
+            let status = match e.classify() {
+                serde_json::error::Category::Data => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            return (status, format!("Invalid JSON body: {e}"));
+        }
+    };
+    if let Err((status, msg)) = admit_session_upload(
+        &caller,
+        &payload.host,
+        &payload.session_id,
+        &payload.project,
+    ) {
+        return (status, msg.to_string());
     }
     if payload.messages.is_empty() {
         return (StatusCode::BAD_REQUEST, "No messages provided".to_string());
@@ -626,39 +1880,13 @@ async fn post_session_messages(
             format!("Too many messages (max {MAX_MESSAGES_PER_BATCH})"),
         );
     }
-
-    // Validate individual messages
-    for msg in &payload.messages {
-        if msg.uuid.is_empty() || msg.uuid.len() > MAX_STRING_LEN {
-            return (StatusCode::BAD_REQUEST, "Invalid message uuid".to_string());
-        }
-        if msg.content.len() > MAX_CONTENT_LEN {
-            return (
-                StatusCode::BAD_REQUEST,
-                "Message content too long".to_string(),
-            );
-        }
-    }
-
-    // Convert SessionMessagePayload items to ExtractedMessage structs
-    let extracted: Vec<sessions::ExtractedMessage> = payload
+    if let Some(msg) = payload
         .messages
         .iter()
-        .map(|m| sessions::ExtractedMessage {
-            uuid: m.uuid.clone(),
-            session_id: payload.session_id.clone(),
-            role: m.role.clone(),
-            content: m.content.clone(),
-            timestamp: m.timestamp.clone(),
-            git_branch: payload.git_branch.clone(),
-            tools_used: m.tools_used.clone(),
-            files_modified: m.files_modified.clone(),
-            code_changes: Vec::new(),
-            commands_run: Vec::new(),
-            tool_details: Vec::new(),
-            tool_actions: Vec::new(),
-        })
-        .collect();
+        .find_map(|m| validate_session_message(m).err())
+    {
+        return (StatusCode::BAD_REQUEST, msg.to_string());
+    }
 
     let idx = match &state.session_index {
         Some(idx) => idx.clone(),
@@ -669,34 +1897,11 @@ async fn post_session_messages(
             );
         }
     };
-    let host = payload.host.clone();
-    let project = payload.project.clone();
-    let result = tokio::task::block_in_place(|| -> Result<usize, String> {
-        let mut count = 0usize;
-        for msg in &extracted {
-            idx.index_message(msg, &project, &host)?;
-            count += 1;
-        }
-
-        let mut writer = idx.writer.lock();
-        writer.commit().map_err(|e| format!("Commit index: {e}"))?;
-        Ok(count)
-    });
+    let result = tokio::task::block_in_place(|| index_session_batch(&state, &idx, &payload));
 
     match result {
         Ok(count) => {
-            let rt_pairs: Vec<(&str, &str)> = payload
-                .messages
-                .iter()
-                .map(|m| (m.role.as_str(), m.timestamp.as_str()))
-                .collect();
-            if let Err(e) = state
-                .storage
-                .ingest_response_times(&payload.session_id, &rt_pairs)
-            {
-                log::warn!("Failed to store response times: {e}");
-            }
-            let _ = state.app_handle.emit("sessions-index-updated", count);
+            state.events.emit("sessions-index-updated", count);
             (StatusCode::OK, format!("ok ({count} messages indexed)"))
         }
         Err(e) => {
@@ -709,18 +1914,318 @@ async fn post_session_messages(
     }
 }
 
+/// Parse state of one streamed upload. Messages are indexed every
+/// `MAX_MESSAGES_PER_BATCH` lines, so an interrupted upload keeps what was
+/// committed and the sync state says where to resume.
+#[derive(Default)]
+struct SessionStream {
+    header: Option<SessionStreamHeader>,
+    pending: Vec<SessionMessagePayload>,
+    lines: usize,
+    received: usize,
+    indexed: usize,
+}
+
+impl SessionStream {
+    fn line(
+        &mut self,
+        state: &ServerState,
+        idx: &sessions::SessionIndex,
+        caller: &Caller,
+        line: &[u8],
+    ) -> Result<(), (StatusCode, String)> {
+        self.lines += 1;
+        let line = line.trim_ascii();
+        if line.is_empty() {
+            return Ok(());
+        }
+        if self.header.is_none() {
+            let header: SessionStreamHeader = serde_json::from_slice(line)
+                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid header line: {e}")))?;
+            admit_session_upload(caller, &header.host, &header.session_id, &header.project)
+                .map_err(|(status, msg)| (status, msg.to_string()))?;
+            self.header = Some(header);
+            return Ok(());
+        }
+
+        let msg: SessionMessagePayload = serde_json::from_slice(line).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid message on line {}: {e}", self.lines),
+            )
+        })?;
+        validate_session_message(&msg).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("{e} on line {}", self.lines),
+            )
+        })?;
+        self.received += 1;
+        if self.received > MAX_STREAMED_MESSAGES {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Too many messages (max {MAX_STREAMED_MESSAGES})"),
+            ));
+        }
+        self.pending.push(msg);
+        if self.pending.len() >= MAX_MESSAGES_PER_BATCH {
+            self.flush(state, idx, None)?;
+        }
+        Ok(())
+    }
+
+    /// Indexes the pending messages. `offset` is the header's end offset,
+    /// passed once the whole body has been read.
+    fn flush(
+        &mut self,
+        state: &ServerState,
+        idx: &sessions::SessionIndex,
+        offset: Option<u64>,
+    ) -> Result<(), (StatusCode, String)> {
+        let Some(header) = &self.header else {
+            return Ok(());
+        };
+        if self.pending.is_empty() && offset.is_none() {
+            return Ok(());
+        }
+        let batch = SessionMessagesPayload {
+            host: header.host.clone(),
+            session_id: header.session_id.clone(),
+            project: header.project.clone(),
+            git_branch: header.git_branch.clone(),
+            messages: std::mem::take(&mut self.pending),
+            offset,
+        };
+        let count = tokio::task::block_in_place(|| index_session_batch(state, idx, &batch))
+            .map_err(|e| {
+                log::error!("Failed to index streamed session messages: {e}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            })?;
+        self.indexed += count;
+        Ok(())
+    }
+}
+
+/// The part of a request body received so far, read by a decoder. Reading
+/// past it fails with `WouldBlock` until more is pushed or the body ends.
+#[derive(Default)]
+struct BodyBuffer {
+    data: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl BodyBuffer {
+    fn push(&mut self, chunk: &[u8]) {
+        self.data.drain(..self.pos);
+        self.pos = 0;
+        self.data.extend_from_slice(chunk);
+    }
+}
+
+impl std::io::Read for BodyBuffer {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        use std::io::BufRead;
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl std::io::BufRead for BodyBuffer {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos == self.data.len() && !self.done {
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+        Ok(&self.data[self.pos..])
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.pos = (self.pos + n).min(self.data.len());
+    }
+}
+
+/// Reads an NDJSON upload, optionally gzip-compressed, indexing messages as
+/// lines arrive.
+async fn read_session_stream(
+    state: &ServerState,
+    idx: &sessions::SessionIndex,
+    caller: &Caller,
+    body: axum::body::Body,
+    gzip: bool,
+    upload: &mut SessionStream,
+) -> Result<(), (StatusCode, String)> {
+    use futures::StreamExt;
+    use std::io::{ErrorKind, Read};
+
+    let mut chunks = body.into_data_stream();
+    // Inflate on the read side, a piece at a time, so a small compressed
+    // chunk never expands further than one piece before the limits below
+    // are checked
+    let mut plain = BodyBuffer::default();
+    let mut decoder = gzip.then(|| flate2::bufread::GzDecoder::new(BodyBuffer::default()));
+    let mut piece = vec![0u8; STREAM_READ_PIECE];
+    let mut buf: Vec<u8> = Vec::new();
+    let mut total = 0usize;
+
+    let mut consume = |buf: &mut Vec<u8>, data: &[u8]| -> Result<(), (StatusCode, String)> {
+        total += data.len();
+        if total > MAX_STREAM_BYTES {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Upload too large (max {MAX_STREAM_BYTES} bytes)"),
+            ));
+        }
+        buf.extend_from_slice(data);
+        let mut start = 0;
+        while let Some(end) = buf[start..].iter().position(|&b| b == b'\n') {
+            upload.line(state, idx, caller, &buf[start..start + end])?;
+            start += end + 1;
+        }
+        buf.drain(..start);
+        if buf.len() > MAX_STREAM_LINE {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Line {} too long", upload.lines + 1),
+            ));
+        }
+        Ok(())
+    };
+
+    'body: loop {
+        let chunk = chunks.next().await;
+        let received = match decoder.as_mut() {
+            Some(d) => d.get_mut(),
+            None => &mut plain,
+        };
+        match chunk {
+            Some(chunk) => received.push(
+                &chunk
+                    .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read body: {e}")))?,
+            ),
+            None => received.done = true,
+        }
+
+        let reader: &mut dyn Read = match decoder.as_mut() {
+            Some(d) => d,
+            None => &mut plain,
+        };
+        loop {
+            match reader.read(&mut piece) {
+                Ok(0) => break 'body,
+                Ok(n) => consume(&mut buf, &piece[..n])?,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    return Err((StatusCode::BAD_REQUEST, format!("Invalid gzip body: {e}")));
+                }
+            }
+        }
+    }
+    // The last line needn't end in a newline
+    upload.line(state, idx, caller, &buf)?;
+
+    let Some(header) = &upload.header else {
+        return Err((StatusCode::BAD_REQUEST, "Missing header line".to_string()));
+    };
+    let offset = header.offset;
+    upload.flush(state, idx, offset)
+}
+
+async fn post_session_messages_stream(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
+    body: axum::body::Body,
+) -> Response {
+    let caller = match authorize(&state, &auth, "sessions:write") {
+        Ok(c) => c,
+        Err((status, msg)) => return json_error(status, msg),
+    };
+    if !check_rate_limit_with_max(&state.session_rate_limiter, MAX_SESSION_MSG_REQUESTS) {
+        return json_error(StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded");
+    }
+    let gzip = match is_gzip(&headers) {
+        Ok(g) => g,
+        Err((status, msg)) => return json_error(status, &msg),
+    };
+    let Some(idx) = state.session_index.clone() else {
+        return json_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Session index not available",
+        );
+    };
+
+    let mut upload = SessionStream::default();
+    let result = read_session_stream(&state, &idx, &caller, body, gzip, &mut upload).await;
+    if upload.indexed > 0 {
+        state.events.emit("sessions-index-updated", upload.indexed);
+    }
+    if let Err((status, msg)) = result {
+        return json_error(status, &msg);
+    }
+
+    let Some(header) = upload.header else {
+        return json_error(StatusCode::BAD_REQUEST, "Missing header line");
+    };
+    match tokio::task::block_in_place(|| {
+        state
+            .storage
+            .get_session_sync(&header.host, &header.session_id)
+    }) {
+        Ok(sync) => Json(sync).into_response(),
+        Err(e) => {
+            log::error!("Failed to read session sync state: {e}");
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+        }
+    }
+}
+
+/// How much of a pushed session the server already has, so the sender can
+/// upload only the rest.
+async fn get_session_sync(
+    State(state): State<Arc<ServerState>>,
+    Extension(auth): Extension<Authentication>,
+    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
+) -> Response {
+    let caller = match authorize(&state, &auth, "sessions:write") {
+        Ok(c) => c,
+        Err((status, msg)) => return json_error(status, msg),
+    };
+    let host = params.get("host").map(String::as_str).unwrap_or("");
+    let session_id = params.get("session_id").map(String::as_str).unwrap_or("");
+    if host.is_empty() || host.len() > MAX_STRING_LEN {
+        return json_error(StatusCode::BAD_REQUEST, "Invalid host");
+    }
+    if session_id.is_empty() || session_id.len() > MAX_STRING_LEN {
+        return json_error(StatusCode::BAD_REQUEST, "Invalid session_id");
+    }
+    if !caller.may_report_as(host) {
+        return json_error(StatusCode::FORBIDDEN, "host does not match API token");
+    }
+    match tokio::task::block_in_place(|| state.storage.get_session_sync(host, session_id)) {
+        Ok(sync) => Json(sync).into_response(),
+        Err(e) => {
+            log::error!("Failed to read session sync state: {e}");
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+        }
+    }
+}
+
 // --- Session search/context/facets GET endpoints ---
 
 async fn get_session_search(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "search:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
 
     let idx = match &state.session_index {
@@ -734,6 +2239,12 @@ async fn get_session_search(
     };
 
     let query = params.get("q").cloned().unwrap_or_default();
+    if let Err(e) = crate::search_query::parse(&query) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": e})),
+        );
+    }
     let page: usize = params.get("page").and_then(|v| v.parse().ok()).unwrap_or(0);
     let page_size: usize = params
         .get("page_size")
@@ -773,14 +2284,11 @@ async fn get_session_search(
 
 async fn get_session_context_api(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
     axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "search:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
 
     let idx = match &state.session_index {
@@ -834,13 +2342,10 @@ async fn get_session_context_api(
 
 async fn get_session_facets(
     State(state): State<Arc<ServerState>>,
-    headers: HeaderMap,
+    Extension(auth): Extension<Authentication>,
 ) -> impl IntoResponse {
-    if !check_auth(&headers, &state.secret) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"error": "Unauthorized"})),
-        );
+    if let Err((status, msg)) = authorize(&state, &auth, "search:read") {
+        return (status, Json(serde_json::json!({"error": msg})));
     }
 
     let idx = match &state.session_index {
@@ -866,3 +2371,53 @@ async fn get_session_facets(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(hostname: Option<&str>, forwards: bool) -> Caller {
+        Caller::Token {
+            id: 1,
+            hostname: hostname.map(str::to_string),
+            forwards,
+        }
+    }
+
+    #[test]
+    fn only_bound_or_relay_tokens_report() {
+        assert!(Caller::Shared.may_report_as("laptop"));
+        assert!(token(Some("laptop"), false).may_report_as("laptop"));
+        assert!(!token(Some("laptop"), false).may_report_as("desktop"));
+        assert!(!token(Some("laptop"), true).may_report_as("desktop"));
+        assert!(!token(None, false).may_report());
+        assert!(!token(None, false).may_report_as("laptop"));
+        assert!(token(None, true).may_report_as("laptop"));
+    }
+
+    #[test]
+    fn rate_limiter_evicts_the_least_recent_client_at_the_cap() {
+        let limiter = Mutex::new(HashMap::new());
+        assert!(check_client_rate_limit(&limiter, "token:0"));
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        for i in 1..MAX_TRACKED_CLIENTS {
+            assert!(check_client_rate_limit(&limiter, &format!("token:{i}")));
+        }
+        assert!(check_client_rate_limit(&limiter, "addr:10.0.0.1"));
+
+        let windows = limiter.lock();
+        assert_eq!(windows.len(), MAX_TRACKED_CLIENTS);
+        assert!(!windows.contains_key("token:0"));
+        assert!(windows.contains_key("token:1"));
+    }
+
+    #[test]
+    fn rate_limiter_counts_each_client_separately() {
+        let limiter = Mutex::new(HashMap::new());
+        for _ in 0..MAX_REQUESTS {
+            assert!(check_client_rate_limit(&limiter, "token:1"));
+        }
+        assert!(!check_client_rate_limit(&limiter, "token:1"));
+        assert!(check_client_rate_limit(&limiter, "token:2"));
+    }
+}
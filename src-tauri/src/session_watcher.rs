@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::events::EventBus;
+use crate::saved_searches;
+use crate::sessions::{self, SessionIndex};
+use crate::storage::Storage;
+
+// ── Session watcher ──
+//
+// Watches ~/.claude/projects for writes to session JSONL files and indexes
+// them, so transcripts from tools or machines that don't run the
+// `session-sync` hook show up without a restart. Changes are debounced: a
+// batch is indexed once writes pause, or after `MAX_DELAY` while a session
+// keeps streaming. Files go through the same mtime check as the startup scan
+// and the notify hook, so a file the hook already indexed is skipped.
+
+/// How long writes have to pause before the changed files are indexed.
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+/// Upper bound on how long a continuously written file waits.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Starts watching the Claude projects directory. Does nothing if the
+/// directory doesn't exist yet.
+pub fn spawn(
+    idx: Arc<SessionIndex>,
+    events: EventBus,
+    storage: Option<&'static Storage>,
+) -> Result<(), String> {
+    let projects_dir = sessions::claude_projects_dir()?;
+    if !projects_dir.exists() {
+        log::info!("No ~/.claude/projects directory found, not watching for sessions");
+        return Ok(());
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel::<PathBuf>();
+    let filter_dir = projects_dir.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        match res {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    if sessions::is_session_file(&filter_dir, &path) {
+                        // Only fails once the receiving task is gone
+                        let _ = tx.send(path);
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Session watcher error: {e}"),
+        }
+    })
+    .map_err(|e| format!("Failed to create session watcher: {e}"))?;
+    watcher
+        .watch(&projects_dir, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch {}: {e}", projects_dir.display()))?;
+    log::info!("Watching {} for session changes", projects_dir.display());
+
+    crate::runtime::spawn(async move {
+        // Dropping the watcher stops it, so it lives as long as the task
+        let _watcher = watcher;
+        run(rx, idx, events, storage).await;
+    });
+    Ok(())
+}
+
+async fn run(
+    mut rx: mpsc::UnboundedReceiver<PathBuf>,
+    idx: Arc<SessionIndex>,
+    events: EventBus,
+    storage: Option<&'static Storage>,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut changed = HashSet::from([first]);
+        let deadline = Instant::now() + MAX_DELAY;
+        while Instant::now() < deadline {
+            let wait = QUIET_PERIOD.min(deadline.saturating_duration_since(Instant::now()));
+            match tokio::time::timeout(wait, rx.recv()).await {
+                Ok(Some(path)) => {
+                    changed.insert(path);
+                }
+                // Writes paused, or the watcher is gone
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let paths: Vec<PathBuf> = changed.into_iter().collect();
+        match tokio::task::block_in_place(|| idx.index_changed_files(&paths, storage)) {
+            Ok(indexed) if indexed.is_empty() => {}
+            Ok(indexed) => {
+                log::info!(
+                    "Session watcher indexed {} messages from {} files",
+                    indexed.len(),
+                    paths.len()
+                );
+                events.emit("sessions-index-updated", indexed.len());
+                if let Some(storage) = storage {
+                    tokio::task::block_in_place(|| {
+                        saved_searches::check_watches(storage, &idx, &events, &indexed)
+                    });
+                }
+            }
+            Err(e) => log::error!("Session watcher indexing failed: {e}"),
+        }
+    }
+}
@@ -1,16 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Bound;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use tantivy::collector::{Count, FacetCollector, TopDocs};
-use tantivy::query::{BooleanQuery, Occur, QueryParser, RangeQuery, TermQuery};
+use tantivy::collector::{Count, DocSetCollector, FacetCollector, TopDocs};
+use tantivy::query::{BooleanQuery, Occur, PhraseQuery, RangeQuery, TermQuery, TermSetQuery};
 use tantivy::schema::*;
 use tantivy::snippet::SnippetGenerator;
 use tantivy::{DateTime, Index, IndexReader, IndexWriter, TantivyDocument, Term};
 
+use crate::redact::redact;
+use crate::search_query::{self, Clause, QueryField};
+
 // ---------------------------------------------------------------------------
 // Schema fields wrapper
 // ---------------------------------------------------------------------------
@@ -36,13 +39,62 @@ pub struct SessionSchema {
 }
 
 // ---------------------------------------------------------------------------
-// Index state -- tracks which files have been indexed and their mtimes
+// Index state -- tracks which files have been indexed and how far
 // ---------------------------------------------------------------------------
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct IndexState {
     /// Map of JSONL file path -> last-modified epoch seconds
     pub file_mtimes: HashMap<String, u64>,
+    /// Map of JSONL file path -> how far it has been parsed
+    #[serde(default)]
+    pub files: HashMap<String, FileProgress>,
+}
+
+/// How far a session JSONL has been indexed, so later passes only parse the
+/// lines appended since.
+#[derive(Serialize, Deserialize, Default)]
+pub struct FileProgress {
+    /// Byte offset just past the last line parsed
+    pub offset: u64,
+    /// Start offset and uuid of the last parsed line that had one. If that
+    /// line no longer carries the uuid, the file was rewritten.
+    pub last_line_start: u64,
+    pub last_uuid: String,
+    /// (role, timestamp) of the last message parsed
+    pub last_message: Option<(String, String)>,
+    /// Messages with tool uses whose tool_result hasn't been seen yet, so a
+    /// later result can re-index them
+    #[serde(default)]
+    pub pending: Vec<PendingMessage>,
+}
+
+/// Where to find a message that still waits on tool results. The message
+/// itself is not kept; it is parsed again from the file when a result for it
+/// arrives.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingMessage {
+    pub uuid: String,
+    /// Byte offset of the line the message was parsed from
+    pub offset: u64,
+    /// tool_use ids without a tool_result so far
+    pub tool_use_ids: Vec<String>,
+}
+
+/// What one [`SessionIndex::index_file`] pass changed.
+pub struct FileIndexUpdate {
+    /// Messages indexed for the first time
+    pub added: usize,
+    /// Earlier messages re-indexed because a tool_result for them arrived
+    pub updated: usize,
+    /// The file had been rewritten, so its session was dropped and
+    /// indexed again from the start
+    pub reset: bool,
+    /// (role, timestamp) of the added messages, preceded by the last
+    /// message from before them
+    pub timeline: Vec<(String, String)>,
+    /// Ids of the added and updated messages, for checking watches
+    pub message_ids: Vec<String>,
 }
 
 // ---------------------------------------------------------------------------
@@ -186,8 +238,7 @@ impl SessionIndex {
     pub fn save_state(&self) -> Result<(), String> {
         let state_path = self.index_dir.join("index_state.json");
         let state = self.state.lock();
-        let json =
-            serde_json::to_string_pretty(&*state).map_err(|e| format!("Serialize state: {e}"))?;
+        let json = serde_json::to_string(&*state).map_err(|e| format!("Serialize state: {e}"))?;
         std::fs::write(&state_path, json).map_err(|e| format!("Write state: {e}"))?;
         Ok(())
     }
@@ -259,31 +310,34 @@ impl SessionIndex {
     ) -> Result<(), String> {
         let mut doc = TantivyDocument::default();
 
+        // Secrets in prompts and tool data never reach the index
+        let content = redact(&msg.content);
+        let redact_all = |items: &[String]| -> Vec<String> {
+            items.iter().map(|s| redact(s).into_owned()).collect()
+        };
+        let code_changes = redact_all(&msg.code_changes);
+        let commands_run = redact_all(&msg.commands_run);
+        let tool_details = redact_all(&msg.tool_details);
+
         doc.add_text(self.fields.message_id, &msg.uuid);
         doc.add_text(self.fields.session_id, &msg.session_id);
-        doc.add_text(self.fields.content, &msg.content);
+        doc.add_text(self.fields.content, &content);
         doc.add_text(self.fields.role, &msg.role);
         doc.add_text(self.fields.git_branch, &msg.git_branch);
         doc.add_text(self.fields.tools_used, msg.tools_used.join(" "));
         doc.add_text(self.fields.files_modified, msg.files_modified.join(" "));
-        doc.add_text(self.fields.code_changes, msg.code_changes.join("\n"));
-        doc.add_text(self.fields.commands_run, msg.commands_run.join("\n"));
-        doc.add_text(self.fields.tool_details, msg.tool_details.join("\n"));
+        doc.add_text(self.fields.code_changes, code_changes.join("\n"));
+        doc.add_text(self.fields.commands_run, commands_run.join("\n"));
+        doc.add_text(self.fields.tool_details, tool_details.join("\n"));
 
         // Compose display_text: text content + tool summaries
         let mut display_parts: Vec<String> = Vec::new();
-        if !msg.content.is_empty() {
-            display_parts.push(truncate(&msg.content, 500));
-        }
-        for change in &msg.code_changes {
-            display_parts.push(change.clone());
-        }
-        for cmd in &msg.commands_run {
-            display_parts.push(cmd.clone());
-        }
-        for detail in &msg.tool_details {
-            display_parts.push(detail.clone());
+        if !content.is_empty() {
+            display_parts.push(truncate(&content, 500));
         }
+        display_parts.extend(code_changes);
+        display_parts.extend(commands_run);
+        display_parts.extend(tool_details);
         let display_text = truncate(&display_parts.join("\n"), 2000);
         doc.add_text(self.fields.display_text, &display_text);
 
@@ -311,128 +365,305 @@ impl SessionIndex {
         Ok(())
     }
 
-    /// Scan ~/.claude/projects/*/*.jsonl and index new/modified files.
-    /// Returns the number of newly indexed messages.
-    pub fn startup_scan(
+    /// Index a session JSONL if it changed since it was last indexed: only
+    /// what was appended since the last pass is parsed, and earlier messages
+    /// that a new tool_result completed are re-indexed. Returns None if the
+    /// file is unchanged. The caller commits the writer and saves the state.
+    pub fn index_file(
         &self,
-        app_handle: &tauri::AppHandle,
+        path: &Path,
+        project_facet: &str,
+        host_facet: &str,
         storage: Option<&crate::storage::Storage>,
-    ) -> Result<usize, String> {
-        use tauri::Emitter;
+    ) -> Result<Option<FileIndexUpdate>, String> {
+        let mut state = self.state.lock();
+        self.index_if_modified(&mut state, path, project_facet, host_facet, storage)
+    }
 
-        let projects_dir = dirs::home_dir()
-            .ok_or("Cannot determine home directory")?
-            .join(".claude")
-            .join("projects");
+    /// Index session files reported by the filesystem watcher, then commit
+    /// and save the state. Returns the ids of the messages indexed.
+    pub fn index_changed_files(
+        &self,
+        paths: &[PathBuf],
+        storage: Option<&crate::storage::Storage>,
+    ) -> Result<Vec<String>, String> {
+        let hostname = local_hostname();
+        let mut indexed = Vec::new();
+        let mut state = self.state.lock();
 
-        if !projects_dir.exists() {
-            log::info!("No ~/.claude/projects directory found, skipping scan");
-            return Ok(0);
+        for path in paths {
+            let project_name = path
+                .parent()
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str())
+                .map(Self::project_display_name)
+                .unwrap_or_else(|| "unknown".to_string());
+            match self.index_if_modified(&mut state, path, &project_name, &hostname, storage) {
+                Ok(Some(update)) => indexed.extend(update.message_ids),
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to index {}: {e}", path.display()),
+            }
         }
 
-        let mut total_indexed = 0usize;
-        let mut state = self.state.lock();
+        if !indexed.is_empty() {
+            let mut writer = self.writer.lock();
+            writer.commit().map_err(|e| format!("Commit index: {e}"))?;
+        }
 
-        // Collect all JSONL files and their mtimes
-        let project_entries: Vec<_> = std::fs::read_dir(&projects_dir)
-            .map_err(|e| format!("Read projects dir: {e}"))?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().is_dir())
+        // Must drop state lock before save_state which acquires it
+        drop(state);
+
+        self.save_state()?;
+        Ok(indexed)
+    }
+
+    /// Runs [`Self::index_appended`] unless the file's mtime matches the one
+    /// recorded at its last pass. The startup scan, the watcher and the
+    /// notify hook all go through here, so whichever sees a change first
+    /// indexes it and the others find nothing to do.
+    fn index_if_modified(
+        &self,
+        state: &mut IndexState,
+        path: &Path,
+        project_facet: &str,
+        host_facet: &str,
+        storage: Option<&crate::storage::Storage>,
+    ) -> Result<Option<FileIndexUpdate>, String> {
+        let meta = std::fs::metadata(path).map_err(|e| format!("Stat {}: {e}", path.display()))?;
+        let mtime = mtime_secs(&meta);
+        let file_key = path.to_string_lossy().to_string();
+
+        // mtimes have one-second resolution, so an append within the same
+        // second is caught by the length instead
+        if state.file_mtimes.get(&file_key) == Some(&mtime)
+            && state
+                .files
+                .get(&file_key)
+                .is_none_or(|p| p.offset == meta.len())
+        {
+            return Ok(None);
+        }
+
+        let update = self.index_appended(state, path, project_facet, host_facet, storage)?;
+        state.file_mtimes.insert(file_key, mtime);
+        Ok(Some(update))
+    }
+
+    fn index_appended(
+        &self,
+        state: &mut IndexState,
+        path: &Path,
+        project_facet: &str,
+        host_facet: &str,
+        storage: Option<&crate::storage::Storage>,
+    ) -> Result<FileIndexUpdate, String> {
+        let file_key = path.to_string_lossy().to_string();
+        let progress = state.files.entry(file_key).or_default();
+        let appended = extract_appended_messages(path, progress)?;
+
+        {
+            let writer = self.writer.lock();
+            // A rewritten file is indexed again from scratch
+            if appended.reset
+                && let Some(session_id) = path.file_stem().and_then(|s| s.to_str())
+            {
+                writer.delete_term(Term::from_field_text(self.fields.session_id, session_id));
+                if let Some(storage) = storage
+                    && let Err(e) = storage.delete_tool_actions_for_session(session_id)
+                {
+                    log::warn!("Failed to delete old tool_actions: {e}");
+                }
+            }
+            // Replace rather than duplicate anything indexed before
+            for msg in appended.updated.iter().chain(&appended.new) {
+                if !msg.uuid.is_empty() {
+                    writer.delete_term(Term::from_field_text(self.fields.message_id, &msg.uuid));
+                }
+            }
+        }
+
+        for msg in appended.updated.iter().chain(&appended.new) {
+            if let Err(e) = self.index_message(msg, project_facet, host_facet) {
+                log::warn!("Failed to index message: {e}");
+            }
+            // Store tool actions in SQLite
+            if !msg.tool_actions.is_empty()
+                && let Some(storage) = storage
+                && let Err(e) =
+                    storage.replace_tool_actions(&msg.tool_actions, &msg.uuid, &msg.session_id)
+            {
+                log::warn!("Failed to store tool actions: {e}");
+            }
+        }
+
+        let message_ids = appended
+            .updated
+            .iter()
+            .chain(&appended.new)
+            .map(|m| m.uuid.clone())
             .collect();
+        let timeline = appended
+            .previous
+            .into_iter()
+            .chain(
+                appended
+                    .new
+                    .iter()
+                    .map(|m| (m.role.clone(), m.timestamp.clone())),
+            )
+            .collect();
+        Ok(FileIndexUpdate {
+            added: appended.new.len(),
+            updated: appended.updated.len(),
+            reset: appended.reset,
+            timeline,
+            message_ids,
+        })
+    }
 
-        // Detect hostname from system
-        let hostname = std::env::var("HOSTNAME")
-            .or_else(|_| std::env::var("COMPUTERNAME"))
-            .or_else(|_| {
-                // /etc/hostname exists on Linux; on macOS use `hostname` command
-                std::fs::read_to_string("/etc/hostname")
-                    .map(|s| s.trim().to_string())
-                    .or_else(|_| {
-                        std::process::Command::new("hostname")
-                            .output()
-                            .map_err(|e| e.to_string())
-                            .and_then(|o| {
-                                String::from_utf8(o.stdout)
-                                    .map(|s| s.trim().to_string())
-                                    .map_err(|e| e.to_string())
-                            })
+    /// Re-indexes every message whose text the current redaction rules would
+    /// change, then merges segments so the replaced documents are gone from
+    /// disk as well. Also rewrites `index_state.json`, which drops message
+    /// bodies that older versions kept there. Returns the number of messages
+    /// rewritten.
+    pub fn redact_existing(&self) -> Result<usize, String> {
+        self.reader
+            .reload()
+            .map_err(|e| format!("Reload index reader: {e}"))?;
+        let searcher = self.searcher();
+        let f = &self.fields;
+        let addrs = searcher
+            .search(&tantivy::query::AllQuery, &DocSetCollector)
+            .map_err(|e| format!("Search error: {e}"))?;
+
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut rewritten = 0usize;
+        for addr in addrs {
+            let doc: TantivyDocument = searcher
+                .doc(addr)
+                .map_err(|e| format!("Doc retrieval: {e}"))?;
+            let get_text = |field: Field| -> String {
+                doc.get_first(field)
+                    .and_then(|v| v.as_value().as_str().map(|s| s.to_string()))
+                    .unwrap_or_default()
+            };
+            let get_list = |field: Field, sep: char| -> Vec<String> {
+                let text = get_text(field);
+                if text.is_empty() {
+                    Vec::new()
+                } else {
+                    text.split(sep).map(|s| s.to_string()).collect()
+                }
+            };
+            let get_facet_str = |field: Field| -> String {
+                doc.get_first(field)
+                    .and_then(|v| {
+                        v.as_value()
+                            .as_facet()
+                            .map(|f| f.strip_prefix('/').unwrap_or(f).to_string())
                     })
-            })
-            .unwrap_or_else(|_| "unknown".to_string());
+                    .unwrap_or_default()
+            };
 
-        for project_entry in &project_entries {
-            let project_dir = project_entry.path();
-            let project_dir_name = project_dir
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-            let project_name = Self::project_display_name(project_dir_name);
+            let message_id = get_text(f.message_id);
+            if message_id.is_empty() || !seen.insert(message_id.clone()) {
+                continue;
+            }
+            if ![f.content, f.code_changes, f.commands_run, f.tool_details]
+                .into_iter()
+                .any(|field| crate::redact::contains_secret(&get_text(field)))
+            {
+                continue;
+            }
 
-            let jsonl_files: Vec<_> = std::fs::read_dir(&project_dir)
-                .map_err(|e| format!("Read project dir: {e}"))?
-                .filter_map(|e| e.ok())
-                .filter(|e| e.path().extension().is_some_and(|ext| ext == "jsonl"))
-                .collect();
+            let timestamp = doc
+                .get_first(f.timestamp)
+                .and_then(|v| v.as_value().as_datetime())
+                .and_then(|dt| chrono::DateTime::from_timestamp(dt.into_timestamp_secs(), 0))
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_default();
+            // index_message applies redaction as it rebuilds the document
+            let msg = ExtractedMessage {
+                uuid: message_id.clone(),
+                session_id: get_text(f.session_id),
+                role: get_text(f.role),
+                content: get_text(f.content),
+                timestamp,
+                git_branch: get_text(f.git_branch),
+                tools_used: get_list(f.tools_used, ' '),
+                files_modified: get_list(f.files_modified, ' '),
+                code_changes: get_list(f.code_changes, '\n'),
+                commands_run: get_list(f.commands_run, '\n'),
+                tool_details: get_list(f.tool_details, '\n'),
+                tool_actions: Vec::new(),
+            };
+            self.writer
+                .lock()
+                .delete_term(Term::from_field_text(f.message_id, &message_id));
+            self.index_message(&msg, &get_facet_str(f.project), &get_facet_str(f.host))?;
+            rewritten += 1;
+        }
+
+        // Merge and collect even when nothing was rewritten here: documents
+        // deleted earlier may still sit in old segment files
+        {
+            let mut writer = self.writer.lock();
+            writer.commit().map_err(|e| format!("Commit index: {e}"))?;
+            let segments = self
+                .index
+                .searchable_segment_ids()
+                .map_err(|e| format!("List segments: {e}"))?;
+            if !segments.is_empty()
+                && let Err(e) = writer.merge(&segments).wait()
+            {
+                log::warn!("Failed to merge index segments after redaction: {e}");
+            }
+            if let Err(e) = writer.garbage_collect_files().wait() {
+                log::warn!("Failed to remove stale index files: {e}");
+            }
+        }
+        self.save_state()?;
+        Ok(rewritten)
+    }
 
-            for entry in &jsonl_files {
-                let file_path = entry.path();
-                let file_key = file_path.to_string_lossy().to_string();
+    /// Scan ~/.claude/projects/*/*.jsonl and index new/modified files.
+    /// Returns the number of newly indexed messages.
+    pub fn startup_scan(
+        &self,
+        events: &crate::events::EventBus,
+        storage: Option<&crate::storage::Storage>,
+    ) -> Result<usize, String> {
+        let projects_dir = claude_projects_dir()?;
 
-                let mtime = entry
-                    .metadata()
-                    .ok()
-                    .and_then(|m| m.modified().ok())
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0);
+        if !projects_dir.exists() {
+            log::info!("No ~/.claude/projects directory found, skipping scan");
+            return Ok(0);
+        }
 
-                let known_mtime = state.file_mtimes.get(&file_key).copied();
+        let mut indexed = Vec::new();
+        let mut state = self.state.lock();
+        // Nothing indexed yet, so everything found is history
+        let backfill = state.files.is_empty() && state.file_mtimes.is_empty();
 
-                if known_mtime == Some(mtime) {
-                    // File hasn't changed since last index
-                    continue;
-                }
+        // Collect all JSONL files
+        let session_files = list_session_files(&projects_dir)?;
 
-                // If file was previously indexed but modified, delete old docs + tool_actions
-                if known_mtime.is_some()
-                    && let Some(session_id) = file_path.file_stem().and_then(|s| s.to_str())
-                {
-                    let term = Term::from_field_text(self.fields.session_id, session_id);
-                    let writer = self.writer.lock();
-                    writer.delete_term(term);
-                    // Also clear old tool_actions to prevent duplicates
-                    if let Some(storage) = storage
-                        && let Err(e) = storage.delete_tool_actions_for_session(session_id)
-                    {
-                        log::warn!("Failed to delete old tool_actions: {e}");
-                    }
-                }
+        let hostname = local_hostname();
 
-                // Index messages from this file
-                let messages = extract_messages_from_jsonl(&file_path);
-                for msg in &messages {
-                    if let Err(e) = self.index_message(msg, &project_name, &hostname) {
-                        log::warn!("Failed to index message: {e}");
-                    }
-                    // Store tool actions in SQLite
-                    if !msg.tool_actions.is_empty()
-                        && let Some(storage) = storage
-                        && let Err(e) = storage.store_tool_actions(
-                            &msg.tool_actions,
-                            &msg.uuid,
-                            &msg.session_id,
-                        )
-                    {
-                        log::warn!("Failed to store tool actions: {e}");
-                    }
-                }
+        for session_file in &session_files {
+            let project_name = Self::project_display_name(&session_file.project_dir_name);
+            let file_path = &session_file.path;
 
-                total_indexed += messages.len();
-                state.file_mtimes.insert(file_key, mtime);
+            // Index only what was appended since the last pass
+            match self.index_if_modified(&mut state, file_path, &project_name, &hostname, storage) {
+                Ok(Some(update)) => indexed.extend(update.message_ids),
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to index {}: {e}", file_path.display()),
             }
         }
 
         // Commit all changes
+        let total_indexed = indexed.len();
         if total_indexed > 0 {
             let mut writer = self.writer.lock();
             writer.commit().map_err(|e| format!("Commit index: {e}"))?;
@@ -444,7 +675,12 @@ impl SessionIndex {
         self.save_state()?;
 
         log::info!("Session index scan complete: {total_indexed} messages indexed");
-        let _ = app_handle.emit("sessions-index-updated", total_indexed);
+        events.emit("sessions-index-updated", total_indexed);
+        if let Some(storage) = storage
+            && !backfill
+        {
+            crate::saved_searches::check_watches(storage, self, events, &indexed);
+        }
 
         Ok(total_indexed)
     }
@@ -453,7 +689,8 @@ impl SessionIndex {
     // Search
     // -------------------------------------------------------------------
 
-    /// Search the index with a query string and optional filters.
+    /// Search the index with a query in the [`search_query`] syntax and
+    /// optional filters. The filters are ANDed with the query.
     pub fn search(
         &self,
         query: &str,
@@ -461,31 +698,41 @@ impl SessionIndex {
         sort_by: &str,
         page: usize,
         page_size: usize,
+    ) -> Result<SearchResults, String> {
+        self.search_within(query, filters, None, sort_by, page, page_size)
+    }
+
+    /// Like [`Self::search`], but only among the given messages, newest
+    /// first. Watches use it to look at just what an index update added.
+    pub fn search_messages(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        message_ids: &[String],
+        limit: usize,
+    ) -> Result<SearchResults, String> {
+        self.search_within(query, filters, Some(message_ids), "recency", 0, limit)
+    }
+
+    fn search_within(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        message_ids: Option<&[String]>,
+        sort_by: &str,
+        page: usize,
+        page_size: usize,
     ) -> Result<SearchResults, String> {
         let start = std::time::Instant::now();
         let searcher = self.searcher();
         let f = &self.fields;
 
-        // Build query parser targeting content, tools_used, files_modified, and new fields
-        let mut parser = QueryParser::for_index(
-            &self.index,
-            vec![
-                f.content,
-                f.tools_used,
-                f.files_modified,
-                f.code_changes,
-                f.commands_run,
-                f.tool_details,
-                f.display_text,
-            ],
-        );
-        parser.set_conjunction_by_default();
-
-        let text_query: Box<dyn tantivy::query::Query> = if query.trim().is_empty() {
-            Box::new(tantivy::query::AllQuery)
-        } else {
-            let (parsed, _errors) = parser.parse_query_lenient(query);
-            parsed
+        let text_query: Box<dyn tantivy::query::Query> = match search_query::parse(query)? {
+            None => Box::new(tantivy::query::AllQuery),
+            // Nothing searchable, e.g. only punctuation
+            Some(clause) => self
+                .compile_clause(&clause)
+                .unwrap_or_else(|| Box::new(tantivy::query::EmptyQuery)),
         };
 
         // Combine with filter clauses via BooleanQuery
@@ -494,49 +741,32 @@ impl SessionIndex {
 
         // Project facet filter
         if let Some(ref proj) = filters.project {
-            let facet = Facet::from(&format!("/{proj}"));
-            let term = Term::from_facet(f.project, &facet);
-            clauses.push((
-                Occur::Must,
-                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
-            ));
+            clauses.push((Occur::Must, facet_query(f.project, proj)));
         }
 
         // Host facet filter
         if let Some(ref host) = filters.host {
-            let facet = Facet::from(&format!("/{host}"));
-            let term = Term::from_facet(f.host, &facet);
-            clauses.push((
-                Occur::Must,
-                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
-            ));
+            clauses.push((Occur::Must, facet_query(f.host, host)));
         }
 
         // Role filter
         if let Some(ref role) = filters.role {
-            let term = Term::from_field_text(f.role, role);
-            clauses.push((
-                Occur::Must,
-                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
-            ));
+            clauses.push((Occur::Must, exact_query(f.role, role)));
         }
 
         // Git branch filter
         if let Some(ref branch) = filters.git_branch {
-            let term = Term::from_field_text(f.git_branch, branch);
-            clauses.push((
-                Occur::Must,
-                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
-            ));
+            clauses.push((Occur::Must, exact_query(f.git_branch, branch)));
         }
 
         // Session ID filter
         if let Some(ref sid) = filters.session_id {
-            let term = Term::from_field_text(f.session_id, sid);
-            clauses.push((
-                Occur::Must,
-                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
-            ));
+            clauses.push((Occur::Must, exact_query(f.session_id, sid)));
+        }
+
+        if let Some(ids) = message_ids {
+            let terms = ids.iter().map(|id| Term::from_field_text(f.message_id, id));
+            clauses.push((Occur::Must, Box::new(TermSetQuery::new(terms))));
         }
 
         // Date range filter
@@ -676,6 +906,122 @@ impl SessionIndex {
         })
     }
 
+    /// Turns a parsed query into a tantivy query. Returns None for a clause
+    /// with nothing to search for, such as text that is all punctuation.
+    fn compile_clause(&self, clause: &Clause) -> Option<Box<dyn tantivy::query::Query>> {
+        let f = &self.fields;
+        match clause {
+            Clause::Text(text) => self.text_query(
+                &[
+                    f.content,
+                    f.tools_used,
+                    f.files_modified,
+                    f.code_changes,
+                    f.commands_run,
+                    f.tool_details,
+                    f.display_text,
+                ],
+                text,
+            ),
+            Clause::Field(field, value) => match field {
+                QueryField::Project => Some(facet_query(f.project, value)),
+                QueryField::Host => Some(facet_query(f.host, value)),
+                QueryField::Role => Some(exact_query(f.role, value)),
+                QueryField::Branch => Some(exact_query(f.git_branch, value)),
+                QueryField::Session => Some(exact_query(f.session_id, value)),
+                QueryField::Tool => self.text_query(&[f.tools_used], value),
+                QueryField::File => self.text_query(&[f.files_modified], value),
+                QueryField::Command => self.text_query(&[f.commands_run], value),
+            },
+            Clause::After(secs) => Some(Box::new(RangeQuery::new(
+                Bound::Included(self.date_term(*secs)),
+                Bound::Unbounded,
+            ))),
+            Clause::Before(secs) => Some(Box::new(RangeQuery::new(
+                Bound::Unbounded,
+                Bound::Excluded(self.date_term(*secs)),
+            ))),
+            Clause::Not(inner) => {
+                let inner = self.compile_clause(inner)?;
+                Some(Box::new(BooleanQuery::new(vec![
+                    (Occur::Must, Box::new(tantivy::query::AllQuery)),
+                    (Occur::MustNot, inner),
+                ])))
+            }
+            Clause::And(terms) => {
+                let mut clauses = Vec::new();
+                for term in terms {
+                    let (occur, term) = match term {
+                        Clause::Not(inner) => (Occur::MustNot, inner.as_ref()),
+                        _ => (Occur::Must, term),
+                    };
+                    if let Some(query) = self.compile_clause(term) {
+                        clauses.push((occur, query));
+                    }
+                }
+                if clauses.is_empty() {
+                    return None;
+                }
+                // Exclusions alone match nothing in tantivy
+                if clauses.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+                    clauses.push((Occur::Must, Box::new(tantivy::query::AllQuery)));
+                }
+                Some(Box::new(BooleanQuery::new(clauses)))
+            }
+            Clause::Or(terms) => {
+                let clauses: Vec<_> = terms
+                    .iter()
+                    .filter_map(|term| self.compile_clause(term))
+                    .map(|query| (Occur::Should, query))
+                    .collect();
+                (!clauses.is_empty())
+                    .then(|| Box::new(BooleanQuery::new(clauses)) as Box<dyn tantivy::query::Query>)
+            }
+        }
+    }
+
+    /// Matches `text` in any of `fields`, as a phrase if it tokenizes to more
+    /// than one word. None if it has no words.
+    fn text_query(&self, fields: &[Field], text: &str) -> Option<Box<dyn tantivy::query::Query>> {
+        // All text fields share the default tokenizer
+        let mut analyzer = self.index.tokenizer_for_field(fields[0]).ok()?;
+        let mut stream = analyzer.token_stream(text);
+        let mut words = Vec::new();
+        while stream.advance() {
+            words.push(stream.token().text.clone());
+        }
+        if words.is_empty() {
+            return None;
+        }
+
+        let mut queries: Vec<(Occur, Box<dyn tantivy::query::Query>)> = fields
+            .iter()
+            .map(|&field| {
+                let mut terms: Vec<Term> = words
+                    .iter()
+                    .map(|w| Term::from_field_text(field, w))
+                    .collect();
+                let query: Box<dyn tantivy::query::Query> = if terms.len() == 1 {
+                    Box::new(TermQuery::new(
+                        terms.remove(0),
+                        IndexRecordOption::WithFreqs,
+                    ))
+                } else {
+                    Box::new(PhraseQuery::new(terms))
+                };
+                (Occur::Should, query)
+            })
+            .collect();
+        if queries.len() == 1 {
+            return queries.pop().map(|(_, query)| query);
+        }
+        Some(Box::new(BooleanQuery::new(queries)))
+    }
+
+    fn date_term(&self, secs: i64) -> Term {
+        Term::from_field_date(self.fields.timestamp, DateTime::from_timestamp_secs(secs))
+    }
+
     // -------------------------------------------------------------------
     // Facets
     // -------------------------------------------------------------------
@@ -790,11 +1136,12 @@ impl SessionIndex {
                     .collect::<Vec<_>>()
                     .join("\n");
 
+                // Read straight from the transcript, so not redacted yet
                 ContextMessage {
                     message_id: m.uuid.clone(),
                     role: m.role.clone(),
-                    content: m.content.clone(),
-                    tool_summary,
+                    content: redact(&m.content).into_owned(),
+                    tool_summary: redact(&tool_summary).into_owned(),
                     tools_used: m.tools_used.join(" "),
                     timestamp: m.timestamp.clone(),
                     is_match: m.uuid == message_id,
@@ -810,11 +1157,23 @@ impl SessionIndex {
     }
 }
 
+/// Matches a project or host facet and everything below it.
+fn facet_query(field: Field, value: &str) -> Box<dyn tantivy::query::Query> {
+    let term = Term::from_facet(field, &Facet::from(&format!("/{value}")));
+    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+}
+
+/// Matches an untokenized field exactly.
+fn exact_query(field: Field, value: &str) -> Box<dyn tantivy::query::Query> {
+    let term = Term::from_field_text(field, value);
+    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+}
+
 // ---------------------------------------------------------------------------
 // Search result types (serializable for frontend)
 // ---------------------------------------------------------------------------
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SearchHit {
     pub message_id: String,
     pub session_id: String,
@@ -833,14 +1192,14 @@ pub struct SearchHit {
     pub score: f32,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SearchResults {
     pub hits: Vec<SearchHit>,
     pub total_hits: u64,
     pub query_time_ms: u64,
 }
 
-#[derive(Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, schemars::JsonSchema)]
 pub struct SearchFilters {
     pub project: Option<String>,
     pub host: Option<String>,
@@ -851,19 +1210,19 @@ pub struct SearchFilters {
     pub session_id: Option<String>,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct FacetCount {
     pub name: String,
     pub count: u64,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SearchFacets {
     pub projects: Vec<FacetCount>,
     pub hosts: Vec<FacetCount>,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct ContextMessage {
     pub message_id: String,
     pub role: String,
@@ -874,7 +1233,7 @@ pub struct ContextMessage {
     pub is_match: bool,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug, schemars::JsonSchema)]
 pub struct SessionContext {
     pub session_id: String,
     pub project: String,
@@ -886,6 +1245,7 @@ pub struct SessionContext {
 // ---------------------------------------------------------------------------
 
 #[allow(dead_code)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ToolAction {
     pub tool_use_id: String,
     pub tool_name: String,
@@ -897,6 +1257,7 @@ pub struct ToolAction {
     pub timestamp: String,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ExtractedMessage {
     pub uuid: String,
     pub session_id: String,
@@ -915,6 +1276,112 @@ pub struct ExtractedMessage {
     pub tool_actions: Vec<ToolAction>,
 }
 
+/// Token usage of one assistant API call, as recorded in a session JSONL.
+pub struct TurnUsage {
+    /// Anthropic message id; streamed content blocks of one call share it
+    pub message_id: String,
+    pub session_id: String,
+    pub timestamp: String,
+    pub model: Option<String>,
+    pub cwd: Option<String>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+}
+
+/// Everything extracted from a single session JSONL in one pass.
+pub struct ExtractedSession {
+    pub messages: Vec<ExtractedMessage>,
+    pub usage: Vec<TurnUsage>,
+}
+
+// ---------------------------------------------------------------------------
+// Session file discovery
+// ---------------------------------------------------------------------------
+
+/// A session transcript under ~/.claude/projects/<project-dir>/.
+pub struct SessionFile {
+    pub path: PathBuf,
+    pub project_dir_name: String,
+}
+
+pub fn claude_projects_dir() -> Result<PathBuf, String> {
+    Ok(dirs::home_dir()
+        .ok_or("Cannot determine home directory")?
+        .join(".claude")
+        .join("projects"))
+}
+
+/// List every `*.jsonl` one level below each project directory.
+pub fn list_session_files(projects_dir: &Path) -> Result<Vec<SessionFile>, String> {
+    let project_entries: Vec<_> = std::fs::read_dir(projects_dir)
+        .map_err(|e| format!("Read projects dir: {e}"))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .collect();
+
+    let mut files = Vec::new();
+    for project_entry in &project_entries {
+        let project_dir = project_entry.path();
+        let project_dir_name = project_dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+
+        let jsonl_files = std::fs::read_dir(&project_dir)
+            .map_err(|e| format!("Read project dir: {e}"))?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "jsonl"));
+
+        for entry in jsonl_files {
+            files.push(SessionFile {
+                path: entry.path(),
+                project_dir_name: project_dir_name.clone(),
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `path` is one of the files [`list_session_files`] would return.
+pub fn is_session_file(projects_dir: &Path, path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jsonl")
+        && path.parent().and_then(|p| p.parent()) == Some(projects_dir)
+}
+
+/// Detect hostname from system
+pub(crate) fn local_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .or_else(|_| {
+            // /etc/hostname exists on Linux; on macOS use `hostname` command
+            std::fs::read_to_string("/etc/hostname")
+                .map(|s| s.trim().to_string())
+                .or_else(|_| {
+                    std::process::Command::new("hostname")
+                        .output()
+                        .map_err(|e| e.to_string())
+                        .and_then(|o| {
+                            String::from_utf8(o.stdout)
+                                .map(|s| s.trim().to_string())
+                                .map_err(|e| e.to_string())
+                        })
+                })
+        })
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Last-modified epoch seconds, or 0 if the platform doesn't report it.
+fn mtime_secs(meta: &std::fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 // ---------------------------------------------------------------------------
 // JSONL parsing
 // ---------------------------------------------------------------------------
@@ -998,51 +1465,306 @@ fn build_tool_summary(
     }
 }
 
-#[allow(dead_code)]
+/// A tool_use waiting for its tool_result.
 struct ToolUseEntry {
     tool_name: String,
-    category: String,
-    file_path: Option<String>,
     summary: String,
-    full_input: Option<String>,
-    timestamp: String,
-    // Index into messages vec where this tool_use appeared
-    message_idx: usize,
+    /// uuid of the message the tool_use appeared in
+    message_uuid: String,
 }
 
 /// Extract indexable messages from a Claude Code JSONL session file.
 /// Only "user" and "assistant" type messages are extracted.
 /// isMeta messages and messages with empty content are skipped.
 pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
+    extract_session_from_jsonl(path).messages
+}
+
+/// Like [`extract_messages_from_jsonl`], but also collects per-call token
+/// usage from assistant entries (including ones with no indexable content).
+pub fn extract_session_from_jsonl(path: &Path) -> ExtractedSession {
     let contents = match std::fs::read_to_string(path) {
         Ok(c) => c,
         Err(e) => {
             log::warn!("Failed to read JSONL {}: {e}", path.display());
-            return Vec::new();
+            return ExtractedSession {
+                messages: Vec::new(),
+                usage: Vec::new(),
+            };
         }
     };
 
-    let mut messages: Vec<ExtractedMessage> = Vec::new();
-    // Maps tool_use block id -> entry for cross-message correlation
-    let mut tool_use_map: HashMap<String, ToolUseEntry> = HashMap::new();
-
+    let mut parser = SessionParser::default();
     for line in contents.lines() {
         if line.trim().is_empty() {
             continue;
         }
-        let obj: serde_json::Value = match serde_json::from_str(line) {
-            Ok(v) => v,
-            Err(_) => continue,
+        if let Ok(obj) = serde_json::from_str(line) {
+            parser.parse_line(&obj);
+        }
+    }
+
+    ExtractedSession {
+        messages: parser.messages,
+        usage: parser.usage,
+    }
+}
+
+/// Messages whose tool uses are still unanswered are tracked in
+/// [`FileProgress`] up to this many per file; older ones never get their tool
+/// output.
+const MAX_OPEN_MESSAGES: usize = 32;
+
+/// Messages parsed by [`extract_appended_messages`].
+pub struct AppendedMessages {
+    /// The file was truncated or rewritten since the last call, so parsing
+    /// restarted from the beginning and anything indexed for it is stale
+    pub reset: bool,
+    /// Messages from the newly appended lines
+    pub new: Vec<ExtractedMessage>,
+    /// Messages from earlier calls that a new tool_result completed
+    pub updated: Vec<ExtractedMessage>,
+    /// (role, timestamp) of the last message before the appended lines
+    pub previous: Option<(String, String)>,
+}
+
+/// Parse only the lines appended to a session JSONL since `progress` was
+/// last advanced, and advance it. A trailing line without a newline is left
+/// for the next call unless it is already complete JSON.
+pub fn extract_appended_messages(
+    path: &Path,
+    progress: &mut FileProgress,
+) -> Result<AppendedMessages, String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let read_err = |e: std::io::Error| format!("Failed to read JSONL {}: {e}", path.display());
+    let mut file = std::fs::File::open(path).map_err(read_err)?;
+    let len = file.metadata().map_err(read_err)?.len();
+
+    let reset = progress.offset > 0
+        && (len < progress.offset
+            || !still_has_last_line(&mut file, progress).map_err(read_err)?);
+    let start = if reset { 0 } else { progress.offset };
+
+    let mut appended = Vec::new();
+    file.seek(SeekFrom::Start(start)).map_err(read_err)?;
+    file.read_to_end(&mut appended).map_err(read_err)?;
+
+    if reset {
+        *progress = FileProgress::default();
+    }
+    let previous = progress.last_message.clone();
+    let (open_messages, tool_use_map) =
+        reload_pending(&mut file, progress, &appended).map_err(read_err)?;
+    let mut parser = SessionParser {
+        tool_use_map,
+        open_messages,
+        ..SessionParser::default()
+    };
+
+    // Line start of each entry in `parser.messages`
+    let mut message_offsets = Vec::new();
+    let mut pos = 0usize;
+    while pos < appended.len() {
+        let (line, next) = match appended[pos..].iter().position(|&b| b == b'\n') {
+            Some(i) => (&appended[pos..pos + i], Some(pos + i + 1)),
+            None => (&appended[pos..], None),
         };
+        let obj = std::str::from_utf8(line)
+            .ok()
+            .filter(|l| !l.trim().is_empty())
+            .and_then(|l| serde_json::from_str::<serde_json::Value>(l).ok());
+        if next.is_none() && obj.is_none() {
+            break;
+        }
+        if let Some(obj) = obj {
+            let line_start = progress.offset + pos as u64;
+            if let Some(uuid) = obj.get("uuid").and_then(|v| v.as_str())
+                && !uuid.is_empty()
+            {
+                progress.last_line_start = line_start;
+                progress.last_uuid = uuid.to_string();
+            }
+            parser.parse_line(&obj);
+            message_offsets.resize(parser.messages.len(), line_start);
+        }
+        pos = next.unwrap_or(appended.len());
+    }
+    progress.offset += pos as u64;
+
+    let SessionParser {
+        messages,
+        open_messages,
+        updated,
+        tool_use_map,
+        ..
+    } = parser;
+
+    if let Some(last) = messages.last() {
+        progress.last_message = Some((last.role.clone(), last.timestamp.clone()));
+    }
+    let reloaded: HashSet<String> = open_messages.iter().map(|m| m.uuid.clone()).collect();
+    let updated_messages: Vec<ExtractedMessage> = open_messages
+        .into_iter()
+        .filter(|m| updated.contains(&m.uuid))
+        .collect();
+
+    // Keep tracking messages that still wait on a tool_result: pending ones
+    // that weren't reloaded are unchanged, the rest get their remaining ids
+    let mut waiting: HashMap<&str, Vec<String>> = HashMap::new();
+    for (id, entry) in &tool_use_map {
+        waiting
+            .entry(entry.message_uuid.as_str())
+            .or_default()
+            .push(id.clone());
+    }
+    let mut pending: Vec<PendingMessage> = std::mem::take(&mut progress.pending)
+        .into_iter()
+        .filter_map(|mut p| {
+            if reloaded.contains(&p.uuid) {
+                p.tool_use_ids = waiting.remove(p.uuid.as_str())?;
+                p.tool_use_ids.sort();
+            }
+            Some(p)
+        })
+        .collect();
+    for (msg, &offset) in messages.iter().zip(&message_offsets) {
+        if let Some(mut tool_use_ids) = waiting.remove(msg.uuid.as_str()) {
+            tool_use_ids.sort();
+            pending.push(PendingMessage {
+                uuid: msg.uuid.clone(),
+                offset,
+                tool_use_ids,
+            });
+        }
+    }
+    if pending.len() > MAX_OPEN_MESSAGES {
+        pending.drain(..pending.len() - MAX_OPEN_MESSAGES);
+    }
+    progress.pending = pending;
+
+    Ok(AppendedMessages {
+        reset,
+        new: messages,
+        updated: updated_messages,
+        previous,
+    })
+}
+
+/// Parses again the pending messages that a tool_result in `appended` refers
+/// to, replaying everything from the first of them up to `progress.offset` so
+/// results seen in earlier calls are applied too. Returns those messages and
+/// their still unanswered tool uses.
+fn reload_pending(
+    file: &mut std::fs::File,
+    progress: &FileProgress,
+    appended: &[u8],
+) -> std::io::Result<(Vec<ExtractedMessage>, HashMap<String, ToolUseEntry>)> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let appended = String::from_utf8_lossy(appended);
+    let answered: HashSet<&str> = progress
+        .pending
+        .iter()
+        .filter(|p| {
+            p.tool_use_ids
+                .iter()
+                .any(|id| appended.contains(id.as_str()))
+        })
+        .map(|p| p.uuid.as_str())
+        .collect();
+    let Some(from) = progress
+        .pending
+        .iter()
+        .filter(|p| answered.contains(p.uuid.as_str()))
+        .map(|p| p.offset)
+        .min()
+    else {
+        return Ok((Vec::new(), HashMap::new()));
+    };
+
+    let mut span = Vec::new();
+    file.seek(SeekFrom::Start(from))?;
+    (&mut *file)
+        .take(progress.offset.saturating_sub(from))
+        .read_to_end(&mut span)?;
+
+    let mut parser = SessionParser::default();
+    for line in span.split(|&b| b == b'\n') {
+        if let Some(obj) = std::str::from_utf8(line)
+            .ok()
+            .filter(|l| !l.trim().is_empty())
+            .and_then(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        {
+            parser.parse_line(&obj);
+        }
+    }
+
+    let messages = parser
+        .messages
+        .into_iter()
+        .filter(|m| answered.contains(m.uuid.as_str()))
+        .collect();
+    let mut tool_use_map = parser.tool_use_map;
+    tool_use_map.retain(|_, e| answered.contains(e.message_uuid.as_str()));
+    Ok((messages, tool_use_map))
+}
+
+/// Whether the last line recorded in `progress` still carries its uuid, i.e.
+/// the file was only appended to since.
+fn still_has_last_line(file: &mut std::fs::File, progress: &FileProgress) -> std::io::Result<bool> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    if progress.last_uuid.is_empty() {
+        return Ok(true);
+    }
+    let mut line = Vec::new();
+    file.seek(SeekFrom::Start(progress.last_line_start))?;
+    (&mut *file)
+        .take(progress.offset.saturating_sub(progress.last_line_start))
+        .read_to_end(&mut line)?;
+    let uuid = progress.last_uuid.as_bytes();
+    Ok(line.windows(uuid.len()).any(|w| w == uuid))
+}
+
+/// Turns session JSONL entries into messages and token usage. A tool_use is
+/// remembered until its tool_result shows up, possibly several lines later,
+/// or in a later call when resumed from a [`FileProgress`].
+#[derive(Default)]
+struct SessionParser {
+    messages: Vec<ExtractedMessage>,
+    /// Messages from earlier calls that still wait on a tool_result
+    open_messages: Vec<ExtractedMessage>,
+    /// uuids of `open_messages` that a tool_result in this call completed
+    updated: HashSet<String>,
+    // Maps tool_use block id -> entry for cross-message correlation
+    tool_use_map: HashMap<String, ToolUseEntry>,
+    usage: Vec<TurnUsage>,
+    // Maps message id -> index in `usage`; later entries of a call carry final counts
+    usage_idx: HashMap<String, usize>,
+}
 
+impl SessionParser {
+    /// The message a tool_use was recorded in, parsed either in this call or
+    /// an earlier one.
+    fn tool_use_message(&mut self, uuid: &str) -> Option<&mut ExtractedMessage> {
+        if let Some(i) = self.messages.iter().rposition(|m| m.uuid == uuid) {
+            return self.messages.get_mut(i);
+        }
+        let msg = self.open_messages.iter_mut().find(|m| m.uuid == uuid)?;
+        self.updated.insert(uuid.to_string());
+        Some(msg)
+    }
+
+    fn parse_line(&mut self, obj: &serde_json::Value) {
         let msg_type = obj.get("type").and_then(|v| v.as_str()).unwrap_or("");
         if msg_type != "user" && msg_type != "assistant" {
-            continue;
+            return;
         }
 
         // Skip isMeta messages
         if obj.get("isMeta").and_then(|v| v.as_bool()).unwrap_or(false) {
-            continue;
+            return;
         }
 
         let uuid = obj
@@ -1068,9 +1790,22 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
 
         let message = match obj.get("message") {
             Some(m) => m,
-            None => continue,
+            None => return,
         };
 
+        if msg_type == "assistant"
+            && let Some(turn) = parse_turn_usage(obj, message, &session_id, &timestamp)
+        {
+            match self.usage_idx.get(&turn.message_id) {
+                Some(&i) => self.usage[i] = turn,
+                None => {
+                    self.usage_idx
+                        .insert(turn.message_id.clone(), self.usage.len());
+                    self.usage.push(turn);
+                }
+            }
+        }
+
         let role = message
             .get("role")
             .and_then(|v| v.as_str())
@@ -1149,26 +1884,22 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
                             let action = ToolAction {
                                 tool_use_id: tool_id.clone(),
                                 tool_name: name.clone(),
-                                category: category.clone(),
-                                file_path: file_path.clone(),
+                                category,
+                                file_path,
                                 summary: summary.clone(),
-                                full_input: full_input.clone(),
+                                full_input,
                                 full_output: None,
                                 timestamp: timestamp.clone(),
                             };
                             tool_actions.push(action);
 
                             if !tool_id.is_empty() {
-                                tool_use_map.insert(
+                                self.tool_use_map.insert(
                                     tool_id,
                                     ToolUseEntry {
                                         tool_name: name,
-                                        category,
-                                        file_path,
                                         summary,
-                                        full_input,
-                                        timestamp: timestamp.clone(),
-                                        message_idx: messages.len(),
+                                        message_uuid: uuid.clone(),
                                     },
                                 );
                             }
@@ -1205,13 +1936,14 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
                             });
 
                             // Correlate with the original tool_use
-                            if let Some(entry) = tool_use_map.get_mut(&tool_use_id) {
+                            if let Some(entry) = self.tool_use_map.remove(&tool_use_id)
+                                && let Some(msg) = self.tool_use_message(&entry.message_uuid)
+                            {
                                 // Update the ToolAction in the original message
-                                if entry.message_idx < messages.len()
-                                    && let Some(action) = messages[entry.message_idx]
-                                        .tool_actions
-                                        .iter_mut()
-                                        .find(|a| a.tool_use_id == tool_use_id)
+                                if let Some(action) = msg
+                                    .tool_actions
+                                    .iter_mut()
+                                    .find(|a| a.tool_use_id == tool_use_id)
                                 {
                                     action.full_output = output_content.clone();
                                 }
@@ -1223,11 +1955,10 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
                                     let output_preview = truncate(output, 300);
                                     let enhanced = format!("{}\n{}", entry.summary, output_preview);
                                     // Update the summary in the original message's commands_run
-                                    if entry.message_idx < messages.len()
-                                        && let Some(cmd) = messages[entry.message_idx]
-                                            .commands_run
-                                            .iter_mut()
-                                            .find(|c: &&mut String| c.starts_with(&entry.summary))
+                                    if let Some(cmd) = msg
+                                        .commands_run
+                                        .iter_mut()
+                                        .find(|c: &&mut String| c.starts_with(&entry.summary))
                                     {
                                         *cmd = enhanced;
                                     }
@@ -1240,15 +1971,15 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
                     }
                 }
             }
-            _ => continue,
+            _ => return,
         }
 
         let content = text_parts.join("\n");
         if content.trim().is_empty() && tools_used.is_empty() {
-            continue;
+            return;
         }
 
-        messages.push(ExtractedMessage {
+        self.messages.push(ExtractedMessage {
             uuid,
             session_id,
             role,
@@ -1263,8 +1994,44 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
             tool_actions,
         });
     }
+}
 
-    messages
+/// Read the `usage` object of an assistant entry. Synthetic entries (local
+/// error messages) and calls without any counted tokens are ignored.
+fn parse_turn_usage(
+    obj: &serde_json::Value,
+    message: &serde_json::Value,
+    session_id: &str,
+    timestamp: &str,
+) -> Option<TurnUsage> {
+    let usage = message.get("usage")?;
+    let message_id = message.get("id").and_then(|v| v.as_str())?;
+    let model = message.get("model").and_then(|v| v.as_str());
+    if message_id.is_empty() || session_id.is_empty() || model == Some("<synthetic>") {
+        return None;
+    }
+
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_i64()).unwrap_or(0).max(0);
+    let turn = TurnUsage {
+        message_id: message_id.to_string(),
+        session_id: session_id.to_string(),
+        timestamp: timestamp.to_string(),
+        model: model.map(|m| m.to_string()),
+        cwd: obj
+            .get("cwd")
+            .and_then(|v| v.as_str())
+            .filter(|c| !c.is_empty())
+            .map(|c| c.to_string()),
+        input_tokens: count("input_tokens"),
+        output_tokens: count("output_tokens"),
+        cache_creation_input_tokens: count("cache_creation_input_tokens"),
+        cache_read_input_tokens: count("cache_read_input_tokens"),
+    };
+    let total = turn.input_tokens
+        + turn.output_tokens
+        + turn.cache_creation_input_tokens
+        + turn.cache_read_input_tokens;
+    (total > 0).then_some(turn)
 }
 
 // ---------------------------------------------------------------------------
@@ -1272,8 +2039,10 @@ pub fn extract_messages_from_jsonl(path: &Path) -> Vec<ExtractedMessage> {
 // ---------------------------------------------------------------------------
 
 /// Wrapper for managed Tauri state.
+#[cfg(feature = "gui")]
 pub struct SessionIndexState(pub Arc<SessionIndex>);
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub async fn search_sessions(
     query: String,
@@ -1288,6 +2057,7 @@ pub async fn search_sessions(
     crate::run_blocking(move || idx.search(&query, &filters, &sort, page, page_size))
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub async fn get_session_context(
     session_id: String,
@@ -1300,6 +2070,7 @@ pub async fn get_session_context(
     crate::run_blocking(move || idx.get_context(&session_id, &around_message_id, w))
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub async fn get_search_facets(
     state: tauri::State<'_, SessionIndexState>,
@@ -1308,12 +2079,14 @@ pub async fn get_search_facets(
     crate::run_blocking(move || idx.get_facets())
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub async fn rebuild_search_index(
-    app: tauri::AppHandle,
+    events: tauri::State<'_, crate::events::EventBus>,
     state: tauri::State<'_, SessionIndexState>,
 ) -> Result<usize, String> {
     let idx = state.0.clone();
+    let events = events.inner().clone();
     let storage = crate::STORAGE.get();
-    crate::run_blocking(move || idx.startup_scan(&app, storage))
+    crate::run_blocking(move || idx.startup_scan(&events, storage))
 }
@@ -1,16 +1,90 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use chrono::{DateTime, TimeDelta, Utc};
 use parking_lot::Mutex;
-use rusqlite::{Connection, OptionalExtension, params};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
 
+#[cfg(any(feature = "gui", test))]
+use crate::models::AlertRulePayload;
 use crate::models::{
-    BucketStats, CodeStats, CodeStatsHistoryPoint, DataPoint, GitSnapshot, HostBreakdown,
+    AlertFiring, AlertRule, ApiToken, AuditEntry, AuditQuery, BucketStats, Budget, CodeStats,
+    CodeStatsHistoryPoint, DataPoint, GitSnapshot, HostBreakdown, HostTokenTotals,
     LanguageBreakdown, LearnedRule, LearnedRulePayload, LearningRun, LearningRunPayload,
-    LearningStatus, ObservationPayload, ProjectBreakdown, ProjectTokens, ResponseTimeStats,
-    SessionBreakdown, SessionCodeStats, SessionStats, TokenDataPoint, TokenReportPayload,
-    TokenStats, ToolCount, UsageBucket,
+    LearningStatus, MetricsSummary, ModelBreakdown, ModelCost, ModelPricing, ObservationPayload,
+    OutboxEntry, ProjectBreakdown, RedactionSummary, ResponseTimeStats, SavedSearch,
+    SavedSearchPayload, SessionBreakdown, SessionSyncState, TokenDataPoint, TokenReportPayload,
+    TokenStats, TrashBatch, UsageBucket, UsageForecast,
 };
+#[cfg(feature = "gui")]
+use crate::models::{
+    BudgetPayload, ModelPricingPayload, ProjectTokens, SessionCodeStats, SessionStats, ToolCount,
+};
+use crate::pricing;
+use crate::redact::redact;
+
+/// Newest migration `Storage::init` applies. Bump it with each migration.
+pub const SCHEMA_VERSION: i32 = 25;
+
+/// Columns copied between `token_snapshots` and `token_snapshots_trash`.
+const SNAPSHOT_TRASH_COLUMNS: &str =
+    "id, session_id, hostname, timestamp, input_tokens, output_tokens,
+     cache_creation_input_tokens, cache_read_input_tokens, cwd, created_at, model, idempotency_key";
+/// Columns copied between `token_hourly` and `token_hourly_trash`. The id is
+/// left out because restored rows merge into any hour recorded since.
+const HOURLY_TRASH_COLUMNS: &str =
+    "hour, hostname, model, total_input, total_output, total_cache_creation, total_cache_read,
+     turn_count";
+
+const TRASH_BATCH_COLUMNS: &str = "id, kind, target, deleted_at, snapshot_count, hourly_count";
+
+fn trash_batch_from_row(row: &rusqlite::Row) -> rusqlite::Result<TrashBatch> {
+    Ok(TrashBatch {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        target: row.get(2)?,
+        deleted_at: row.get(3)?,
+        snapshot_count: row.get(4)?,
+        hourly_count: row.get(5)?,
+    })
+}
+
+const API_TOKEN_COLUMNS: &str =
+    "id, name, hostname, scopes, token_prefix, created_at, last_used_at, revoked_at";
+
+fn api_token_from_row(row: &rusqlite::Row) -> rusqlite::Result<ApiToken> {
+    let scopes: String = row.get(3)?;
+    Ok(ApiToken {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        hostname: row.get(2)?,
+        scopes: scopes
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect(),
+        token_prefix: row.get(4)?,
+        created_at: row.get(5)?,
+        last_used_at: row.get(6)?,
+        revoked_at: row.get(7)?,
+    })
+}
+
+fn alert_firing_from_row(row: &rusqlite::Row) -> rusqlite::Result<AlertFiring> {
+    Ok(AlertFiring {
+        id: row.get(0)?,
+        rule_id: row.get(1)?,
+        rule_name: row.get(2)?,
+        bucket_label: row.get(3)?,
+        utilization: row.get(4)?,
+        threshold: row.get(5)?,
+        resets_at: row.get(6)?,
+        window_key: row.get(7)?,
+        message: row.get(8)?,
+        fired_at: row.get(9)?,
+    })
+}
 
 fn wilson_lower_bound(alpha: f64, beta: f64) -> f64 {
     let n = alpha + beta;
@@ -48,7 +122,7 @@ fn freshness_factor(last_evidence_at: Option<&str>) -> f64 {
     0.5_f64.powf(days / 90.0)
 }
 
-fn db_path() -> Result<PathBuf, String> {
+pub(crate) fn db_path() -> Result<PathBuf, String> {
     let data_dir = dirs::data_local_dir()
         .or_else(|| {
             dirs::home_dir().map(|h| {
@@ -134,17 +208,23 @@ fn range_to_duration(range: &str) -> TimeDelta {
 
 pub struct Storage {
     conn: Mutex<Connection>,
+    /// Where the database lives, for the separate connection backups read through
+    path: PathBuf,
 }
 
 impl Storage {
     pub fn init() -> Result<Self, String> {
-        let path = db_path()?;
-        let conn = Connection::open(&path).map_err(|e| format!("Failed to open database: {e}"))?;
+        Self::open(&db_path()?)
+    }
+
+    /// Opens and migrates the database at `path`.
+    pub(crate) fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open database: {e}"))?;
 
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+            let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
         }
 
         conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA busy_timeout=5000;")
@@ -560,8 +640,366 @@ impl Storage {
                 .map_err(|e| format!("Failed to record migration 11: {e}"))?;
         }
 
+        // Migration 12: utilization alert rules and their firing history
+        if current_version < 12 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS alert_rules (
+                    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name                TEXT NOT NULL,
+                    bucket_label        TEXT NOT NULL,
+                    threshold           REAL NOT NULL,
+                    min_hours_to_reset  REAL,
+                    max_hours_to_reset  REAL,
+                    notify_desktop      INTEGER NOT NULL DEFAULT 1,
+                    webhook_url         TEXT,
+                    enabled             INTEGER NOT NULL DEFAULT 1,
+                    created_at          TEXT DEFAULT (datetime('now'))
+                );
+                CREATE TABLE IF NOT EXISTS alert_firings (
+                    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                    rule_id       INTEGER NOT NULL,
+                    rule_name     TEXT NOT NULL,
+                    bucket_label  TEXT NOT NULL,
+                    utilization   REAL NOT NULL,
+                    threshold     REAL NOT NULL,
+                    resets_at     TEXT,
+                    window_key    TEXT NOT NULL,
+                    message       TEXT NOT NULL,
+                    fired_at      TEXT NOT NULL,
+                    UNIQUE(rule_id, window_key)
+                );
+                CREATE INDEX IF NOT EXISTS idx_alert_firings_fired ON alert_firings(fired_at);",
+            )
+            .map_err(|e| format!("Migration 12 (alert tables): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (12)", [])
+                .map_err(|e| format!("Failed to record migration 12: {e}"))?;
+        }
+
+        // Migration 13: per-model token tracking. token_hourly is rebuilt so the
+        // model becomes part of its unique key ('' for turns reported without one).
+        if current_version < 13 {
+            let has_model: bool = conn
+                .prepare("SELECT model FROM token_snapshots LIMIT 0")
+                .is_ok();
+            if !has_model {
+                conn.execute_batch(
+                    "ALTER TABLE token_snapshots ADD COLUMN model TEXT DEFAULT NULL;",
+                )
+                .map_err(|e| format!("Migration 13 (model column): {e}"))?;
+            }
+            conn.execute_batch(
+                "CREATE INDEX IF NOT EXISTS idx_token_snap_model ON token_snapshots(model, timestamp);
+
+                CREATE TABLE token_hourly_new (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    hour TEXT NOT NULL,
+                    hostname TEXT NOT NULL DEFAULT 'local',
+                    model TEXT NOT NULL DEFAULT '',
+                    total_input INTEGER NOT NULL,
+                    total_output INTEGER NOT NULL,
+                    total_cache_creation INTEGER NOT NULL DEFAULT 0,
+                    total_cache_read INTEGER NOT NULL DEFAULT 0,
+                    turn_count INTEGER NOT NULL,
+                    UNIQUE(hour, hostname, model)
+                );
+                INSERT INTO token_hourly_new (hour, hostname, model, total_input, total_output, total_cache_creation, total_cache_read, turn_count)
+                    SELECT hour, hostname, '', total_input, total_output, total_cache_creation, total_cache_read, turn_count
+                    FROM token_hourly;
+                DROP TABLE token_hourly;
+                ALTER TABLE token_hourly_new RENAME TO token_hourly;
+                CREATE INDEX IF NOT EXISTS idx_token_hourly_hour ON token_hourly(hour);",
+            )
+            .map_err(|e| format!("Migration 13 (token_hourly model): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (13)", [])
+                .map_err(|e| format!("Failed to record migration 13: {e}"))?;
+        }
+
+        // Migration 14: user-editable per-model pricing for cost estimates
+        if current_version < 14 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS model_pricing (
+                    model_pattern        TEXT PRIMARY KEY,
+                    input_per_mtok       REAL NOT NULL,
+                    output_per_mtok      REAL NOT NULL,
+                    cache_write_per_mtok REAL NOT NULL,
+                    cache_read_per_mtok  REAL NOT NULL,
+                    updated_at           TEXT DEFAULT (datetime('now'))
+                );",
+            )
+            .map_err(|e| format!("Migration 14 (model_pricing table): {e}"))?;
+            seed_default_pricing(&conn)
+                .map_err(|e| format!("Migration 14 (pricing defaults): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (14)", [])
+                .map_err(|e| format!("Failed to record migration 14: {e}"))?;
+        }
+
+        // Migration 15: message ids already backfilled from session transcripts
+        if current_version < 15 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS token_import_log (
+                    message_id   TEXT PRIMARY KEY,
+                    session_id   TEXT NOT NULL,
+                    imported_at  TEXT DEFAULT (datetime('now'))
+                );",
+            )
+            .map_err(|e| format!("Migration 15 (token_import_log table): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (15)", [])
+                .map_err(|e| format!("Failed to record migration 15: {e}"))?;
+        }
+
+        // Migration 16: project/host token and cost budgets
+        if current_version < 16 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS budgets (
+                    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name         TEXT NOT NULL,
+                    scope_type   TEXT NOT NULL,
+                    scope_value  TEXT NOT NULL,
+                    period       TEXT NOT NULL,
+                    metric       TEXT NOT NULL,
+                    limit_value  REAL NOT NULL,
+                    enabled      INTEGER NOT NULL DEFAULT 1,
+                    created_at   TEXT DEFAULT (datetime('now'))
+                );
+                CREATE TABLE IF NOT EXISTS budget_crossings (
+                    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                    budget_id     INTEGER NOT NULL,
+                    period_start  TEXT NOT NULL,
+                    threshold     INTEGER NOT NULL,
+                    crossed_at    TEXT DEFAULT (datetime('now')),
+                    UNIQUE(budget_id, period_start, threshold)
+                );",
+            )
+            .map_err(|e| format!("Migration 16 (budget tables): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (16)", [])
+                .map_err(|e| format!("Failed to record migration 16: {e}"))?;
+        }
+
+        if current_version < 17 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS api_tokens (
+                    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name          TEXT NOT NULL,
+                    hostname      TEXT,
+                    token_hash    TEXT NOT NULL UNIQUE,
+                    token_prefix  TEXT NOT NULL,
+                    scopes        TEXT NOT NULL DEFAULT '',
+                    created_at    TEXT NOT NULL,
+                    last_used_at  TEXT,
+                    revoked_at    TEXT
+                );",
+            )
+            .map_err(|e| format!("Migration 17 (api_tokens table): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (17)", [])
+                .map_err(|e| format!("Failed to record migration 17: {e}"))?;
+        }
+
+        // Migration 18: idempotency keys on token snapshots, so hooks can retry
+        // reports without double counting
+        if current_version < 18 {
+            let has_key: bool = conn
+                .prepare("SELECT idempotency_key FROM token_snapshots LIMIT 0")
+                .is_ok();
+            if !has_key {
+                conn.execute_batch(
+                    "ALTER TABLE token_snapshots ADD COLUMN idempotency_key TEXT DEFAULT NULL;",
+                )
+                .map_err(|e| format!("Migration 18 (idempotency_key column): {e}"))?;
+            }
+            conn.execute_batch(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_token_snap_idempotency
+                    ON token_snapshots(hostname, idempotency_key)
+                    WHERE idempotency_key IS NOT NULL;",
+            )
+            .map_err(|e| format!("Migration 18 (idempotency index): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (18)", [])
+                .map_err(|e| format!("Failed to record migration 18: {e}"))?;
+        }
+
+        // Migration 19: outbox of accepted payloads to forward in relay mode
+        if current_version < 19 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS relay_outbox (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    route       TEXT NOT NULL,
+                    body        TEXT NOT NULL,
+                    created_at  TEXT NOT NULL,
+                    attempts    INTEGER NOT NULL DEFAULT 0,
+                    last_error  TEXT
+                );",
+            )
+            .map_err(|e| format!("Migration 19 (relay_outbox table): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (19)", [])
+                .map_err(|e| format!("Failed to record migration 19: {e}"))?;
+        }
+
+        if current_version < 20 {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS session_sync_state (
+                    hostname      TEXT NOT NULL,
+                    session_id    TEXT NOT NULL,
+                    last_uuid     TEXT,
+                    byte_offset   INTEGER NOT NULL DEFAULT 0,
+                    message_count INTEGER NOT NULL DEFAULT 0,
+                    updated_at    TEXT NOT NULL,
+                    PRIMARY KEY (hostname, session_id)
+                );",
+            )
+            .map_err(|e| format!("Migration 20 (session_sync_state table): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (20)", [])
+                .map_err(|e| format!("Failed to record migration 20: {e}"))?;
+        }
+
+        if current_version < 21 {
+            // Rows can't be edited, and only retention may delete them once
+            // they are a week old
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS audit_log (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp   TEXT NOT NULL,
+                    kind        TEXT NOT NULL,
+                    action      TEXT NOT NULL,
+                    actor       TEXT NOT NULL,
+                    token_id    INTEGER,
+                    hostname    TEXT,
+                    client_addr TEXT,
+                    status      INTEGER,
+                    bytes       INTEGER,
+                    detail      TEXT,
+                    error       TEXT
+                );
+                CREATE INDEX IF NOT EXISTS idx_audit_timestamp ON audit_log(timestamp);
+                CREATE INDEX IF NOT EXISTS idx_audit_kind_action ON audit_log(kind, action);
+                CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+                    BEFORE UPDATE ON audit_log
+                BEGIN
+                    SELECT RAISE(ABORT, 'audit_log is append-only');
+                END;
+                CREATE TRIGGER IF NOT EXISTS audit_log_min_retention
+                    BEFORE DELETE ON audit_log
+                    WHEN OLD.timestamp > strftime('%Y-%m-%dT%H:%M:%S', 'now', '-7 days')
+                BEGIN
+                    SELECT RAISE(ABORT, 'audit_log entries are kept for at least 7 days');
+                END;",
+            )
+            .map_err(|e| format!("Migration 21 (audit_log table): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (21)", [])
+                .map_err(|e| format!("Failed to record migration 21: {e}"))?;
+        }
+
+        if current_version < 22 {
+            // Deleted token rows are moved here, grouped by batch, so a
+            // delete can be undone until the batch is purged
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS trash_batches (
+                    id             TEXT PRIMARY KEY,
+                    kind           TEXT NOT NULL,
+                    target         TEXT NOT NULL,
+                    deleted_at     TEXT NOT NULL,
+                    snapshot_count INTEGER NOT NULL,
+                    hourly_count   INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_trash_batches_deleted ON trash_batches(deleted_at);
+                CREATE TABLE IF NOT EXISTS token_snapshots_trash (
+                    batch_id                    TEXT NOT NULL,
+                    id                          INTEGER NOT NULL,
+                    session_id                  TEXT NOT NULL,
+                    hostname                    TEXT NOT NULL,
+                    timestamp                   TEXT NOT NULL,
+                    input_tokens                INTEGER NOT NULL,
+                    output_tokens               INTEGER NOT NULL,
+                    cache_creation_input_tokens INTEGER NOT NULL,
+                    cache_read_input_tokens     INTEGER NOT NULL,
+                    cwd                         TEXT,
+                    created_at                  TEXT,
+                    model                       TEXT,
+                    idempotency_key             TEXT
+                );
+                CREATE INDEX IF NOT EXISTS idx_token_snap_trash_batch ON token_snapshots_trash(batch_id);
+                CREATE TABLE IF NOT EXISTS token_hourly_trash (
+                    batch_id             TEXT NOT NULL,
+                    hour                 TEXT NOT NULL,
+                    hostname             TEXT NOT NULL,
+                    model                TEXT NOT NULL,
+                    total_input          INTEGER NOT NULL,
+                    total_output         INTEGER NOT NULL,
+                    total_cache_creation INTEGER NOT NULL,
+                    total_cache_read     INTEGER NOT NULL,
+                    turn_count           INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_token_hourly_trash_batch ON token_hourly_trash(batch_id);",
+            )
+            .map_err(|e| format!("Migration 22 (trash tables): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (22)", [])
+                .map_err(|e| format!("Failed to record migration 22: {e}"))?;
+        }
+
+        if current_version < 23 {
+            // Named session searches; watches also record which messages
+            // they have already reported
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS saved_searches (
+                    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name       TEXT NOT NULL UNIQUE,
+                    query      TEXT NOT NULL,
+                    filters    TEXT NOT NULL DEFAULT '{}',
+                    watch      INTEGER NOT NULL DEFAULT 0,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS saved_search_matches (
+                    search_id  INTEGER NOT NULL,
+                    message_id TEXT NOT NULL,
+                    session_id TEXT NOT NULL,
+                    matched_at TEXT NOT NULL,
+                    PRIMARY KEY (search_id, message_id)
+                );",
+            )
+            .map_err(|e| format!("Migration 23 (saved searches): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (23)", [])
+                .map_err(|e| format!("Failed to record migration 23: {e}"))?;
+        }
+
+        if current_version < 24 {
+            // Tokens issued without scopes had full access; say so explicitly
+            // now that an empty scope list grants nothing
+            conn.execute_batch("UPDATE api_tokens SET scopes = '*' WHERE scopes = '';")
+                .map_err(|e| format!("Migration 24 (explicit full-access scope): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (24)", [])
+                .map_err(|e| format!("Failed to record migration 24: {e}"))?;
+        }
+
+        if current_version < 25 {
+            // Watches report only messages written after they were turned
+            // on; existing ones start from now rather than their history
+            conn.execute_batch("ALTER TABLE saved_searches ADD COLUMN watch_since TEXT;")
+                .map_err(|e| format!("Migration 25 (saved search watch_since): {e}"))?;
+            conn.execute(
+                "UPDATE saved_searches SET watch_since = ?1 WHERE watch != 0",
+                params![Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Migration 25 (saved search watch_since): {e}"))?;
+
+            conn.execute("INSERT INTO schema_version (version) VALUES (25)", [])
+                .map_err(|e| format!("Failed to record migration 25: {e}"))?;
+        }
+
         let storage = Self {
             conn: Mutex::new(conn),
+            path: path.to_path_buf(),
         };
 
         if let Err(e) = storage.aggregate_and_cleanup() {
@@ -801,6 +1239,7 @@ impl Storage {
         Ok(BucketStats { trend, ..stats })
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_all_bucket_stats(
         &self,
         current_buckets: &[UsageBucket],
@@ -816,151 +1255,1353 @@ impl Storage {
         Ok(results)
     }
 
-    pub fn get_snapshot_count(&self) -> Result<i64, String> {
+    /// Project when each bucket in the latest snapshot will hit 100%, based on
+    /// the slope of its recent utilization within the current reset window.
+    pub fn get_usage_forecasts(&self, bucket: Option<&str>) -> Result<Vec<UsageForecast>, String> {
         let conn = self.conn.lock();
-        conn.query_row("SELECT COUNT(*) FROM usage_snapshots", [], |row| row.get(0))
-            .map_err(|e| format!("Count error: {e}"))
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT bucket_label, utilization, resets_at FROM usage_snapshots
+                 WHERE timestamp = (SELECT MAX(timestamp) FROM usage_snapshots)
+                 ORDER BY id ASC",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(UsageBucket {
+                    label: row.get(0)?,
+                    utilization: row.get(1)?,
+                    resets_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let mut latest = Vec::new();
+        for row in rows {
+            latest.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+
+        let now = Utc::now();
+        let mut results = Vec::new();
+        for current in latest
+            .iter()
+            .filter(|b| bucket.is_none_or(|label| label == b.label))
+        {
+            results.push(forecast_bucket(&conn, current, now)?);
+        }
+        Ok(results)
     }
 
-    pub fn store_token_snapshot(&self, payload: &TokenReportPayload) -> Result<(), String> {
+    pub fn get_alert_rules(&self) -> Result<Vec<AlertRule>, String> {
         let conn = self.conn.lock();
-        let now = Utc::now().to_rfc3339();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, name, bucket_label, threshold, min_hours_to_reset, max_hours_to_reset,
+                        notify_desktop, webhook_url, enabled, created_at
+                 FROM alert_rules ORDER BY id",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(AlertRule {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    bucket_label: row.get(2)?,
+                    threshold: row.get(3)?,
+                    min_hours_to_reset: row.get(4)?,
+                    max_hours_to_reset: row.get(5)?,
+                    notify_desktop: row.get::<_, i64>(6)? != 0,
+                    webhook_url: row.get(7)?,
+                    enabled: row.get::<_, i64>(8)? != 0,
+                    created_at: row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
 
+    #[cfg(any(feature = "gui", test))]
+    pub fn create_alert_rule(&self, payload: &AlertRulePayload) -> Result<i64, String> {
+        let conn = self.conn.lock();
         conn.execute(
-            "INSERT INTO token_snapshots (session_id, hostname, timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens, cwd)
+            "INSERT INTO alert_rules (name, bucket_label, threshold, min_hours_to_reset,
+                                      max_hours_to_reset, notify_desktop, webhook_url, enabled)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
-                payload.session_id,
-                payload.hostname,
-                now,
-                payload.input_tokens,
-                payload.output_tokens,
-                payload.cache_creation_input_tokens,
-                payload.cache_read_input_tokens,
-                payload.cwd
+                payload.name,
+                payload.bucket_label,
+                payload.threshold,
+                payload.min_hours_to_reset,
+                payload.max_hours_to_reset,
+                payload.notify_desktop as i64,
+                payload.webhook_url,
+                payload.enabled as i64,
             ],
         )
-        .map_err(|e| format!("Insert token snapshot error: {e}"))?;
+        .map_err(|e| format!("Insert alert rule error: {e}"))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn update_alert_rule(&self, id: i64, payload: &AlertRulePayload) -> Result<(), String> {
+        let conn = self.conn.lock();
+        let updated = conn
+            .execute(
+                "UPDATE alert_rules SET name=?2, bucket_label=?3, threshold=?4,
+                 min_hours_to_reset=?5, max_hours_to_reset=?6, notify_desktop=?7,
+                 webhook_url=?8, enabled=?9
+                 WHERE id=?1",
+                params![
+                    id,
+                    payload.name,
+                    payload.bucket_label,
+                    payload.threshold,
+                    payload.min_hours_to_reset,
+                    payload.max_hours_to_reset,
+                    payload.notify_desktop as i64,
+                    payload.webhook_url,
+                    payload.enabled as i64,
+                ],
+            )
+            .map_err(|e| format!("Update alert rule error: {e}"))?;
+        if updated == 0 {
+            return Err(format!("Alert rule {id} not found"));
+        }
+        Ok(())
+    }
 
+    #[cfg(feature = "gui")]
+    pub fn delete_alert_rule(&self, id: i64) -> Result<(), String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        tx.execute("DELETE FROM alert_firings WHERE rule_id = ?1", params![id])
+            .map_err(|e| format!("Delete alert firings error: {e}"))?;
+        tx.execute("DELETE FROM alert_rules WHERE id = ?1", params![id])
+            .map_err(|e| format!("Delete alert rule error: {e}"))?;
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
         Ok(())
     }
 
-    pub fn get_token_history(
-        &self,
-        range: &str,
-        hostname: Option<&str>,
-        session_id: Option<&str>,
-        cwd: Option<&str>,
-    ) -> Result<Vec<TokenDataPoint>, String> {
+    /// Records a firing unless the rule already fired in the same reset window.
+    /// Returns the new row id, or `None` when the firing was deduplicated.
+    pub fn record_alert_firing(&self, firing: &AlertFiring) -> Result<Option<i64>, String> {
         let conn = self.conn.lock();
-        let now = Utc::now();
-
-        // Skip hourly aggregates when filtering by session_id or cwd since
-        // token_hourly doesn't store those fields
-        let needs_granular = session_id.is_some() || cwd.is_some();
-        let (from, use_hourly) = match range {
-            "1h" => (now - TimeDelta::hours(1), false),
-            "24h" => (now - TimeDelta::hours(24), false),
-            "7d" => (now - TimeDelta::days(7), false),
-            "30d" => (now - TimeDelta::days(30), !needs_granular),
-            "all" => (now - TimeDelta::days(365), !needs_granular),
-            _ => (now - TimeDelta::hours(24), false),
-        };
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO alert_firings
+                    (rule_id, rule_name, bucket_label, utilization, threshold, resets_at,
+                     window_key, message, fired_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    firing.rule_id,
+                    firing.rule_name,
+                    firing.bucket_label,
+                    firing.utilization,
+                    firing.threshold,
+                    firing.resets_at,
+                    firing.window_key,
+                    firing.message,
+                    firing.fired_at,
+                ],
+            )
+            .map_err(|e| format!("Insert alert firing error: {e}"))?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        Ok(Some(conn.last_insert_rowid()))
+    }
 
-        let from_str = from.to_rfc3339();
-        let mut points = Vec::new();
+    /// The most recent firing of a rule for a bucket, if any.
+    pub fn last_alert_firing(
+        &self,
+        rule_id: i64,
+        bucket_label: &str,
+    ) -> Result<Option<AlertFiring>, String> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT id, rule_id, rule_name, bucket_label, utilization, threshold, resets_at,
+                    window_key, message, fired_at
+             FROM alert_firings WHERE rule_id = ?1 AND bucket_label = ?2
+             ORDER BY id DESC LIMIT 1",
+            params![rule_id, bucket_label],
+            alert_firing_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))
+    }
 
-        if use_hourly {
-            let from_hour = from.format("%Y-%m-%dT%H:00:00Z").to_string();
+    #[cfg(any(feature = "gui", test))]
+    pub fn get_alert_firings(&self, limit: i64) -> Result<Vec<AlertFiring>, String> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, rule_id, rule_name, bucket_label, utilization, threshold, resets_at,
+                        window_key, message, fired_at
+                 FROM alert_firings ORDER BY fired_at DESC LIMIT ?1",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
 
-            let (hourly_sql, hourly_params): (String, Vec<Box<dyn rusqlite::types::ToSql>>) =
-                if let Some(host) = hostname {
-                    (
-                        "SELECT hour, total_input, total_output, total_cache_creation, total_cache_read
-                         FROM token_hourly
-                         WHERE hour >= ?1 AND hostname = ?2
-                         ORDER BY hour ASC".to_string(),
-                        vec![Box::new(from_hour.clone()), Box::new(host.to_string())],
-                    )
-                } else {
-                    (
-                        "SELECT hour, SUM(total_input), SUM(total_output), SUM(total_cache_creation), SUM(total_cache_read)
-                         FROM token_hourly
-                         WHERE hour >= ?1
-                         GROUP BY hour
-                         ORDER BY hour ASC".to_string(),
-                        vec![Box::new(from_hour.clone())],
-                    )
-                };
+        let rows = stmt
+            .query_map(params![limit], alert_firing_from_row)
+            .map_err(|e| format!("Query error: {e}"))?;
 
-            let mut stmt = conn
-                .prepare(&hourly_sql)
-                .map_err(|e| format!("Prepare error: {e}"))?;
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
 
-            let params_refs: Vec<&dyn rusqlite::types::ToSql> =
-                hourly_params.iter().map(|p| p.as_ref()).collect();
+    pub fn get_budgets(&self) -> Result<Vec<Budget>, String> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, name, scope_type, scope_value, period, metric, limit_value, enabled, created_at
+                 FROM budgets ORDER BY id",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
 
-            let rows = stmt
-                .query_map(params_refs.as_slice(), |row| {
-                    let inp: i64 = row.get(1)?;
-                    let out: i64 = row.get(2)?;
-                    let cc: i64 = row.get(3)?;
-                    let cr: i64 = row.get(4)?;
-                    Ok(TokenDataPoint {
-                        timestamp: row.get(0)?,
-                        input_tokens: inp,
-                        output_tokens: out,
-                        cache_creation_input_tokens: cc,
-                        cache_read_input_tokens: cr,
-                        total_tokens: inp + out + cc + cr,
-                    })
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(Budget {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    scope_type: row.get(2)?,
+                    scope_value: row.get(3)?,
+                    period: row.get(4)?,
+                    metric: row.get(5)?,
+                    limit_value: row.get(6)?,
+                    enabled: row.get::<_, i64>(7)? != 0,
+                    created_at: row.get::<_, Option<String>>(8)?.unwrap_or_default(),
                 })
-                .map_err(|e| format!("Query error: {e}"))?;
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
 
-            for row in rows {
-                points.push(row.map_err(|e| format!("Row error: {e}"))?);
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn create_budget(&self, payload: &BudgetPayload) -> Result<i64, String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO budgets (name, scope_type, scope_value, period, metric, limit_value, enabled)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                payload.name,
+                payload.scope_type,
+                payload.scope_value,
+                payload.period,
+                payload.metric,
+                payload.limit_value,
+                payload.enabled as i64,
+            ],
+        )
+        .map_err(|e| format!("Insert budget error: {e}"))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn update_budget(&self, id: i64, payload: &BudgetPayload) -> Result<(), String> {
+        let conn = self.conn.lock();
+        let updated = conn
+            .execute(
+                "UPDATE budgets SET name=?2, scope_type=?3, scope_value=?4, period=?5, metric=?6,
+                 limit_value=?7, enabled=?8
+                 WHERE id=?1",
+                params![
+                    id,
+                    payload.name,
+                    payload.scope_type,
+                    payload.scope_value,
+                    payload.period,
+                    payload.metric,
+                    payload.limit_value,
+                    payload.enabled as i64,
+                ],
+            )
+            .map_err(|e| format!("Update budget error: {e}"))?;
+        if updated == 0 {
+            return Err(format!("Budget {id} not found"));
+        }
+        // Thresholds already crossed may no longer hold under the new limit
+        conn.execute(
+            "DELETE FROM budget_crossings WHERE budget_id = ?1",
+            params![id],
+        )
+        .map_err(|e| format!("Delete budget crossings error: {e}"))?;
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn delete_budget(&self, id: i64) -> Result<(), String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        tx.execute(
+            "DELETE FROM budget_crossings WHERE budget_id = ?1",
+            params![id],
+        )
+        .map_err(|e| format!("Delete budget crossings error: {e}"))?;
+        tx.execute("DELETE FROM budgets WHERE id = ?1", params![id])
+            .map_err(|e| format!("Delete budget error: {e}"))?;
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(())
+    }
+
+    /// Tokens and estimated cost consumed within a budget's scope since `from`.
+    /// Host budgets also read token_hourly; project budgets match the cwd and
+    /// its subdirectories and can only use token_snapshots, which stores cwd.
+    pub fn budget_consumption(&self, budget: &Budget, from: &str) -> Result<(i64, f64), String> {
+        let conn = self.conn.lock();
+        let from_hour = DateTime::parse_from_rfc3339(from)
+            .map(|t| {
+                t.with_timezone(&Utc)
+                    .format("%Y-%m-%dT%H:00:00Z")
+                    .to_string()
+            })
+            .map_err(|e| format!("Invalid period start: {e}"))?;
+
+        let (sql, params_vec): (&str, Vec<Box<dyn rusqlite::types::ToSql>>) =
+            if budget.scope_type == "host" {
+                (
+                    "SELECT model, SUM(inp), SUM(outp), SUM(cc), SUM(cr)
+                     FROM (
+                         SELECT model, input_tokens as inp, output_tokens as outp,
+                                cache_creation_input_tokens as cc, cache_read_input_tokens as cr
+                         FROM token_snapshots
+                         WHERE timestamp >= ?1 AND hostname = ?2
+                         UNION ALL
+                         SELECT NULLIF(model, ''), total_input, total_output,
+                                total_cache_creation, total_cache_read
+                         FROM token_hourly
+                         WHERE hour >= ?3 AND hostname = ?2
+                     )
+                     GROUP BY model",
+                    vec![
+                        Box::new(from.to_string()),
+                        Box::new(budget.scope_value.clone()),
+                        Box::new(from_hour),
+                    ],
+                )
+            } else {
+                (
+                    "SELECT model, SUM(input_tokens), SUM(output_tokens),
+                            SUM(cache_creation_input_tokens), SUM(cache_read_input_tokens)
+                     FROM token_snapshots
+                     WHERE timestamp >= ?1
+                       AND (cwd = ?2 OR substr(cwd, 1, length(?2) + 1) = ?2 || '/')
+                     GROUP BY model",
+                    vec![
+                        Box::new(from.to_string()),
+                        Box::new(budget.scope_value.clone()),
+                    ],
+                )
+            };
+        let params_refs: Vec<&dyn rusqlite::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+
+        let mut stmt = conn
+            .prepare_cached(sql)
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let rows = stmt
+            .query_map(params_refs.as_slice(), |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let pricing_table = load_pricing(&conn)?;
+        let mut tokens = 0i64;
+        let mut cost = 0.0f64;
+        for row in rows {
+            let (model, inp, out, cc, cr) = row.map_err(|e| format!("Row error: {e}"))?;
+            tokens += inp + out + cc + cr;
+            let rate = pricing::rate_for(&pricing_table, model.as_deref());
+            cost += pricing::cost_usd(rate, inp, out, cc, cr);
+        }
+        Ok((tokens, cost))
+    }
+
+    /// Records that a budget crossed `threshold` percent in a period.
+    /// Returns false if that crossing was already recorded.
+    pub fn record_budget_crossing(
+        &self,
+        budget_id: i64,
+        period_start: &str,
+        threshold: i64,
+    ) -> Result<bool, String> {
+        let conn = self.conn.lock();
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO budget_crossings (budget_id, period_start, threshold)
+                 VALUES (?1, ?2, ?3)",
+                params![budget_id, period_start, threshold],
+            )
+            .map_err(|e| format!("Insert budget crossing error: {e}"))?;
+        Ok(inserted > 0)
+    }
+
+    /// When a budget was recorded crossing `threshold` percent in a period,
+    /// if it has.
+    pub fn budget_crossed_at(
+        &self,
+        budget_id: i64,
+        period_start: &str,
+        threshold: i64,
+    ) -> Result<Option<String>, String> {
+        let conn = self.conn.lock();
+        let crossed_at: Option<String> = conn
+            .query_row(
+                "SELECT crossed_at FROM budget_crossings
+                 WHERE budget_id = ?1 AND period_start = ?2 AND threshold = ?3",
+                params![budget_id, period_start, threshold],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Query budget crossing error: {e}"))?
+            .flatten();
+        // Stored by SQLite's datetime('now'), which is UTC without an offset
+        Ok(crossed_at.and_then(|t| {
+            chrono::NaiveDateTime::parse_from_str(&t, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|t| t.and_utc().to_rfc3339())
+        }))
+    }
+
+    // ── API tokens ──
+
+    pub fn create_api_token(
+        &self,
+        name: &str,
+        hostname: Option<&str>,
+        scopes: &[String],
+        token_hash: &str,
+        token_prefix: &str,
+    ) -> Result<i64, String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO api_tokens (name, hostname, token_hash, token_prefix, scopes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                name,
+                hostname,
+                token_hash,
+                token_prefix,
+                scopes.join(","),
+                Utc::now().to_rfc3339(),
+            ],
+        )
+        .map_err(|e| format!("Insert API token error: {e}"))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_api_token(&self, id: i64) -> Result<Option<ApiToken>, String> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            &format!("SELECT {} FROM api_tokens WHERE id = ?1", API_TOKEN_COLUMNS),
+            params![id],
+            api_token_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))
+    }
+
+    pub fn list_api_tokens(&self) -> Result<Vec<ApiToken>, String> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT {} FROM api_tokens ORDER BY revoked_at IS NOT NULL, id",
+                API_TOKEN_COLUMNS
+            ))
+            .map_err(|e| format!("Prepare error: {e}"))?;
+
+        let rows = stmt
+            .query_map([], api_token_from_row)
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
+
+    /// Active (non-revoked) token matching a hash, used to authenticate requests.
+    pub fn find_api_token(&self, token_hash: &str) -> Result<Option<ApiToken>, String> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM api_tokens WHERE token_hash = ?1 AND revoked_at IS NULL",
+                API_TOKEN_COLUMNS
+            ),
+            params![token_hash],
+            api_token_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))
+    }
+
+    /// Replaces the hash of an active token; the old plaintext stops working.
+    pub fn rotate_api_token(
+        &self,
+        id: i64,
+        token_hash: &str,
+        token_prefix: &str,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock();
+        let updated = conn
+            .execute(
+                "UPDATE api_tokens SET token_hash = ?2, token_prefix = ?3, last_used_at = NULL
+                 WHERE id = ?1 AND revoked_at IS NULL",
+                params![id, token_hash, token_prefix],
+            )
+            .map_err(|e| format!("Rotate API token error: {e}"))?;
+        if updated == 0 {
+            return Err(format!("API token {id} not found or revoked"));
+        }
+        Ok(())
+    }
+
+    pub fn revoke_api_token(&self, id: i64) -> Result<(), String> {
+        let conn = self.conn.lock();
+        let updated = conn
+            .execute(
+                "UPDATE api_tokens SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL",
+                params![id, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Revoke API token error: {e}"))?;
+        if updated == 0 {
+            return Err(format!("API token {id} not found or already revoked"));
+        }
+        Ok(())
+    }
+
+    /// Records that a token was used. Writes at most once a minute per token
+    /// so busy hooks don't turn every request into a write.
+    pub fn touch_api_token(&self, id: i64) -> Result<(), String> {
+        let conn = self.conn.lock();
+        let now = Utc::now();
+        let stale = (now - TimeDelta::minutes(1)).to_rfc3339();
+        conn.execute(
+            "UPDATE api_tokens SET last_used_at = ?2
+             WHERE id = ?1 AND (last_used_at IS NULL OR last_used_at < ?3)",
+            params![id, now.to_rfc3339(), stale],
+        )
+        .map_err(|e| format!("Update API token error: {e}"))?;
+        Ok(())
+    }
+
+    // --- Relay outbox ---
+
+    /// Queues an accepted payload for the upstream Quill. When the outbox
+    /// holds more than `max_rows`, the oldest entries are dropped.
+    pub fn enqueue_outbox(&self, route: &str, body: &str, max_rows: i64) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO relay_outbox (route, body, created_at) VALUES (?1, ?2, ?3)",
+            params![route, body, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| format!("Insert outbox error: {e}"))?;
+        let dropped = conn
+            .execute(
+                "DELETE FROM relay_outbox WHERE id <= (SELECT MAX(id) FROM relay_outbox) - ?1",
+                params![max_rows],
+            )
+            .map_err(|e| format!("Prune outbox error: {e}"))?;
+        if dropped > 0 {
+            log::warn!("Relay outbox full; dropped {dropped} oldest entries");
+        }
+        Ok(())
+    }
+
+    /// Oldest queued entries, in the order they were accepted.
+    pub fn get_outbox(&self, limit: i64) -> Result<Vec<OutboxEntry>, String> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, route, body, attempts, created_at FROM relay_outbox ORDER BY id LIMIT ?1",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let rows = stmt
+            .query_map(params![limit], |row| {
+                Ok(OutboxEntry {
+                    id: row.get(0)?,
+                    route: row.get(1)?,
+                    body: row.get(2)?,
+                    attempts: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row error: {e}"))
+    }
+
+    pub fn delete_outbox_entry(&self, id: i64) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute("DELETE FROM relay_outbox WHERE id = ?1", params![id])
+            .map_err(|e| format!("Delete outbox error: {e}"))?;
+        Ok(())
+    }
+
+    /// Records a failed forwarding attempt.
+    pub fn fail_outbox_entry(&self, id: i64, error: &str) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "UPDATE relay_outbox SET attempts = attempts + 1, last_error = ?2 WHERE id = ?1",
+            params![id, error],
+        )
+        .map_err(|e| format!("Update outbox error: {e}"))?;
+        Ok(())
+    }
+
+    /// (pending entries, oldest created_at, most recent error)
+    pub fn get_outbox_summary(&self) -> Result<(i64, Option<String>, Option<String>), String> {
+        let conn = self.conn.lock();
+        conn.query_row(
+            "SELECT COUNT(*), MIN(created_at),
+                    (SELECT last_error FROM relay_outbox WHERE last_error IS NOT NULL ORDER BY id LIMIT 1)
+             FROM relay_outbox",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Outbox summary error: {e}"))
+    }
+
+    // --- Audit log ---
+
+    pub fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO audit_log
+                 (timestamp, kind, action, actor, token_id, hostname, client_addr,
+                  status, bytes, detail, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                entry.timestamp,
+                entry.kind,
+                entry.action,
+                entry.actor,
+                entry.token_id,
+                entry.hostname,
+                entry.client_addr,
+                entry.status,
+                entry.bytes,
+                entry.detail,
+                entry.error
+            ],
+        )
+        .map_err(|e| format!("Failed to append audit entry: {e}"))?;
+        Ok(())
+    }
+
+    /// Audit entries matching `query`, newest first. No limit returns all.
+    pub fn get_audit_log(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, String> {
+        let mut sql = String::from(
+            "SELECT id, timestamp, kind, action, actor, token_id, hostname, client_addr,
+                    status, bytes, detail, error
+             FROM audit_log WHERE 1 = 1",
+        );
+        let mut values: Vec<String> = Vec::new();
+        if let Some(kind) = &query.kind {
+            values.push(kind.clone());
+            sql.push_str(&format!(" AND kind = ?{}", values.len()));
+        }
+        if let Some(action) = &query.action {
+            values.push(format!("%{action}%"));
+            sql.push_str(&format!(" AND action LIKE ?{}", values.len()));
+        }
+        if let Some(actor) = &query.actor {
+            values.push(actor.clone());
+            sql.push_str(&format!(" AND actor = ?{}", values.len()));
+        }
+        if let Some(hostname) = &query.hostname {
+            values.push(hostname.clone());
+            sql.push_str(&format!(" AND hostname = ?{}", values.len()));
+        }
+        if let Some(days) = query.days {
+            values.push((Utc::now() - TimeDelta::days(days as i64)).to_rfc3339());
+            sql.push_str(&format!(" AND timestamp >= ?{}", values.len()));
+        }
+        sql.push_str(" ORDER BY id DESC");
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {}", limit.max(0)));
+        }
+
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    kind: row.get(2)?,
+                    action: row.get(3)?,
+                    actor: row.get(4)?,
+                    token_id: row.get(5)?,
+                    hostname: row.get(6)?,
+                    client_addr: row.get(7)?,
+                    status: row.get(8)?,
+                    bytes: row.get(9)?,
+                    detail: row.get(10)?,
+                    error: row.get(11)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row error: {e}"))
+    }
+
+    /// Deletes audit entries older than `retention_days`. The table's
+    /// trigger refuses anything newer than a week.
+    pub fn prune_audit_log(&self, retention_days: i64) -> Result<usize, String> {
+        // Whole seconds, like the trigger's cutoff, so an entry from the
+        // boundary second is kept rather than aborting the delete
+        let cutoff = (Utc::now() - TimeDelta::days(retention_days.max(7)))
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+        let conn = self.conn.lock();
+        conn.execute(
+            "DELETE FROM audit_log WHERE timestamp < ?1",
+            params![cutoff],
+        )
+        .map_err(|e| format!("Failed to prune audit log: {e}"))
+    }
+
+    // --- Trash ---
+
+    /// Moves every token row whose `column` equals `target` into the trash
+    /// as one batch. Returns None when nothing matched.
+    #[cfg(feature = "gui")]
+    fn move_to_trash(
+        &self,
+        kind: &str,
+        column: &str,
+        target: &str,
+        include_hourly: bool,
+    ) -> Result<Option<TrashBatch>, String> {
+        let now = Utc::now();
+        let batch_id = format!(
+            "{}-{:06x}",
+            now.format("%Y%m%dT%H%M%SZ"),
+            rand::random::<u32>() & 0xff_ffff
+        );
+
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+
+        let snapshot_count = tx
+            .execute(
+                &format!(
+                    "INSERT INTO token_snapshots_trash (batch_id, {SNAPSHOT_TRASH_COLUMNS})
+                     SELECT ?1, {SNAPSHOT_TRASH_COLUMNS} FROM token_snapshots WHERE {column} = ?2"
+                ),
+                params![batch_id, target],
+            )
+            .map_err(|e| format!("Trash snapshots error: {e}"))?;
+        tx.execute(
+            &format!("DELETE FROM token_snapshots WHERE {column} = ?1"),
+            params![target],
+        )
+        .map_err(|e| format!("Delete snapshots error: {e}"))?;
+
+        let mut hourly_count = 0;
+        if include_hourly {
+            hourly_count = tx
+                .execute(
+                    &format!(
+                        "INSERT INTO token_hourly_trash (batch_id, {HOURLY_TRASH_COLUMNS})
+                         SELECT ?1, {HOURLY_TRASH_COLUMNS} FROM token_hourly WHERE {column} = ?2"
+                    ),
+                    params![batch_id, target],
+                )
+                .map_err(|e| format!("Trash hourly error: {e}"))?;
+            tx.execute(
+                &format!("DELETE FROM token_hourly WHERE {column} = ?1"),
+                params![target],
+            )
+            .map_err(|e| format!("Delete hourly error: {e}"))?;
+        }
+
+        if snapshot_count == 0 && hourly_count == 0 {
+            return Ok(None);
+        }
+
+        let batch = TrashBatch {
+            id: batch_id,
+            kind: kind.to_string(),
+            target: target.to_string(),
+            deleted_at: now.to_rfc3339(),
+            snapshot_count: snapshot_count as i64,
+            hourly_count: hourly_count as i64,
+        };
+        tx.execute(
+            "INSERT INTO trash_batches (id, kind, target, deleted_at, snapshot_count, hourly_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                batch.id,
+                batch.kind,
+                batch.target,
+                batch.deleted_at,
+                batch.snapshot_count,
+                batch.hourly_count
+            ],
+        )
+        .map_err(|e| format!("Trash batch error: {e}"))?;
+
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(Some(batch))
+    }
+
+    /// Trash batches, most recently deleted first.
+    pub fn list_trash_batches(&self) -> Result<Vec<TrashBatch>, String> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {TRASH_BATCH_COLUMNS} FROM trash_batches ORDER BY deleted_at DESC, id DESC"
+            ))
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let rows = stmt
+            .query_map([], trash_batch_from_row)
+            .map_err(|e| format!("Query error: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row error: {e}"))
+    }
+
+    /// Puts a batch's rows back and removes it from the trash. Snapshots
+    /// whose idempotency key has been reported again since are skipped, and
+    /// hourly totals are added to any recorded for the same hour.
+    pub fn restore_trash_batch(&self, batch_id: &str) -> Result<TrashBatch, String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+
+        let batch = Self::find_trash_batch(&tx, batch_id)?;
+
+        tx.execute(
+            &format!(
+                "INSERT OR IGNORE INTO token_snapshots ({SNAPSHOT_TRASH_COLUMNS})
+                 SELECT {SNAPSHOT_TRASH_COLUMNS} FROM token_snapshots_trash WHERE batch_id = ?1"
+            ),
+            params![batch_id],
+        )
+        .map_err(|e| format!("Restore snapshots error: {e}"))?;
+
+        tx.execute(
+            &format!(
+                "INSERT INTO token_hourly ({HOURLY_TRASH_COLUMNS})
+                 SELECT {HOURLY_TRASH_COLUMNS} FROM token_hourly_trash WHERE batch_id = ?1
+                 ON CONFLICT(hour, hostname, model) DO UPDATE SET
+                     total_input = token_hourly.total_input + excluded.total_input,
+                     total_output = token_hourly.total_output + excluded.total_output,
+                     total_cache_creation = token_hourly.total_cache_creation + excluded.total_cache_creation,
+                     total_cache_read = token_hourly.total_cache_read + excluded.total_cache_read,
+                     turn_count = token_hourly.turn_count + excluded.turn_count"
+            ),
+            params![batch_id],
+        )
+        .map_err(|e| format!("Restore hourly error: {e}"))?;
+
+        Self::drop_trash_batch(&tx, batch_id)?;
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(batch)
+    }
+
+    /// Permanently deletes a batch, returning what it held.
+    pub fn purge_trash_batch(&self, batch_id: &str) -> Result<TrashBatch, String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        let batch = Self::find_trash_batch(&tx, batch_id)?;
+        Self::drop_trash_batch(&tx, batch_id)?;
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(batch)
+    }
+
+    /// Permanently deletes batches trashed more than `retention_days` ago.
+    /// Returns the number of batches removed.
+    pub fn purge_expired_trash(&self, retention_days: i64) -> Result<usize, String> {
+        let cutoff = (Utc::now() - TimeDelta::days(retention_days)).to_rfc3339();
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        let ids = {
+            let mut stmt = tx
+                .prepare("SELECT id FROM trash_batches WHERE deleted_at < ?1")
+                .map_err(|e| format!("Prepare error: {e}"))?;
+            let rows = stmt
+                .query_map(params![cutoff], |row| row.get::<_, String>(0))
+                .map_err(|e| format!("Query error: {e}"))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Row error: {e}"))?
+        };
+        for id in &ids {
+            Self::drop_trash_batch(&tx, id)?;
+        }
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(ids.len())
+    }
+
+    fn find_trash_batch(tx: &rusqlite::Transaction, batch_id: &str) -> Result<TrashBatch, String> {
+        tx.query_row(
+            &format!("SELECT {TRASH_BATCH_COLUMNS} FROM trash_batches WHERE id = ?1"),
+            params![batch_id],
+            trash_batch_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Trash lookup error: {e}"))?
+        .ok_or_else(|| format!("Trash batch {batch_id} not found"))
+    }
+
+    fn drop_trash_batch(tx: &rusqlite::Transaction, batch_id: &str) -> Result<(), String> {
+        for sql in [
+            "DELETE FROM token_snapshots_trash WHERE batch_id = ?1",
+            "DELETE FROM token_hourly_trash WHERE batch_id = ?1",
+            "DELETE FROM trash_batches WHERE id = ?1",
+        ] {
+            tx.execute(sql, params![batch_id])
+                .map_err(|e| format!("Trash delete error: {e}"))?;
+        }
+        Ok(())
+    }
+
+    // --- Backup ---
+
+    /// Copies the database to `dest` with SQLite's online backup API. The
+    /// source is read through a separate connection in one step, so the copy
+    /// is consistent and, in WAL mode, writers aren't held up.
+    pub fn backup_to(&self, dest: &Path) -> Result<(), String> {
+        let src = Connection::open(&self.path)
+            .map_err(|e| format!("Failed to open database for backup: {e}"))?;
+        let mut dst = Connection::open(dest)
+            .map_err(|e| format!("Failed to create {}: {e}", dest.display()))?;
+        {
+            let backup =
+                Backup::new(&src, &mut dst).map_err(|e| format!("Database backup failed: {e}"))?;
+            // All pages in one step, retried while another connection
+            // briefly holds a lock
+            let mut attempts = 0;
+            loop {
+                match backup
+                    .step(-1)
+                    .map_err(|e| format!("Database backup failed: {e}"))?
+                {
+                    StepResult::Done => break,
+                    result if attempts < 50 => {
+                        log::debug!("Database backup step returned {result:?}; retrying");
+                        attempts += 1;
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                    result => return Err(format!("Database backup gave up: {result:?}")),
+                }
             }
         }
+        // A single file, without -wal and -shm to carry along
+        dst.execute_batch("PRAGMA journal_mode=DELETE;")
+            .map_err(|e| format!("Failed to finish backup: {e}"))?;
 
-        // Append granular snapshots
-        let (snap_sql, snap_params): (String, Vec<Box<dyn rusqlite::types::ToSql>>) = if let Some(
-            sid,
-        ) =
-            session_id
+        #[cfg(unix)]
         {
-            (
-                    "SELECT timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1 AND session_id = ?2
-                     ORDER BY timestamp ASC".to_string(),
-                    vec![Box::new(from_str.clone()), Box::new(sid.to_string())],
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(dest, std::fs::Permissions::from_mode(0o600));
+        }
+        Ok(())
+    }
+
+    /// Checks that a backed-up database is intact and no newer than this
+    /// build understands. Returns its schema version.
+    pub fn check_backup(path: &Path) -> Result<i32, String> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        let check: String = conn
+            .query_row("PRAGMA quick_check", [], |row| row.get(0))
+            .map_err(|e| format!("Integrity check of {} failed: {e}", path.display()))?;
+        if check != "ok" {
+            return Err(format!("{} is damaged: {check}", path.display()));
+        }
+        let version: i32 = conn
+            .query_row(
+                "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("{} has no schema_version: {e}", path.display()))?;
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "{} is at schema version {version}, newer than this Quill ({SCHEMA_VERSION}); update Quill first",
+                path.display()
+            ));
+        }
+        Ok(version)
+    }
+
+    // --- Session sync state ---
+
+    /// Records that `count` more messages of a pushed session were indexed.
+    /// None keeps the stored value, so a sender that doesn't track offsets
+    /// leaves it alone and one that skipped unindexable lines can still
+    /// move the offset.
+    pub fn advance_session_sync(
+        &self,
+        hostname: &str,
+        session_id: &str,
+        last_uuid: Option<&str>,
+        offset: Option<u64>,
+        count: usize,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO session_sync_state
+                 (hostname, session_id, last_uuid, byte_offset, message_count, updated_at)
+             VALUES (?1, ?2, ?3, COALESCE(?4, 0), ?5, ?6)
+             ON CONFLICT (hostname, session_id) DO UPDATE SET
+                 last_uuid = COALESCE(excluded.last_uuid, last_uuid),
+                 byte_offset = COALESCE(?4, byte_offset),
+                 message_count = message_count + excluded.message_count,
+                 updated_at = excluded.updated_at",
+            params![
+                hostname,
+                session_id,
+                last_uuid,
+                offset.map(|o| o as i64),
+                count as i64,
+                Utc::now().to_rfc3339()
+            ],
+        )
+        .map_err(|e| format!("Failed to update session sync state: {e}"))?;
+        Ok(())
+    }
+
+    /// Sync state of a pushed session; a session never seen starts at offset 0.
+    pub fn get_session_sync(
+        &self,
+        hostname: &str,
+        session_id: &str,
+    ) -> Result<SessionSyncState, String> {
+        let conn = self.conn.lock();
+        let row = conn
+            .query_row(
+                "SELECT last_uuid, byte_offset, message_count, updated_at
+                 FROM session_sync_state WHERE hostname = ?1 AND session_id = ?2",
+                params![hostname, session_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read session sync state: {e}"))?;
+        let (last_uuid, offset, message_count, updated_at) = match row {
+            Some((uuid, offset, count, at)) => (uuid, offset.max(0) as u64, count, Some(at)),
+            None => (None, 0, 0, None),
+        };
+        Ok(SessionSyncState {
+            host: hostname.to_string(),
+            session_id: session_id.to_string(),
+            last_uuid,
+            offset,
+            message_count,
+            updated_at,
+        })
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn get_snapshot_count(&self) -> Result<i64, String> {
+        let conn = self.conn.lock();
+        conn.query_row("SELECT COUNT(*) FROM usage_snapshots", [], |row| row.get(0))
+            .map_err(|e| format!("Count error: {e}"))
+    }
+
+    /// Stores one reported turn. Returns false when a snapshot with the same
+    /// hostname and idempotency key already exists.
+    pub fn store_token_snapshot(&self, payload: &TokenReportPayload) -> Result<bool, String> {
+        let inserted = self.store_token_snapshots(std::slice::from_ref(payload))?;
+        Ok(inserted[0])
+    }
+
+    /// Stores reported turns in one transaction. The result says, per turn,
+    /// whether it was new; repeated idempotency keys are skipped.
+    pub fn store_token_snapshots(
+        &self,
+        payloads: &[TokenReportPayload],
+    ) -> Result<Vec<bool>, String> {
+        let mut conn = self.conn.lock();
+        let now = Utc::now().to_rfc3339();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        let mut inserted = Vec::with_capacity(payloads.len());
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    "INSERT INTO token_snapshots (session_id, hostname, timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens, cwd, model, idempotency_key)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT DO NOTHING",
                 )
-        } else if let Some(project) = cwd {
-            (
-                    "SELECT timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1 AND cwd = ?2
-                     ORDER BY timestamp ASC".to_string(),
-                    vec![Box::new(from_str.clone()), Box::new(project.to_string())],
+                .map_err(|e| format!("Prepare error: {e}"))?;
+            for payload in payloads {
+                let n = stmt
+                    .execute(params![
+                        payload.session_id,
+                        payload.hostname,
+                        now,
+                        payload.input_tokens,
+                        payload.output_tokens,
+                        payload.cache_creation_input_tokens,
+                        payload.cache_read_input_tokens,
+                        payload.cwd,
+                        payload.model,
+                        payload.idempotency_key,
+                    ])
+                    .map_err(|e| format!("Insert token snapshot error: {e}"))?;
+                inserted.push(n > 0);
+            }
+        }
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(inserted)
+    }
+
+    /// Timestamp before which transcript usage may be backfilled for a host.
+    pub fn token_import_cutoff(&self, hostname: &str) -> Result<String, String> {
+        let key = format!("token_import_cutoff:{hostname}");
+        if let Some(cutoff) = self.get_setting(&key)? {
+            return Ok(cutoff);
+        }
+
+        // Fixed on first use to the earliest hook-reported data (or now), so
+        // turns the Stop hook already reported are never imported again
+        let cutoff = {
+            let conn = self.conn.lock();
+            let earliest: Option<String> = conn
+                .query_row(
+                    "SELECT MIN(ts) FROM (
+                         SELECT MIN(timestamp) as ts FROM token_snapshots WHERE hostname = ?1
+                         UNION ALL
+                         SELECT MIN(hour) FROM token_hourly WHERE hostname = ?1
+                     )",
+                    params![hostname],
+                    |row| row.get(0),
                 )
-        } else if let Some(host) = hostname {
-            (
-                    "SELECT timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1 AND hostname = ?2
-                     ORDER BY timestamp ASC".to_string(),
-                    vec![Box::new(from_str.clone()), Box::new(host.to_string())],
+                .map_err(|e| format!("Query error: {e}"))?;
+            earliest
+                .and_then(|ts| DateTime::parse_from_rfc3339(&ts).ok())
+                .map(|ts| ts.with_timezone(&Utc))
+                .unwrap_or_else(Utc::now)
+                .to_rfc3339()
+        };
+        self.set_setting(&key, &cutoff)?;
+        Ok(cutoff)
+    }
+
+    /// Insert transcript usage older than `cutoff` as token snapshots, skipping
+    /// message ids imported before. Returns (imported, skipped).
+    pub fn import_token_usage(
+        &self,
+        hostname: &str,
+        cutoff: &str,
+        turns: &[crate::sessions::TurnUsage],
+    ) -> Result<(usize, usize), String> {
+        let cutoff = DateTime::parse_from_rfc3339(cutoff)
+            .map_err(|e| format!("Invalid import cutoff: {e}"))?
+            .with_timezone(&Utc);
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        let mut imported = 0usize;
+        let mut skipped = 0usize;
+        {
+            let mut log_stmt = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO token_import_log (message_id, session_id) VALUES (?1, ?2)",
                 )
-        } else {
-            (
-                    "SELECT timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1
-                     ORDER BY timestamp ASC".to_string(),
-                    vec![Box::new(from_str.clone())],
+                .map_err(|e| format!("Prepare error: {e}"))?;
+            let mut snap_stmt = tx
+                .prepare_cached(
+                    "INSERT INTO token_snapshots (session_id, hostname, timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens, cwd, model, idempotency_key)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT DO NOTHING",
                 )
+                .map_err(|e| format!("Prepare error: {e}"))?;
+
+            for turn in turns {
+                let Some(ts) = DateTime::parse_from_rfc3339(&turn.timestamp)
+                    .ok()
+                    .map(|t| t.with_timezone(&Utc))
+                else {
+                    skipped += 1;
+                    continue;
+                };
+                if ts >= cutoff {
+                    skipped += 1;
+                    continue;
+                }
+                let new = log_stmt
+                    .execute(params![turn.message_id, turn.session_id])
+                    .map_err(|e| format!("Insert import log error: {e}"))?;
+                if new == 0 {
+                    skipped += 1;
+                    continue;
+                }
+                // Keyed by message id, so turns the hook already reported are skipped
+                let new = snap_stmt
+                    .execute(params![
+                        turn.session_id,
+                        hostname,
+                        ts.to_rfc3339(),
+                        turn.input_tokens,
+                        turn.output_tokens,
+                        turn.cache_creation_input_tokens,
+                        turn.cache_read_input_tokens,
+                        turn.cwd,
+                        turn.model,
+                        turn.message_id,
+                    ])
+                    .map_err(|e| format!("Insert token snapshot error: {e}"))?;
+                if new == 0 {
+                    skipped += 1;
+                    continue;
+                }
+                imported += 1;
+            }
+        }
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok((imported, skipped))
+    }
+
+    /// Token usage over time. `model` narrows results to turns whose model id
+    /// contains the given string (e.g. "opus"), case-insensitively.
+    pub fn get_token_history(
+        &self,
+        range: &str,
+        hostname: Option<&str>,
+        session_id: Option<&str>,
+        cwd: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<Vec<TokenDataPoint>, String> {
+        let conn = self.conn.lock();
+        let now = Utc::now();
+
+        // Skip hourly aggregates when filtering by session_id or cwd since
+        // token_hourly doesn't store those fields
+        let needs_granular = session_id.is_some() || cwd.is_some();
+        let (from, use_hourly) = match range {
+            "1h" => (now - TimeDelta::hours(1), false),
+            "24h" => (now - TimeDelta::hours(24), false),
+            "7d" => (now - TimeDelta::days(7), false),
+            "30d" => (now - TimeDelta::days(30), !needs_granular),
+            "all" => (now - TimeDelta::days(365), !needs_granular),
+            _ => (now - TimeDelta::hours(24), false),
         };
 
+        let from_str = from.to_rfc3339();
+        let mut points = Vec::new();
+
+        if use_hourly {
+            let from_hour = from.format("%Y-%m-%dT%H:00:00Z").to_string();
+
+            let mut hourly_params: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(from_hour)];
+            let mut hourly_filter = String::new();
+            if let Some(host) = hostname {
+                hourly_params.push(Box::new(host.to_string()));
+                hourly_filter.push_str(&format!(" AND hostname = ?{}", hourly_params.len()));
+            }
+            if let Some(m) = model {
+                hourly_params.push(Box::new(like_escape(m)));
+                hourly_filter.push_str(&model_filter_clause("model", hourly_params.len()));
+            }
+            let hourly_sql = format!(
+                "SELECT hour, SUM(total_input), SUM(total_output), SUM(total_cache_creation), SUM(total_cache_read)
+                 FROM token_hourly
+                 WHERE hour >= ?1{hourly_filter}
+                 GROUP BY hour
+                 ORDER BY hour ASC"
+            );
+
+            let mut stmt = conn
+                .prepare(&hourly_sql)
+                .map_err(|e| format!("Prepare error: {e}"))?;
+
+            let params_refs: Vec<&dyn rusqlite::types::ToSql> =
+                hourly_params.iter().map(|p| p.as_ref()).collect();
+
+            let rows = stmt
+                .query_map(params_refs.as_slice(), |row| {
+                    let inp: i64 = row.get(1)?;
+                    let out: i64 = row.get(2)?;
+                    let cc: i64 = row.get(3)?;
+                    let cr: i64 = row.get(4)?;
+                    Ok(TokenDataPoint {
+                        timestamp: row.get(0)?,
+                        input_tokens: inp,
+                        output_tokens: out,
+                        cache_creation_input_tokens: cc,
+                        cache_read_input_tokens: cr,
+                        total_tokens: inp + out + cc + cr,
+                    })
+                })
+                .map_err(|e| format!("Query error: {e}"))?;
+
+            for row in rows {
+                points.push(row.map_err(|e| format!("Row error: {e}"))?);
+            }
+        }
+
+        // Append granular snapshots. Session, project and host filters are
+        // mutually exclusive (most specific wins); the model filter stacks.
+        let mut snap_params: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(from_str)];
+        let mut snap_filter = String::new();
+        if let Some(sid) = session_id {
+            snap_params.push(Box::new(sid.to_string()));
+            snap_filter.push_str(" AND session_id = ?2");
+        } else if let Some(project) = cwd {
+            snap_params.push(Box::new(project.to_string()));
+            snap_filter.push_str(" AND cwd = ?2");
+        } else if let Some(host) = hostname {
+            snap_params.push(Box::new(host.to_string()));
+            snap_filter.push_str(" AND hostname = ?2");
+        }
+        if let Some(m) = model {
+            snap_params.push(Box::new(like_escape(m)));
+            snap_filter.push_str(&model_filter_clause("model", snap_params.len()));
+        }
+        let snap_sql = format!(
+            "SELECT timestamp, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens
+             FROM token_snapshots
+             WHERE timestamp >= ?1{snap_filter}
+             ORDER BY timestamp ASC"
+        );
+
         let mut stmt2 = conn
             .prepare(&snap_sql)
             .map_err(|e| format!("Prepare error: {e}"))?;
@@ -1004,52 +2645,35 @@ impl Storage {
         days: i32,
         hostname: Option<&str>,
         cwd: Option<&str>,
+        model: Option<&str>,
     ) -> Result<TokenStats, String> {
         let days = days.clamp(1, 365);
         let conn = self.conn.lock();
         let from = (Utc::now() - TimeDelta::days(days as i64)).to_rfc3339();
 
-        let (sql, params_vec): (String, Vec<Box<dyn rusqlite::types::ToSql>>) =
-            if let Some(project) = cwd {
-                (
-                    "SELECT
-                         COALESCE(SUM(input_tokens), 0),
-                         COALESCE(SUM(output_tokens), 0),
-                         COALESCE(SUM(cache_creation_input_tokens), 0),
-                         COALESCE(SUM(cache_read_input_tokens), 0),
-                         COUNT(*)
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1 AND cwd = ?2"
-                        .to_string(),
-                    vec![Box::new(from), Box::new(project.to_string())],
-                )
-            } else if let Some(host) = hostname {
-                (
-                    "SELECT
-                         COALESCE(SUM(input_tokens), 0),
-                         COALESCE(SUM(output_tokens), 0),
-                         COALESCE(SUM(cache_creation_input_tokens), 0),
-                         COALESCE(SUM(cache_read_input_tokens), 0),
-                         COUNT(*)
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1 AND hostname = ?2"
-                        .to_string(),
-                    vec![Box::new(from), Box::new(host.to_string())],
-                )
-            } else {
-                (
-                    "SELECT
-                         COALESCE(SUM(input_tokens), 0),
-                         COALESCE(SUM(output_tokens), 0),
-                         COALESCE(SUM(cache_creation_input_tokens), 0),
-                         COALESCE(SUM(cache_read_input_tokens), 0),
-                         COUNT(*)
-                     FROM token_snapshots
-                     WHERE timestamp >= ?1"
-                        .to_string(),
-                    vec![Box::new(from)],
-                )
-            };
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(from)];
+        let mut filter = String::new();
+        if let Some(project) = cwd {
+            params_vec.push(Box::new(project.to_string()));
+            filter.push_str(" AND cwd = ?2");
+        } else if let Some(host) = hostname {
+            params_vec.push(Box::new(host.to_string()));
+            filter.push_str(" AND hostname = ?2");
+        }
+        if let Some(m) = model {
+            params_vec.push(Box::new(like_escape(m)));
+            filter.push_str(&model_filter_clause("model", params_vec.len()));
+        }
+        let sql = format!(
+            "SELECT
+                 COALESCE(SUM(input_tokens), 0),
+                 COALESCE(SUM(output_tokens), 0),
+                 COALESCE(SUM(cache_creation_input_tokens), 0),
+                 COALESCE(SUM(cache_read_input_tokens), 0),
+                 COUNT(*)
+             FROM token_snapshots
+             WHERE timestamp >= ?1{filter}"
+        );
 
         let mut stmt = conn
             .prepare(&sql)
@@ -1058,6 +2682,17 @@ impl Storage {
         let params_refs: Vec<&dyn rusqlite::types::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
 
+        let pricing_table = load_pricing(&conn)?;
+        let cost_usd = group_costs(
+            &conn,
+            &pricing_table,
+            "''",
+            &format!("timestamp >= ?1{filter}"),
+            &params_refs,
+        )?
+        .into_values()
+        .sum();
+
         stmt.query_row(params_refs.as_slice(), |row| {
             let total_input: i64 = row.get(0)?;
             let total_output: i64 = row.get(1)?;
@@ -1083,11 +2718,13 @@ impl Storage {
                 } else {
                     0.0
                 },
+                cost_usd,
             })
         })
         .map_err(|e| format!("Query error: {e}"))
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_token_hostnames(&self) -> Result<Vec<String>, String> {
         let conn = self.conn.lock();
         let mut stmt = conn
@@ -1105,30 +2742,62 @@ impl Storage {
         Ok(hostnames)
     }
 
-    pub fn get_host_breakdown(&self, days: i32) -> Result<Vec<HostBreakdown>, String> {
+    pub fn get_host_breakdown(
+        &self,
+        days: i32,
+        model: Option<&str>,
+    ) -> Result<Vec<HostBreakdown>, String> {
         let days = days.clamp(1, 365);
         let conn = self.conn.lock();
         let from = (Utc::now() - TimeDelta::days(days as i64)).to_rfc3339();
+        let model = model.map(like_escape);
 
+        // One fixed statement per filter, so the statement cache never sees
+        // built SQL. Yields the query and its WHERE clause for `group_costs`.
+        macro_rules! host_breakdown {
+            ($filter:literal) => {
+                (
+                    concat!(
+                        "SELECT
+                             hostname,
+                             SUM(input_tokens + output_tokens + cache_creation_input_tokens + cache_read_input_tokens) as total_tokens,
+                             COUNT(*) as turn_count,
+                             MAX(timestamp) as last_active
+                         FROM token_snapshots
+                         WHERE ",
+                        $filter,
+                        "
+                         GROUP BY hostname
+                         ORDER BY total_tokens DESC
+                         LIMIT 50"
+                    ),
+                    $filter,
+                )
+            };
+        }
+        let (sql, filter) = match model {
+            Some(_) => {
+                host_breakdown!("timestamp >= ?1 AND model LIKE '%' || ?2 || '%' ESCAPE '\\'")
+            }
+            None => host_breakdown!("timestamp >= ?1"),
+        };
         let mut stmt = conn
-            .prepare_cached(
-                "SELECT
-                     hostname,
-                     SUM(input_tokens + output_tokens + cache_creation_input_tokens + cache_read_input_tokens) as total_tokens,
-                     COUNT(*) as turn_count,
-                     MAX(timestamp) as last_active
-                 FROM token_snapshots
-                 WHERE timestamp >= ?1
-                 GROUP BY hostname
-                 ORDER BY total_tokens DESC
-                 LIMIT 50",
-            )
+            .prepare_cached(sql)
             .map_err(|e| format!("Prepare error: {e}"))?;
 
+        let params_vec: Vec<&dyn rusqlite::types::ToSql> = match &model {
+            Some(m) => vec![&from, m],
+            None => vec![&from],
+        };
+        let pricing_table = load_pricing(&conn)?;
+        let costs = group_costs(&conn, &pricing_table, "hostname", filter, &params_vec)?;
+
         let rows = stmt
-            .query_map(params![from], |row| {
+            .query_map(params_vec.as_slice(), |row| {
+                let hostname: String = row.get(0)?;
                 Ok(HostBreakdown {
-                    hostname: row.get(0)?,
+                    cost_usd: costs.get(&hostname).copied().unwrap_or(0.0),
+                    hostname,
                     total_tokens: row.get(1)?,
                     turn_count: row.get(2)?,
                     last_active: row.get(3)?,
@@ -1143,33 +2812,74 @@ impl Storage {
         Ok(results)
     }
 
-    pub fn get_project_breakdown(&self, days: i32) -> Result<Vec<ProjectBreakdown>, String> {
+    pub fn get_project_breakdown(
+        &self,
+        days: i32,
+        model: Option<&str>,
+    ) -> Result<Vec<ProjectBreakdown>, String> {
         let days = days.clamp(1, 365);
         let conn = self.conn.lock();
         let from = (Utc::now() - TimeDelta::days(days as i64)).to_rfc3339();
+        let model = model.map(like_escape);
 
+        // Fixed statements per filter, as in `get_host_breakdown`
+        macro_rules! project_breakdown {
+            ($filter:literal) => {
+                (
+                    concat!(
+                        "SELECT
+                             cwd,
+                             hostname,
+                             SUM(input_tokens + output_tokens + cache_creation_input_tokens + cache_read_input_tokens) as total_tokens,
+                             COUNT(*) as turn_count,
+                             COUNT(DISTINCT session_id) as session_count,
+                             MAX(timestamp) as last_active
+                         FROM token_snapshots
+                         WHERE ",
+                        $filter,
+                        "
+                         GROUP BY cwd, hostname
+                         ORDER BY total_tokens DESC
+                         LIMIT 200"
+                    ),
+                    $filter,
+                )
+            };
+        }
+        let (sql, filter) = match model {
+            Some(_) => project_breakdown!(
+                "timestamp >= ?1 AND cwd IS NOT NULL AND model LIKE '%' || ?2 || '%' ESCAPE '\\'"
+            ),
+            None => project_breakdown!("timestamp >= ?1 AND cwd IS NOT NULL"),
+        };
         let mut stmt = conn
-            .prepare_cached(
-                "SELECT
-                     cwd,
-                     hostname,
-                     SUM(input_tokens + output_tokens + cache_creation_input_tokens + cache_read_input_tokens) as total_tokens,
-                     COUNT(*) as turn_count,
-                     COUNT(DISTINCT session_id) as session_count,
-                     MAX(timestamp) as last_active
-                 FROM token_snapshots
-                 WHERE timestamp >= ?1 AND cwd IS NOT NULL
-                 GROUP BY cwd, hostname
-                 ORDER BY total_tokens DESC
-                 LIMIT 200",
-            )
+            .prepare_cached(sql)
             .map_err(|e| format!("Prepare error: {e}"))?;
 
+        let params_vec: Vec<&dyn rusqlite::types::ToSql> = match &model {
+            Some(m) => vec![&from, m],
+            None => vec![&from],
+        };
+        let pricing_table = load_pricing(&conn)?;
+        let costs = group_costs(
+            &conn,
+            &pricing_table,
+            "cwd || char(31) || hostname",
+            filter,
+            &params_vec,
+        )?;
+
         let rows = stmt
-            .query_map(params![from], |row| {
+            .query_map(params_vec.as_slice(), |row| {
+                let project: String = row.get(0)?;
+                let hostname: String = row.get(1)?;
                 Ok(ProjectBreakdown {
-                    project: row.get(0)?,
-                    hostname: row.get(1)?,
+                    cost_usd: costs
+                        .get(&format!("{project}\u{1f}{hostname}"))
+                        .copied()
+                        .unwrap_or(0.0),
+                    project,
+                    hostname,
                     total_tokens: row.get(2)?,
                     turn_count: row.get(3)?,
                     session_count: row.get(4)?,
@@ -1192,52 +2902,39 @@ impl Storage {
         &self,
         days: i32,
         hostname: Option<&str>,
+        model: Option<&str>,
     ) -> Result<Vec<SessionBreakdown>, String> {
         let days = days.clamp(1, 365);
         let conn = self.conn.lock();
         let from = (Utc::now() - TimeDelta::days(days as i64)).to_rfc3339();
 
-        let (sql, params_vec): (String, Vec<Box<dyn rusqlite::types::ToSql>>) = if let Some(host) =
-            hostname
-        {
-            (
-                    "SELECT
-                         s.session_id,
-                         s.hostname,
-                         SUM(s.input_tokens + s.output_tokens + s.cache_creation_input_tokens + s.cache_read_input_tokens) as total_tokens,
-                         COUNT(*) as turn_count,
-                         MIN(s.timestamp) as first_seen,
-                         MAX(s.timestamp) as last_active,
-                         (SELECT t.cwd FROM token_snapshots t
-                          WHERE t.session_id = s.session_id AND t.cwd IS NOT NULL
-                          ORDER BY t.timestamp DESC LIMIT 1) as project
-                     FROM token_snapshots s
-                     WHERE s.timestamp >= ?1 AND s.hostname = ?2
-                     GROUP BY s.session_id
-                     ORDER BY last_active DESC
-                     LIMIT 10".to_string(),
-                    vec![Box::new(from), Box::new(host.to_string())],
-                )
-        } else {
-            (
-                    "SELECT
-                         s.session_id,
-                         s.hostname,
-                         SUM(s.input_tokens + s.output_tokens + s.cache_creation_input_tokens + s.cache_read_input_tokens) as total_tokens,
-                         COUNT(*) as turn_count,
-                         MIN(s.timestamp) as first_seen,
-                         MAX(s.timestamp) as last_active,
-                         (SELECT t.cwd FROM token_snapshots t
-                          WHERE t.session_id = s.session_id AND t.cwd IS NOT NULL
-                          ORDER BY t.timestamp DESC LIMIT 1) as project
-                     FROM token_snapshots s
-                     WHERE s.timestamp >= ?1
-                     GROUP BY s.session_id
-                     ORDER BY last_active DESC
-                     LIMIT 10".to_string(),
-                    vec![Box::new(from)],
-                )
-        };
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(from)];
+        let mut filter = String::new();
+        if let Some(host) = hostname {
+            params_vec.push(Box::new(host.to_string()));
+            filter.push_str(" AND s.hostname = ?2");
+        }
+        if let Some(m) = model {
+            params_vec.push(Box::new(like_escape(m)));
+            filter.push_str(&model_filter_clause("s.model", params_vec.len()));
+        }
+        let sql = format!(
+            "SELECT
+                 s.session_id,
+                 s.hostname,
+                 SUM(s.input_tokens + s.output_tokens + s.cache_creation_input_tokens + s.cache_read_input_tokens) as total_tokens,
+                 COUNT(*) as turn_count,
+                 MIN(s.timestamp) as first_seen,
+                 MAX(s.timestamp) as last_active,
+                 (SELECT t.cwd FROM token_snapshots t
+                  WHERE t.session_id = s.session_id AND t.cwd IS NOT NULL
+                  ORDER BY t.timestamp DESC LIMIT 1) as project
+             FROM token_snapshots s
+             WHERE s.timestamp >= ?1{filter}
+             GROUP BY s.session_id
+             ORDER BY last_active DESC
+             LIMIT 10"
+        );
 
         let mut stmt = conn
             .prepare(&sql)
@@ -1246,10 +2943,73 @@ impl Storage {
         let params_refs: Vec<&dyn rusqlite::types::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
 
+        let pricing_table = load_pricing(&conn)?;
+        let costs = group_costs(
+            &conn,
+            &pricing_table,
+            "s.session_id",
+            &format!("s.timestamp >= ?1{filter}"),
+            &params_refs,
+        )?;
+
         let rows = stmt
             .query_map(params_refs.as_slice(), |row| {
+                let session_id: String = row.get(0)?;
+                Ok(SessionBreakdown {
+                    cost_usd: costs.get(&session_id).copied().unwrap_or(0.0),
+                    session_id,
+                    hostname: row.get(1)?,
+                    total_tokens: row.get(2)?,
+                    turn_count: row.get(3)?,
+                    first_seen: row.get(4)?,
+                    last_active: row.get(5)?,
+                    project: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
+
+    /// Token totals and estimated cost for a single session, across all time.
+    pub fn get_session_summary(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<SessionBreakdown>, String> {
+        let conn = self.conn.lock();
+        let pricing_table = load_pricing(&conn)?;
+        let costs = group_costs(
+            &conn,
+            &pricing_table,
+            "s.session_id",
+            "s.session_id = ?1",
+            &[&session_id],
+        )?;
+
+        conn.query_row(
+            "SELECT
+                 s.session_id,
+                 s.hostname,
+                 SUM(s.input_tokens + s.output_tokens + s.cache_creation_input_tokens + s.cache_read_input_tokens) as total_tokens,
+                 COUNT(*) as turn_count,
+                 MIN(s.timestamp) as first_seen,
+                 MAX(s.timestamp) as last_active,
+                 (SELECT t.cwd FROM token_snapshots t
+                  WHERE t.session_id = s.session_id AND t.cwd IS NOT NULL
+                  ORDER BY t.timestamp DESC LIMIT 1) as project
+             FROM token_snapshots s
+             WHERE s.session_id = ?1
+             GROUP BY s.session_id",
+            params![session_id],
+            |row| {
+                let session_id: String = row.get(0)?;
                 Ok(SessionBreakdown {
-                    session_id: row.get(0)?,
+                    cost_usd: costs.get(&session_id).copied().unwrap_or(0.0),
+                    session_id,
                     hostname: row.get(1)?,
                     total_tokens: row.get(2)?,
                     turn_count: row.get(3)?,
@@ -1257,6 +3017,205 @@ impl Storage {
                     last_active: row.get(5)?,
                     project: row.get(6)?,
                 })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))
+    }
+
+    pub fn get_model_breakdown(
+        &self,
+        days: i32,
+        hostname: Option<&str>,
+        cwd: Option<&str>,
+    ) -> Result<Vec<ModelBreakdown>, String> {
+        let days = days.clamp(1, 365);
+        let conn = self.conn.lock();
+        let from = (Utc::now() - TimeDelta::days(days as i64)).to_rfc3339();
+
+        let mut params_vec: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(from)];
+        let mut filter = String::new();
+        if let Some(project) = cwd {
+            params_vec.push(Box::new(project.to_string()));
+            filter.push_str(" AND cwd = ?2");
+        } else if let Some(host) = hostname {
+            params_vec.push(Box::new(host.to_string()));
+            filter.push_str(" AND hostname = ?2");
+        }
+        let sql = format!(
+            "SELECT
+                 model,
+                 SUM(input_tokens),
+                 SUM(output_tokens),
+                 SUM(cache_creation_input_tokens),
+                 SUM(cache_read_input_tokens),
+                 COUNT(*) as turn_count,
+                 COUNT(DISTINCT session_id) as session_count,
+                 MAX(timestamp) as last_active
+             FROM token_snapshots
+             WHERE timestamp >= ?1{filter}
+             GROUP BY model
+             ORDER BY SUM(input_tokens + output_tokens + cache_creation_input_tokens + cache_read_input_tokens) DESC"
+        );
+
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("Prepare error: {e}"))?;
+
+        let params_refs: Vec<&dyn rusqlite::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+
+        let pricing_table = load_pricing(&conn)?;
+        let rows = stmt
+            .query_map(params_refs.as_slice(), |row| {
+                let model: Option<String> = row.get(0)?;
+                let inp: i64 = row.get(1)?;
+                let out: i64 = row.get(2)?;
+                let cc: i64 = row.get(3)?;
+                let cr: i64 = row.get(4)?;
+                let rate = pricing::rate_for(&pricing_table, model.as_deref());
+                Ok(ModelBreakdown {
+                    cost_usd: pricing::cost_usd(rate, inp, out, cc, cr),
+                    model,
+                    input_tokens: inp,
+                    output_tokens: out,
+                    cache_creation_input_tokens: cc,
+                    cache_read_input_tokens: cr,
+                    total_tokens: inp + out + cc + cr,
+                    turn_count: row.get(5)?,
+                    session_count: row.get(6)?,
+                    last_active: row.get(7)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn get_model_pricing(&self) -> Result<Vec<ModelPricing>, String> {
+        let conn = self.conn.lock();
+        load_pricing(&conn)
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn upsert_model_pricing(&self, payload: &ModelPricingPayload) -> Result<(), String> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO model_pricing
+                (model_pattern, input_per_mtok, output_per_mtok, cache_write_per_mtok, cache_read_per_mtok, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+             ON CONFLICT(model_pattern) DO UPDATE SET
+                 input_per_mtok = excluded.input_per_mtok,
+                 output_per_mtok = excluded.output_per_mtok,
+                 cache_write_per_mtok = excluded.cache_write_per_mtok,
+                 cache_read_per_mtok = excluded.cache_read_per_mtok,
+                 updated_at = excluded.updated_at",
+            params![
+                payload.model_pattern.trim().to_lowercase(),
+                payload.input_per_mtok,
+                payload.output_per_mtok,
+                payload.cache_write_per_mtok,
+                payload.cache_read_per_mtok,
+            ],
+        )
+        .map_err(|e| format!("Upsert model pricing error: {e}"))?;
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn delete_model_pricing(&self, model_pattern: &str) -> Result<(), String> {
+        if model_pattern == pricing::FALLBACK_PATTERN {
+            return Err("The fallback (*) rate cannot be deleted".to_string());
+        }
+        let conn = self.conn.lock();
+        conn.execute(
+            "DELETE FROM model_pricing WHERE model_pattern = ?1",
+            params![model_pattern],
+        )
+        .map_err(|e| format!("Delete model pricing error: {e}"))?;
+        Ok(())
+    }
+
+    /// Replaces the pricing table with the built-in defaults.
+    #[cfg(feature = "gui")]
+    pub fn reset_model_pricing(&self) -> Result<(), String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        tx.execute("DELETE FROM model_pricing", [])
+            .map_err(|e| format!("Delete model pricing error: {e}"))?;
+        seed_default_pricing(&tx).map_err(|e| format!("Seed pricing error: {e}"))?;
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(())
+    }
+
+    /// Estimated cost per model over raw snapshots and hourly aggregates, so
+    /// ranges longer than the 30-day snapshot retention are still covered.
+    pub fn get_cost_summary(
+        &self,
+        days: i32,
+        hostname: Option<&str>,
+    ) -> Result<Vec<ModelCost>, String> {
+        let days = days.clamp(1, 365);
+        let conn = self.conn.lock();
+        let from = Utc::now() - TimeDelta::days(days as i64);
+        let from_str = from.to_rfc3339();
+        let from_hour = from.format("%Y-%m-%dT%H:00:00Z").to_string();
+        let host_filter = if hostname.is_some() {
+            " AND hostname = ?3"
+        } else {
+            ""
+        };
+
+        let sql = format!(
+            "SELECT model, SUM(inp), SUM(outp), SUM(cc), SUM(cr), SUM(turns)
+             FROM (
+                 SELECT model, input_tokens as inp, output_tokens as outp,
+                        cache_creation_input_tokens as cc, cache_read_input_tokens as cr, 1 as turns
+                 FROM token_snapshots
+                 WHERE timestamp >= ?1{host_filter}
+                 UNION ALL
+                 SELECT NULLIF(model, ''), total_input, total_output,
+                        total_cache_creation, total_cache_read, turn_count
+                 FROM token_hourly
+                 WHERE hour >= ?2{host_filter}
+             )
+             GROUP BY model"
+        );
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("Prepare error: {e}"))?;
+
+        let params_vec: Vec<&dyn rusqlite::types::ToSql> = match &hostname {
+            Some(h) => vec![&from_str, &from_hour, h],
+            None => vec![&from_str, &from_hour],
+        };
+        let pricing_table = load_pricing(&conn)?;
+        let rows = stmt
+            .query_map(params_vec.as_slice(), |row| {
+                let model: Option<String> = row.get(0)?;
+                let inp: i64 = row.get(1)?;
+                let out: i64 = row.get(2)?;
+                let cc: i64 = row.get(3)?;
+                let cr: i64 = row.get(4)?;
+                let rate = pricing::rate_for(&pricing_table, model.as_deref());
+                Ok(ModelCost {
+                    pricing_pattern: rate.map(|r| r.model_pattern.clone()),
+                    cost_usd: pricing::cost_usd(rate, inp, out, cc, cr),
+                    model,
+                    input_tokens: inp,
+                    output_tokens: out,
+                    cache_creation_input_tokens: cc,
+                    cache_read_input_tokens: cr,
+                    total_tokens: inp + out + cc + cr,
+                    turn_count: row.get(5)?,
+                })
             })
             .map_err(|e| format!("Query error: {e}"))?;
 
@@ -1264,9 +3223,11 @@ impl Storage {
         for row in rows {
             results.push(row.map_err(|e| format!("Row error: {e}"))?);
         }
+        results.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
         Ok(results)
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_project_tokens(&self, days: i32) -> Result<Vec<ProjectTokens>, String> {
         let days = days.clamp(1, 365);
         let conn = self.conn.lock();
@@ -1313,6 +3274,7 @@ impl Storage {
         Ok(results)
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_session_stats(&self, days: i32) -> Result<SessionStats, String> {
         let conn = self.conn.lock();
         let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
@@ -1371,55 +3333,25 @@ impl Storage {
         Ok(())
     }
 
-    pub fn delete_host_data(&self, hostname: &str) -> Result<u64, String> {
-        let mut conn = self.conn.lock();
-
-        let tx = conn
-            .transaction()
-            .map_err(|e| format!("Transaction error: {e}"))?;
-
-        let snap_count = tx
-            .execute(
-                "DELETE FROM token_snapshots WHERE hostname = ?1",
-                params![hostname],
-            )
-            .map_err(|e| format!("Delete snapshots error: {e}"))?;
-
-        let hourly_count = tx
-            .execute(
-                "DELETE FROM token_hourly WHERE hostname = ?1",
-                params![hostname],
-            )
-            .map_err(|e| format!("Delete hourly error: {e}"))?;
-
-        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
-
-        Ok((snap_count + hourly_count) as u64)
+    /// Moves a host's snapshots and hourly totals to the trash.
+    #[cfg(feature = "gui")]
+    pub fn delete_host_data(&self, hostname: &str) -> Result<Option<TrashBatch>, String> {
+        self.move_to_trash("host", "hostname", hostname, true)
     }
 
-    pub fn delete_session_data(&self, session_id: &str) -> Result<u64, String> {
-        let conn = self.conn.lock();
-
-        let count = conn
-            .execute(
-                "DELETE FROM token_snapshots WHERE session_id = ?1",
-                params![session_id],
-            )
-            .map_err(|e| format!("Delete error: {e}"))?;
-
-        Ok(count as u64)
+    /// Moves a session's snapshots to the trash.
+    #[cfg(feature = "gui")]
+    pub fn delete_session_data(&self, session_id: &str) -> Result<Option<TrashBatch>, String> {
+        self.move_to_trash("session", "session_id", session_id, false)
     }
 
-    pub fn delete_project_data(&self, cwd: &str) -> Result<u64, String> {
-        let conn = self.conn.lock();
-
-        let count = conn
-            .execute("DELETE FROM token_snapshots WHERE cwd = ?1", params![cwd])
-            .map_err(|e| format!("Delete error: {e}"))?;
-
-        Ok(count as u64)
+    /// Moves a project's snapshots to the trash.
+    #[cfg(feature = "gui")]
+    pub fn delete_project_data(&self, cwd: &str) -> Result<Option<TrashBatch>, String> {
+        self.move_to_trash("project", "cwd", cwd, false)
     }
 
+    #[cfg(feature = "gui")]
     pub fn rename_project(&self, old_cwd: &str, new_cwd: &str) -> Result<u64, String> {
         let new_cwd = new_cwd.trim();
         if new_cwd.is_empty() {
@@ -1455,6 +3387,8 @@ impl Storage {
     // --- Learning system methods ---
 
     pub fn store_observation(&self, payload: &ObservationPayload) -> Result<(), String> {
+        let tool_input = payload.tool_input.as_deref().map(redact);
+        let tool_output = payload.tool_output.as_deref().map(redact);
         let conn = self.conn.lock();
         let now = Utc::now().to_rfc3339();
         conn.execute(
@@ -1465,8 +3399,8 @@ impl Storage {
                 now,
                 payload.hook_phase,
                 payload.tool_name,
-                payload.tool_input,
-                payload.tool_output,
+                tool_input,
+                tool_output,
                 payload.cwd,
             ],
         )
@@ -1564,6 +3498,7 @@ impl Storage {
         .map_err(|e| format!("Count error: {e}"))
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_top_tools(&self, limit: i64, days: i64) -> Result<Vec<ToolCount>, String> {
         let conn = self.conn.lock();
         let mut stmt = conn
@@ -1590,6 +3525,7 @@ impl Storage {
         Ok(results)
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_observation_sparkline(&self) -> Result<Vec<i64>, String> {
         let conn = self.conn.lock();
         let mut stmt = conn
@@ -2029,6 +3965,7 @@ impl Storage {
         Ok(())
     }
 
+    #[cfg(feature = "gui")]
     pub fn delete_learned_rule(&self, name: &str) -> Result<(), String> {
         if !crate::learning::is_safe_rule_name(name) {
             return Err(format!(
@@ -2097,6 +4034,7 @@ impl Storage {
         Ok(())
     }
 
+    #[cfg(feature = "gui")]
     pub fn promote_learned_rule(&self, name: &str) -> Result<(), String> {
         if !crate::learning::is_safe_rule_name(name) {
             return Err(format!(
@@ -2150,7 +4088,81 @@ impl Storage {
         )
         .map_err(|e| format!("Update file_path error: {e}"))?;
 
-        Ok(())
+        Ok(())
+    }
+
+    /// Latest bucket utilization, all-time host token totals, observation counts
+    /// and learning run outcomes, as exported by the `/metrics` endpoint.
+    pub fn get_metrics_summary(&self) -> Result<MetricsSummary, String> {
+        let observation_count = self.get_observation_count()?;
+        let unanalyzed_observations = self.get_unanalyzed_observation_count()?;
+        let conn = self.conn.lock();
+
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT bucket_label, utilization, resets_at FROM usage_snapshots
+                 WHERE timestamp = (SELECT MAX(timestamp) FROM usage_snapshots)
+                 ORDER BY id ASC",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let buckets = stmt
+            .query_map([], |row| {
+                Ok(UsageBucket {
+                    label: row.get(0)?,
+                    utilization: row.get(1)?,
+                    resets_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row error: {e}"))?;
+
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT hostname, SUM(inp), SUM(outp), SUM(cc), SUM(cr)
+                 FROM (
+                     SELECT hostname, input_tokens as inp, output_tokens as outp,
+                            cache_creation_input_tokens as cc, cache_read_input_tokens as cr
+                     FROM token_snapshots
+                     UNION ALL
+                     SELECT hostname, total_input, total_output,
+                            total_cache_creation, total_cache_read
+                     FROM token_hourly
+                 )
+                 GROUP BY hostname
+                 ORDER BY hostname",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let host_tokens = stmt
+            .query_map([], |row| {
+                Ok(HostTokenTotals {
+                    hostname: row.get(0)?,
+                    input_tokens: row.get(1)?,
+                    output_tokens: row.get(2)?,
+                    cache_creation_input_tokens: row.get(3)?,
+                    cache_read_input_tokens: row.get(4)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row error: {e}"))?;
+
+        let mut stmt = conn
+            .prepare_cached("SELECT status, COUNT(*) FROM learning_runs GROUP BY status")
+            .map_err(|e| format!("Prepare error: {e}"))?;
+        let learning_runs = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row error: {e}"))?;
+
+        Ok(MetricsSummary {
+            buckets,
+            host_tokens,
+            observation_count,
+            unanalyzed_observations,
+            learning_runs,
+        })
     }
 
     pub fn get_learning_status(&self) -> Result<LearningStatus, String> {
@@ -2252,10 +4264,11 @@ impl Storage {
             .map_err(|e| format!("Transaction error: {e}"))?;
 
         tx.execute(
-            "INSERT INTO token_hourly (hour, hostname, total_input, total_output, total_cache_creation, total_cache_read, turn_count)
+            "INSERT INTO token_hourly (hour, hostname, model, total_input, total_output, total_cache_creation, total_cache_read, turn_count)
              SELECT
                  strftime('%Y-%m-%dT%H:00:00Z', timestamp) as hour,
                  hostname,
+                 COALESCE(model, '') as model_key,
                  SUM(input_tokens),
                  SUM(output_tokens),
                  SUM(cache_creation_input_tokens),
@@ -2263,8 +4276,8 @@ impl Storage {
                  COUNT(*)
              FROM token_snapshots
              WHERE timestamp < ?1
-             GROUP BY hour, hostname
-             ON CONFLICT(hour, hostname) DO UPDATE SET
+             GROUP BY hour, hostname, model_key
+             ON CONFLICT(hour, hostname, model) DO UPDATE SET
                  total_input = token_hourly.total_input + excluded.total_input,
                  total_output = token_hourly.total_output + excluded.total_output,
                  total_cache_creation = token_hourly.total_cache_creation + excluded.total_cache_creation,
@@ -2296,7 +4309,9 @@ impl Storage {
         Ok(())
     }
 
-    pub fn store_tool_actions(
+    /// Store a message's tool actions, replacing any stored for it before so
+    /// re-indexing a message doesn't duplicate them.
+    pub fn replace_tool_actions(
         &self,
         actions: &[crate::sessions::ToolAction],
         message_id: &str,
@@ -2307,13 +4322,19 @@ impl Storage {
             .transaction()
             .map_err(|e| format!("Begin tool_actions transaction: {e}"))?;
 
+        tx.execute(
+            "DELETE FROM tool_actions WHERE message_id = ?1",
+            rusqlite::params![message_id],
+        )
+        .map_err(|e| format!("Delete tool_actions for message: {e}"))?;
+
         {
             let mut stmt = tx
                 .prepare_cached(
                     "INSERT INTO tool_actions (message_id, session_id, tool_name, category, file_path, summary, full_input, full_output, timestamp)
                      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
                 )
-                .map_err(|e| format!("Prepare store_tool_actions: {e}"))?;
+                .map_err(|e| format!("Prepare replace_tool_actions: {e}"))?;
 
             for action in actions {
                 stmt.execute(rusqlite::params![
@@ -2322,9 +4343,9 @@ impl Storage {
                     action.tool_name,
                     action.category,
                     action.file_path,
-                    action.summary,
-                    action.full_input,
-                    action.full_output,
+                    redact(&action.summary),
+                    action.full_input.as_deref().map(redact),
+                    action.full_output.as_deref().map(redact),
                     action.timestamp,
                 ])
                 .map_err(|e| format!("Insert tool_action: {e}"))?;
@@ -2336,6 +4357,281 @@ impl Storage {
         Ok(())
     }
 
+    // --- Saved searches ---
+
+    pub fn list_saved_searches(&self) -> Result<Vec<SavedSearch>, String> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT s.id, s.name, s.query, s.filters, s.watch, s.created_at, s.updated_at,
+                        COUNT(m.message_id), MAX(m.matched_at), s.watch_since
+                 FROM saved_searches s
+                 LEFT JOIN saved_search_matches m ON m.search_id = s.id
+                 GROUP BY s.id
+                 ORDER BY s.name COLLATE NOCASE",
+            )
+            .map_err(|e| format!("Prepare error: {e}"))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let filters: String = row.get(3)?;
+                Ok(SavedSearch {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    query: row.get(2)?,
+                    // Written by save_search from a SearchFilters, so only
+                    // a hand-edited row fails to parse
+                    filters: serde_json::from_str(&filters).unwrap_or_default(),
+                    watch: row.get::<_, i64>(4)? != 0,
+                    created_at: row.get(5)?,
+                    updated_at: row.get(6)?,
+                    match_count: row.get(7)?,
+                    last_matched_at: row.get(8)?,
+                    watch_since: row.get(9)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| format!("Row error: {e}"))?);
+        }
+        Ok(results)
+    }
+
+    /// Creates a saved search, or replaces the one with `id`. Changing a
+    /// search forgets the matches it reported, since they may no longer hold.
+    /// Turning a watch on, or changing what it matches, moves `watch_since`
+    /// to now so it doesn't report history.
+    pub fn save_search(
+        &self,
+        id: Option<i64>,
+        payload: &SavedSearchPayload,
+    ) -> Result<i64, String> {
+        let filters =
+            serde_json::to_string(&payload.filters).map_err(|e| format!("Serialize error: {e}"))?;
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        let name_taken = |e: rusqlite::Error| match e {
+            rusqlite::Error::SqliteFailure(f, _)
+                if f.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                format!("A saved search named '{}' already exists", payload.name)
+            }
+            e => format!("Save search error: {e}"),
+        };
+        let id = match id {
+            Some(id) => {
+                // Matches recorded for a different query no longer mean anything
+                tx.execute(
+                    "DELETE FROM saved_search_matches WHERE search_id = ?1 AND EXISTS (
+                         SELECT 1 FROM saved_searches
+                         WHERE id = ?1 AND (query != ?2 OR filters != ?3)
+                     )",
+                    params![id, payload.query, filters],
+                )
+                .map_err(|e| format!("Delete saved search matches error: {e}"))?;
+                let updated = tx
+                    .execute(
+                        "UPDATE saved_searches SET name=?2, query=?3, filters=?4, watch=?5, updated_at=?6,
+                             watch_since = CASE
+                                 WHEN ?5 = 0 THEN NULL
+                                 WHEN watch = 0 OR query != ?3 OR filters != ?4 THEN ?6
+                                 ELSE watch_since
+                             END
+                         WHERE id=?1",
+                        params![id, payload.name, payload.query, filters, payload.watch as i64, now],
+                    )
+                    .map_err(name_taken)?;
+                if updated == 0 {
+                    return Err(format!("Saved search {id} not found"));
+                }
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO saved_searches (name, query, filters, watch, created_at, updated_at, watch_since)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?5, CASE WHEN ?4 != 0 THEN ?5 END)",
+                    params![payload.name, payload.query, filters, payload.watch as i64, now],
+                )
+                .map_err(name_taken)?;
+                tx.last_insert_rowid()
+            }
+        };
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(id)
+    }
+
+    pub fn delete_saved_search(&self, id: i64) -> Result<(), String> {
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        tx.execute(
+            "DELETE FROM saved_search_matches WHERE search_id = ?1",
+            params![id],
+        )
+        .map_err(|e| format!("Delete saved search matches error: {e}"))?;
+        let deleted = tx
+            .execute("DELETE FROM saved_searches WHERE id = ?1", params![id])
+            .map_err(|e| format!("Delete saved search error: {e}"))?;
+        if deleted == 0 {
+            return Err(format!("Saved search {id} not found"));
+        }
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(())
+    }
+
+    /// Records messages a watch matched and returns the ids it hadn't
+    /// reported before. Only the newest `keep` matches per search are kept.
+    pub fn record_saved_search_matches(
+        &self,
+        search_id: i64,
+        matches: &[(String, String)],
+        keep: usize,
+    ) -> Result<std::collections::HashSet<String>, String> {
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn.lock();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction error: {e}"))?;
+        let mut new = std::collections::HashSet::new();
+        {
+            let mut stmt = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO saved_search_matches (search_id, message_id, session_id, matched_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                )
+                .map_err(|e| format!("Prepare error: {e}"))?;
+            for (message_id, session_id) in matches {
+                let inserted = stmt
+                    .execute(params![search_id, message_id, session_id, now])
+                    .map_err(|e| format!("Insert saved search match error: {e}"))?;
+                if inserted > 0 {
+                    new.insert(message_id.clone());
+                }
+            }
+        }
+        if !new.is_empty() {
+            tx.execute(
+                "DELETE FROM saved_search_matches WHERE search_id = ?1 AND rowid NOT IN (
+                     SELECT rowid FROM saved_search_matches WHERE search_id = ?1
+                     ORDER BY matched_at DESC, rowid DESC LIMIT ?2
+                 )",
+                params![search_id, keep as i64],
+            )
+            .map_err(|e| format!("Prune saved search matches error: {e}"))?;
+        }
+        tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        Ok(new)
+    }
+
+    // --- Redaction ---
+
+    /// Re-applies redaction to observations, tool actions and queued relay
+    /// payloads stored before the current rules existed, then vacuums so the
+    /// old text doesn't linger in free pages. `messages` is left for the
+    /// session index to fill in.
+    pub fn redact_existing(&self) -> Result<RedactionSummary, String> {
+        let text = |s: &str| -> Option<String> {
+            match redact(s) {
+                std::borrow::Cow::Owned(r) if r != s => Some(r),
+                _ => None,
+            }
+        };
+        let json = |s: &str| -> Option<String> {
+            let mut value: serde_json::Value = serde_json::from_str(s).ok()?;
+            let before = value.clone();
+            crate::redact::redact_json(&mut value);
+            (value != before).then(|| value.to_string())
+        };
+
+        let summary = RedactionSummary {
+            observations: self.redact_rows(
+                "observations",
+                &["tool_input", "tool_output"],
+                &text,
+            )?,
+            tool_actions: self.redact_rows(
+                "tool_actions",
+                &["summary", "full_input", "full_output"],
+                &text,
+            )?,
+            outbox: self.redact_rows("relay_outbox", &["body"], &json)?,
+            messages: 0,
+        };
+
+        if summary.observations + summary.tool_actions + summary.outbox > 0 {
+            let conn = self.conn.lock();
+            conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+                .map_err(|e| format!("Vacuum error: {e}"))?;
+        }
+        Ok(summary)
+    }
+
+    /// Rewrites the given text columns of `table` wherever `redact` returns a
+    /// replacement, a batch of rows per transaction. Returns the number of
+    /// rows changed.
+    fn redact_rows(
+        &self,
+        table: &str,
+        columns: &[&str],
+        redact: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<usize, String> {
+        const BATCH_SIZE: i64 = 500;
+        let select = format!(
+            "SELECT id, {} FROM {table} WHERE id > ?1 ORDER BY id LIMIT ?2",
+            columns.join(", ")
+        );
+
+        let mut last_id = 0i64;
+        let mut changed = 0usize;
+        loop {
+            let mut conn = self.conn.lock();
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("Transaction error: {e}"))?;
+            let rows: Vec<(i64, Vec<Option<String>>)> = {
+                let mut stmt = tx
+                    .prepare(&select)
+                    .map_err(|e| format!("Prepare error: {e}"))?;
+                stmt.query_map(params![last_id, BATCH_SIZE], |row| {
+                    let values = (1..=columns.len())
+                        .map(|i| row.get(i))
+                        .collect::<rusqlite::Result<Vec<Option<String>>>>()?;
+                    Ok((row.get(0)?, values))
+                })
+                .map_err(|e| format!("Query {table} error: {e}"))?
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Row error: {e}"))?
+            };
+            let Some(batch_last) = rows.last().map(|(id, _)| *id) else {
+                break;
+            };
+            last_id = batch_last;
+
+            for (id, values) in &rows {
+                let mut row_changed = false;
+                for (column, value) in columns.iter().zip(values) {
+                    if let Some(redacted) = value.as_deref().and_then(redact) {
+                        tx.execute(
+                            &format!("UPDATE {table} SET {column} = ?1 WHERE id = ?2"),
+                            params![redacted, id],
+                        )
+                        .map_err(|e| format!("Update {table} error: {e}"))?;
+                        row_changed = true;
+                    }
+                }
+                changed += row_changed as usize;
+            }
+            tx.commit().map_err(|e| format!("Commit error: {e}"))?;
+        }
+        Ok(changed)
+    }
+
     // --- Memory optimizer storage methods ---
 
     /// Create a new optimization run record. Returns the run ID.
@@ -2939,7 +5235,7 @@ impl Storage {
                 }
             })
             .collect();
-        by_language.sort_by(|a, b| b.lines.cmp(&a.lines));
+        by_language.sort_by_key(|l| std::cmp::Reverse(l.lines));
 
         Ok(CodeStats {
             lines_added: total_added,
@@ -3039,6 +5335,7 @@ impl Storage {
         Ok(points)
     }
 
+    #[cfg(feature = "gui")]
     pub fn get_batch_session_code_stats(
         &self,
         session_ids: &[String],
@@ -3361,7 +5658,7 @@ fn merge_project_subdirs(mut rows: Vec<ProjectBreakdown>) -> Vec<ProjectBreakdow
 
     if parent_map.is_empty() {
         // No merging needed — sort by total_tokens desc and return
-        rows.sort_by(|a, b| b.total_tokens.cmp(&a.total_tokens));
+        rows.sort_by_key(|r| std::cmp::Reverse(r.total_tokens));
         rows.truncate(50);
         return rows;
     }
@@ -3382,8 +5679,10 @@ fn merge_project_subdirs(mut rows: Vec<ProjectBreakdown>) -> Vec<ProjectBreakdow
             turn_count: 0,
             session_count: 0,
             last_active: String::new(),
+            cost_usd: 0.0,
         });
         entry.total_tokens += row.total_tokens;
+        entry.cost_usd += row.cost_usd;
         entry.turn_count += row.turn_count;
         entry.session_count += row.session_count;
         if row.last_active > entry.last_active {
@@ -3392,11 +5691,115 @@ fn merge_project_subdirs(mut rows: Vec<ProjectBreakdown>) -> Vec<ProjectBreakdow
     }
 
     let mut results: Vec<ProjectBreakdown> = merged.into_values().collect();
-    results.sort_by(|a, b| b.total_tokens.cmp(&a.total_tokens));
+    results.sort_by_key(|r| std::cmp::Reverse(r.total_tokens));
     results.truncate(50);
     results
 }
 
+fn seed_default_pricing(conn: &Connection) -> rusqlite::Result<()> {
+    for &(pattern, input, output, cache_write, cache_read) in pricing::DEFAULT_PRICING {
+        conn.execute(
+            "INSERT OR IGNORE INTO model_pricing
+                (model_pattern, input_per_mtok, output_per_mtok, cache_write_per_mtok, cache_read_per_mtok)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![pattern, input, output, cache_write, cache_read],
+        )?;
+    }
+    Ok(())
+}
+
+fn load_pricing(conn: &Connection) -> Result<Vec<ModelPricing>, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT model_pattern, input_per_mtok, output_per_mtok, cache_write_per_mtok,
+                    cache_read_per_mtok, updated_at
+             FROM model_pricing ORDER BY model_pattern",
+        )
+        .map_err(|e| format!("Prepare error: {e}"))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ModelPricing {
+                model_pattern: row.get(0)?,
+                input_per_mtok: row.get(1)?,
+                output_per_mtok: row.get(2)?,
+                cache_write_per_mtok: row.get(3)?,
+                cache_read_per_mtok: row.get(4)?,
+                updated_at: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row.map_err(|e| format!("Row error: {e}"))?);
+    }
+    Ok(results)
+}
+
+/// Estimated cost of the `token_snapshots s` rows matching `where_sql`, summed
+/// per value of `key_expr`. Rows are priced per model before being summed.
+fn group_costs(
+    conn: &Connection,
+    pricing_table: &[ModelPricing],
+    key_expr: &str,
+    where_sql: &str,
+    params: &[&dyn rusqlite::types::ToSql],
+) -> Result<std::collections::HashMap<String, f64>, String> {
+    let sql = format!(
+        "SELECT {key_expr} as group_key, s.model,
+                SUM(s.input_tokens), SUM(s.output_tokens),
+                SUM(s.cache_creation_input_tokens), SUM(s.cache_read_input_tokens)
+         FROM token_snapshots s
+         WHERE {where_sql}
+         GROUP BY group_key, s.model"
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("Prepare error: {e}"))?;
+
+    let rows = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut costs = std::collections::HashMap::new();
+    for row in rows {
+        let (key, model, inp, out, cc, cr) = row.map_err(|e| format!("Row error: {e}"))?;
+        let rate = pricing::rate_for(pricing_table, model.as_deref());
+        *costs.entry(key).or_insert(0.0) += pricing::cost_usd(rate, inp, out, cc, cr);
+    }
+    Ok(costs)
+}
+
+/// SQL fragment matching model ids that contain the bound parameter, so a
+/// family name like "opus" matches every dated Opus release.
+/// Narrows `column` to values containing parameter `param_idx`, which must be
+/// bound through [`like_escape`].
+fn model_filter_clause(column: &str, param_idx: usize) -> String {
+    format!(" AND {column} LIKE '%' || ?{param_idx} || '%' ESCAPE '\\'")
+}
+
+/// Escapes `%` and `_` so a model filter matches them literally.
+fn like_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 fn calc_trend(conn: &Connection, bucket: &str) -> Result<String, String> {
     let now = Utc::now();
     let one_hour_ago = (now - TimeDelta::hours(1)).to_rfc3339();
@@ -3428,6 +5831,181 @@ fn calc_trend(conn: &Connection, bucket: &str) -> Result<String, String> {
     }
 }
 
+/// Length of a bucket's reset window and how far back to look when fitting
+/// its recent slope. Weekly buckets are fitted over the whole window.
+fn forecast_window(label: &str) -> (TimeDelta, TimeDelta) {
+    match label {
+        "5 hours" => (TimeDelta::hours(5), TimeDelta::hours(1)),
+        _ => (TimeDelta::days(7), TimeDelta::days(7)),
+    }
+}
+
+fn forecast_bucket(
+    conn: &Connection,
+    bucket: &UsageBucket,
+    now: DateTime<Utc>,
+) -> Result<UsageForecast, String> {
+    let resets_at = bucket
+        .resets_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|d| d.with_timezone(&Utc));
+
+    // Only fit points from the current window so a reset doesn't read as a
+    // steep negative slope.
+    let (window, lookback) = forecast_window(&bucket.label);
+    let mut from = now - lookback;
+    if let Some(reset) = resets_at {
+        from = from.max(reset - window);
+    }
+    let from_str = from.to_rfc3339();
+
+    let mut samples: Vec<(f64, f64)> = Vec::new();
+    let mut push_sample = |t: DateTime<Utc>, util: f64| {
+        let hours = (t - now).num_seconds() as f64 / 3600.0;
+        samples.push((hours, util));
+    };
+    let parse = |ts: &str| {
+        DateTime::parse_from_rfc3339(ts)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    };
+
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT timestamp, utilization FROM usage_snapshots
+             WHERE bucket_label = ?1 AND timestamp >= ?2
+             ORDER BY timestamp ASC",
+        )
+        .map_err(|e| format!("Prepare error: {e}"))?;
+    let rows = stmt
+        .query_map(params![bucket.label, from_str], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?;
+    let mut oldest_raw: Option<String> = None;
+    for row in rows {
+        let (ts, util) = row.map_err(|e| format!("Row error: {e}"))?;
+        if let Some(t) = parse(&ts) {
+            push_sample(t, util);
+        }
+        oldest_raw.get_or_insert(ts);
+    }
+
+    // Raw snapshots are folded into hourly averages after 30 days, so the
+    // part of the window before the oldest one comes from usage_hourly. Each
+    // average stands at the middle of its hour.
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT hour, avg_utilization FROM usage_hourly
+             WHERE bucket_label = ?1 AND hour >= ?2 AND hour < ?3
+             ORDER BY hour ASC",
+        )
+        .map_err(|e| format!("Prepare error: {e}"))?;
+    let before = oldest_raw.unwrap_or_else(|| now.to_rfc3339());
+    let rows = stmt
+        .query_map(params![bucket.label, from_str, before], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?;
+    for row in rows {
+        let (hour, util) = row.map_err(|e| format!("Row error: {e}"))?;
+        if let Some(t) = parse(&hour) {
+            push_sample(t + TimeDelta::minutes(30), util);
+        }
+    }
+
+    let rate_per_hour = linear_slope(&samples).unwrap_or(0.0);
+    let exhausted_at = if bucket.utilization >= 100.0 {
+        Some(now)
+    } else if rate_per_hour > 0.0 {
+        let secs = (100.0 - bucket.utilization) / rate_per_hour * 3600.0;
+        TimeDelta::try_seconds(secs as i64).and_then(|d| now.checked_add_signed(d))
+    } else {
+        None
+    };
+    let minutes_before_reset = match (exhausted_at, resets_at) {
+        (Some(out), Some(reset)) if out < reset => Some((reset - out).num_minutes()),
+        _ => None,
+    };
+
+    Ok(UsageForecast {
+        label: bucket.label.clone(),
+        current: bucket.utilization,
+        rate_per_hour,
+        resets_at: bucket.resets_at.clone(),
+        exhausted_at: exhausted_at.map(|t| t.to_rfc3339()),
+        minutes_before_reset,
+        sample_count: samples.len() as i64,
+        summary: forecast_summary(bucket.utilization, exhausted_at, resets_at, now),
+    })
+}
+
+/// Least-squares slope of (x, y) points, or None if x has no spread.
+fn linear_slope(points: &[(f64, f64)]) -> Option<f64> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let mut cov = 0.0;
+    let mut var = 0.0;
+    for (x, y) in points {
+        cov += (x - mean_x) * (y - mean_y);
+        var += (x - mean_x) * (x - mean_x);
+    }
+    if var < f64::EPSILON {
+        None
+    } else {
+        Some(cov / var)
+    }
+}
+
+fn forecast_summary(
+    current: f64,
+    exhausted_at: Option<DateTime<Utc>>,
+    resets_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> String {
+    let clock = |t: DateTime<Utc>| {
+        let local = t.with_timezone(&chrono::Local);
+        if t - now < TimeDelta::hours(24) {
+            local.format("%H:%M").to_string()
+        } else {
+            local.format("%a %H:%M").to_string()
+        }
+    };
+
+    if current >= 100.0 {
+        return match resets_at {
+            Some(reset) => format!("Limit reached, resets at {}", clock(reset)),
+            None => "Limit reached".to_string(),
+        };
+    }
+
+    match (exhausted_at, resets_at) {
+        (Some(out), Some(reset)) if out < reset => {
+            let mins = (reset - out).num_minutes();
+            let margin = if mins >= 120 {
+                format!("{} hours", mins / 60)
+            } else {
+                format!("{mins} minutes")
+            };
+            format!(
+                "At current pace you run out at {}, {margin} before the reset",
+                clock(out)
+            )
+        }
+        (Some(out), None) => format!("At current pace you run out at {}", clock(out)),
+        (_, Some(reset)) => format!(
+            "At current pace you stay under the limit until the reset at {}",
+            clock(reset)
+        ),
+        (None, None) => "Not on pace to run out".to_string(),
+    }
+}
+
 fn downsample_tokens(points: Vec<TokenDataPoint>, max: usize) -> Vec<TokenDataPoint> {
     if points.len() <= max {
         return points;
@@ -3470,3 +6048,288 @@ fn downsample(points: Vec<DataPoint>, max: usize) -> Vec<DataPoint> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh database in the temp directory, removed with its WAL files
+    /// on drop.
+    struct TempDb {
+        path: PathBuf,
+        storage: Storage,
+    }
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = temp_path(&format!("{name}.db"));
+            let storage = Storage::open(&path).unwrap();
+            Self { path, storage }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            remove_db(&self.path);
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("quill-{}-{name}", std::process::id()));
+        remove_db(&path);
+        path
+    }
+
+    fn remove_db(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[test]
+    fn schema_version_is_the_newest_migration() {
+        let db = TempDb::new("schema-version");
+        let applied: i32 = db
+            .storage
+            .conn
+            .lock()
+            .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(applied, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn backup_round_trips() {
+        let db = TempDb::new("backup-source");
+        db.storage.set_setting("backup.keep", "3").unwrap();
+        let dest = temp_path("backup-copy.db");
+
+        db.storage.backup_to(&dest).unwrap();
+        assert_eq!(Storage::check_backup(&dest), Ok(SCHEMA_VERSION));
+        let restored = Storage::open(&dest).unwrap();
+        assert_eq!(
+            restored.get_setting("backup.keep").unwrap().as_deref(),
+            Some("3")
+        );
+
+        drop(restored);
+        remove_db(&dest);
+    }
+
+    fn at(now: DateTime<Utc>, hours: i64) -> DateTime<Utc> {
+        now + TimeDelta::hours(hours)
+    }
+
+    #[test]
+    fn linear_slope_of_flat_usage_is_zero() {
+        let points = [(-3.0, 40.0), (-2.0, 40.0), (-1.0, 40.0), (0.0, 40.0)];
+        assert_eq!(linear_slope(&points), Some(0.0));
+    }
+
+    #[test]
+    fn linear_slope_of_rising_usage() {
+        let points = [(-3.0, 10.0), (-2.0, 15.0), (-1.0, 20.0), (0.0, 25.0)];
+        let slope = linear_slope(&points).unwrap();
+        assert!((slope - 5.0).abs() < 1e-9, "slope {slope}");
+    }
+
+    #[test]
+    fn linear_slope_needs_spread_in_x() {
+        assert_eq!(linear_slope(&[]), None);
+        assert_eq!(linear_slope(&[(0.0, 10.0)]), None);
+        assert_eq!(linear_slope(&[(1.0, 10.0), (1.0, 30.0)]), None);
+    }
+
+    #[test]
+    fn forecast_summary_flat_usage_stays_under_until_reset() {
+        let now = Utc::now();
+        let summary = forecast_summary(40.0, None, Some(at(now, 3)), now);
+        assert!(
+            summary.starts_with("At current pace you stay under the limit until the reset"),
+            "{summary}"
+        );
+        assert_eq!(
+            forecast_summary(40.0, None, None, now),
+            "Not on pace to run out"
+        );
+    }
+
+    #[test]
+    fn forecast_summary_rising_usage_runs_out_before_reset() {
+        let now = Utc::now();
+        let summary = forecast_summary(80.0, Some(at(now, 2)), Some(at(now, 5)), now);
+        assert!(
+            summary.starts_with("At current pace you run out at "),
+            "{summary}"
+        );
+        assert!(summary.ends_with(", 3 hours before the reset"), "{summary}");
+
+        let summary = forecast_summary(95.0, Some(at(now, 1)), Some(at(now, 2)), now);
+        assert!(
+            summary.ends_with(", 60 minutes before the reset"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn forecast_summary_crossing_after_reset_stays_under() {
+        let now = Utc::now();
+        let summary = forecast_summary(50.0, Some(at(now, 10)), Some(at(now, 5)), now);
+        assert!(
+            summary.starts_with("At current pace you stay under the limit until the reset"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn forecast_summary_at_limit() {
+        let now = Utc::now();
+        let summary = forecast_summary(100.0, Some(now), Some(at(now, 1)), now);
+        assert!(
+            summary.starts_with("Limit reached, resets at "),
+            "{summary}"
+        );
+        assert_eq!(
+            forecast_summary(100.0, Some(now), None, now),
+            "Limit reached"
+        );
+    }
+
+    #[test]
+    fn weekly_forecast_fits_hourly_history_and_raw_snapshots() {
+        let db = TempDb::new("forecast");
+        let now = Utc::now();
+        let reset = (now + TimeDelta::days(1)).to_rfc3339();
+        // Rising half a point an hour, reaching 40% now
+        let util_at = |t: DateTime<Utc>| 40.0 + 0.5 * (t - now).num_seconds() as f64 / 3600.0;
+        let hour_of = |t: DateTime<Utc>| t.format("%Y-%m-%dT%H:00:00Z").to_string();
+        {
+            let conn = db.storage.conn.lock();
+            let mut hourly = conn
+                .prepare(
+                    "INSERT INTO usage_hourly (hour, bucket_label, avg_utilization,
+                         max_utilization, min_utilization, sample_count)
+                     VALUES (?1, '7 days', ?2, ?2, ?2, 1)",
+                )
+                .unwrap();
+            // Aggregated history from before the raw snapshots
+            for h in 49..=143 {
+                let start = parse_hour(&hour_of(now - TimeDelta::hours(h)));
+                let mid = start + TimeDelta::minutes(30);
+                hourly
+                    .execute(params![hour_of(start), util_at(mid)])
+                    .unwrap();
+            }
+            // Before the window, and overlapping raw snapshots: both ignored
+            hourly
+                .execute(params![hour_of(now - TimeDelta::days(8)), 0.0])
+                .unwrap();
+            hourly
+                .execute(params![hour_of(now - TimeDelta::hours(10)), 0.0])
+                .unwrap();
+
+            let mut raw = conn
+                .prepare(
+                    "INSERT INTO usage_snapshots (timestamp, bucket_label, utilization, resets_at)
+                     VALUES (?1, '7 days', ?2, ?3)",
+                )
+                .unwrap();
+            for h in (0..=47).rev() {
+                let t = now - TimeDelta::hours(h);
+                raw.execute(params![t.to_rfc3339(), util_at(t), reset])
+                    .unwrap();
+            }
+        }
+
+        let forecasts = db.storage.get_usage_forecasts(Some("7 days")).unwrap();
+        assert_eq!(forecasts.len(), 1);
+        let f = &forecasts[0];
+        assert_eq!(f.sample_count, 95 + 48);
+        assert!(
+            (f.rate_per_hour - 0.5).abs() < 1e-3,
+            "rate {}",
+            f.rate_per_hour
+        );
+        assert!((f.current - 40.0).abs() < 1e-9);
+        // 60 points left at 0.5/h is 120h, after the reset a day from now
+        assert!(f.exhausted_at.is_some());
+        assert_eq!(f.minutes_before_reset, None);
+        assert!(
+            f.summary
+                .starts_with("At current pace you stay under the limit"),
+            "{}",
+            f.summary
+        );
+    }
+
+    fn parse_hour(hour: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(hour)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn model_filter_matches_wildcards_literally() {
+        let db = TempDb::new("model-filter");
+        for (host, model) in [
+            ("a", "claude-opus-4"),
+            ("b", "claude-opus_4"),
+            ("c", "100%"),
+        ] {
+            db.storage
+                .store_token_snapshot(&TokenReportPayload {
+                    session_id: format!("s-{host}"),
+                    hostname: host.to_string(),
+                    input_tokens: 10,
+                    output_tokens: 10,
+                    cache_creation_input_tokens: 0,
+                    cache_read_input_tokens: 0,
+                    cwd: Some(format!("/work/{host}")),
+                    model: Some(model.to_string()),
+                    idempotency_key: None,
+                })
+                .unwrap();
+        }
+        let hosts = |model| -> Vec<String> {
+            let mut hosts: Vec<String> = db
+                .storage
+                .get_host_breakdown(1, Some(model))
+                .unwrap()
+                .into_iter()
+                .map(|h| h.hostname)
+                .collect();
+            hosts.sort();
+            hosts
+        };
+
+        assert_eq!(hosts("opus_4"), ["b"]);
+        assert_eq!(hosts("%"), ["c"]);
+        assert_eq!(hosts("opus"), ["a", "b"]);
+        let projects = db.storage.get_project_breakdown(1, Some("opus_")).unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].hostname, "b");
+    }
+
+    #[test]
+    fn budget_crossing_time_is_rfc3339() {
+        let db = TempDb::new("budget-crossing");
+        let start = "2099-01-01T00:00:00+00:00";
+        assert_eq!(db.storage.budget_crossed_at(1, start, 100), Ok(None));
+
+        assert_eq!(db.storage.record_budget_crossing(1, start, 100), Ok(true));
+        let crossed = db
+            .storage
+            .budget_crossed_at(1, start, 100)
+            .unwrap()
+            .unwrap();
+        let crossed = DateTime::parse_from_rfc3339(&crossed).unwrap();
+        assert!(
+            (Utc::now() - crossed.with_timezone(&Utc))
+                .num_seconds()
+                .abs()
+                < 60
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{
+    CryptoProvider, verify_tls12_signature, verify_tls13_signature,
+};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{DigitallySignedStruct, SignatureScheme};
+
+// ── TLS for the token server ──
+//
+// The server either loads a user-supplied certificate and key or generates a
+// self-signed pair on first run. Remote hooks don't trust a CA; they pin the
+// certificate's SHA-256 fingerprint, so the self-signed certificate only has
+// to stay the same across restarts.
+
+pub struct TlsIdentity {
+    pub config: Arc<rustls::ServerConfig>,
+    /// SHA-256 of the leaf certificate as colon-separated uppercase hex, the
+    /// format printed by `openssl x509 -fingerprint -sha256`.
+    pub fingerprint: String,
+}
+
+fn self_signed_dir() -> Result<PathBuf, String> {
+    let data_dir = dirs::data_local_dir()
+        .ok_or_else(|| "cannot determine local data directory".to_string())?;
+    Ok(data_dir.join("com.quilltoolkit.app").join("tls"))
+}
+
+/// Paths of the generated certificate and key.
+pub fn self_signed_paths() -> Result<(PathBuf, PathBuf), String> {
+    let dir = self_signed_dir()?;
+    Ok((dir.join("cert.pem"), dir.join("key.pem")))
+}
+
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Loads a PEM certificate chain and private key.
+pub fn load_identity(cert_path: &Path, key_path: &Path) -> Result<TlsIdentity, String> {
+    let certs: Vec<CertificateDer<'static>> = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|e| format!("failed to read {}: {e}", cert_path.display()))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid certificate in {}: {e}", cert_path.display()))?;
+    let leaf = certs
+        .first()
+        .ok_or_else(|| format!("no certificate found in {}", cert_path.display()))?;
+    let fingerprint = fingerprint(leaf);
+
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("invalid private key in {}: {e}", key_path.display()))?;
+
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("TLS config error: {e}"))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("certificate and key do not match: {e}"))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsIdentity {
+        config: Arc::new(config),
+        fingerprint,
+    })
+}
+
+/// Loads the generated self-signed certificate, creating it on first use.
+/// `names` are extra DNS names or IP addresses to list in the certificate.
+pub fn load_or_create_self_signed(names: &[String]) -> Result<TlsIdentity, String> {
+    let (cert_path, key_path) = self_signed_paths()?;
+    if cert_path.exists() && key_path.exists() {
+        return load_identity(&cert_path, &key_path);
+    }
+
+    let mut sans = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    for name in names {
+        if !sans.contains(name) {
+            sans.push(name.clone());
+        }
+    }
+    let generated = rcgen::generate_simple_self_signed(sans)
+        .map_err(|e| format!("failed to generate certificate: {e}"))?;
+
+    if let Some(parent) = cert_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("failed to create TLS directory: {e}"))?;
+    }
+    fs::write(&cert_path, generated.cert.pem())
+        .map_err(|e| format!("failed to write certificate: {e}"))?;
+    let mut key_file = crate::auth::create_secret_file(&key_path)
+        .map_err(|e| format!("failed to create key file: {e}"))?;
+    key_file
+        .write_all(generated.key_pair.serialize_pem().as_bytes())
+        .map_err(|e| format!("failed to write key: {e}"))?;
+
+    log::info!(
+        "Generated self-signed TLS certificate at {}",
+        cert_path.display()
+    );
+    load_identity(&cert_path, &key_path)
+}
+
+/// Accepts exactly one server certificate, identified by its SHA-256
+/// fingerprint, the same way remote hooks pin the token server. Handshake
+/// signatures are still verified against that certificate.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if fingerprint(end_entity) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "server certificate does not match the pinned fingerprint".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Client TLS config that trusts only the certificate with this fingerprint.
+/// Colons and case in `pinned` are ignored.
+pub fn pinned_client_config(pinned: &str) -> Result<rustls::ClientConfig, String> {
+    let hex: String = pinned
+        .chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid SHA-256 fingerprint '{pinned}'"));
+    }
+    let fingerprint = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).into_owned())
+        .collect::<Vec<_>>()
+        .join(":");
+
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let verifier = PinnedCertVerifier {
+        fingerprint,
+        provider: provider.clone(),
+    };
+    Ok(rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("TLS config error: {e}"))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
@@ -0,0 +1,53 @@
+use crate::models::TokenImportResult;
+use crate::sessions::{claude_projects_dir, extract_session_from_jsonl, list_session_files};
+use crate::storage::Storage;
+
+/// Settings key set once the first automatic backfill has run.
+pub const IMPORT_DONE_KEY: &str = "token_history_imported";
+
+/// Backfill token_snapshots from the `usage` objects in ~/.claude/projects
+/// transcripts. Safe to re-run: message ids are logged on import, and turns at
+/// or after the host's cutoff are left to the Stop hook.
+pub fn import_token_history(storage: &Storage) -> Result<TokenImportResult, String> {
+    let hostname = crate::claude_setup::reporting_hostname();
+    let cutoff = storage.token_import_cutoff(&hostname)?;
+
+    let mut result = TokenImportResult {
+        hostname,
+        cutoff,
+        files_scanned: 0,
+        turns_imported: 0,
+        turns_skipped: 0,
+    };
+
+    let projects_dir = claude_projects_dir()?;
+    if !projects_dir.exists() {
+        return Ok(result);
+    }
+
+    for session_file in list_session_files(&projects_dir)? {
+        let session = extract_session_from_jsonl(&session_file.path);
+        result.files_scanned += 1;
+        if session.usage.is_empty() {
+            continue;
+        }
+        let (imported, skipped) =
+            storage.import_token_usage(&result.hostname, &result.cutoff, &session.usage)?;
+        result.turns_imported += imported;
+        result.turns_skipped += skipped;
+    }
+
+    // Imported history is mostly older than the snapshot retention window
+    if result.turns_imported > 0 {
+        storage.aggregate_and_cleanup_tokens()?;
+    }
+    storage.set_setting(IMPORT_DONE_KEY, "1")?;
+
+    log::info!(
+        "Token history import: {} turns imported, {} skipped across {} files",
+        result.turns_imported,
+        result.turns_skipped,
+        result.files_scanned
+    );
+    Ok(result)
+}
@@ -0,0 +1,35 @@
+use crate::storage::Storage;
+
+// ── Trash ──
+//
+// Deleting a host, project or session from the analytics view moves its
+// token rows into trash tables as one batch instead of dropping them, so a
+// misclick can be undone. Batches can be restored or purged by hand, and
+// hourly maintenance purges them after `trash.retention_days`.
+
+pub const RETENTION_SETTING: &str = "trash.retention_days";
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+const MIN_RETENTION_DAYS: i64 = 1;
+
+pub fn retention_days(storage: &Storage) -> i64 {
+    storage
+        .get_setting(RETENTION_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+        .max(MIN_RETENTION_DAYS)
+}
+
+pub fn set_retention_days(storage: &Storage, days: i64) -> Result<(), String> {
+    if days < MIN_RETENTION_DAYS {
+        return Err(format!(
+            "trash retention must be at least {MIN_RETENTION_DAYS} day"
+        ));
+    }
+    storage.set_setting(RETENTION_SETTING, &days.to_string())
+}
+
+pub fn purge_expired(storage: &Storage) -> Result<usize, String> {
+    storage.purge_expired_trash(retention_days(storage))
+}
@@ -9,6 +9,7 @@ import type {
   HostBreakdown,
   ProjectBreakdown,
   SessionBreakdown,
+  TrashBatch,
 } from "../../types";
 
 function formatRelativeTime(isoString: string): string {
@@ -185,9 +186,20 @@ function BreakdownPanel({ days, selection, onSelect }: BreakdownPanelProps) {
     const args = argsMap[selection.type];
 
     try {
-      await invoke(command, args);
+      const batch = await invoke<TrashBatch | null>(command, args);
       onSelect(null);
       refresh();
+      if (batch) {
+        const rows = batch.snapshot_count + batch.hourly_count;
+        toast("info", `Moved ${rows} rows to trash`, {
+          label: "Undo",
+          onClick: () => {
+            invoke("restore_trash_batch", { batchId: batch.id })
+              .then(() => refresh())
+              .catch((err) => toast("error", `Undo failed: ${err}`));
+          },
+        });
+      }
     } catch (err) {
       toast("error", `Failed to delete ${selection.type} data: ${err}`);
     } finally {
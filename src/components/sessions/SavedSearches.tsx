@@ -0,0 +1,163 @@
+import { useState, useEffect, useCallback } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import type { SavedSearch, SavedSearchMatch, SearchFilters } from "../../types";
+
+interface SavedSearchesProps {
+	query: string;
+	filters: SearchFilters;
+	onRun: (search: SavedSearch) => void;
+}
+
+function SavedSearches({ query, filters, onRun }: SavedSearchesProps) {
+	const [searches, setSearches] = useState<SavedSearch[]>([]);
+	// Matches reported by watches since the search was last run here
+	const [unseen, setUnseen] = useState<Record<number, number>>({});
+	const [naming, setNaming] = useState(false);
+	const [name, setName] = useState("");
+	const [error, setError] = useState<string | null>(null);
+
+	const fetchSearches = useCallback(() => {
+		invoke<SavedSearch[]>("list_saved_searches")
+			.then(setSearches)
+			.catch(() => {});
+	}, []);
+
+	useEffect(() => {
+		fetchSearches();
+	}, [fetchSearches]);
+
+	useEffect(() => {
+		const unlistenPromise = listen<SavedSearchMatch>(
+			"saved-search-matched",
+			(event) => {
+				const { search_id, total } = event.payload;
+				setUnseen((prev) => ({
+					...prev,
+					[search_id]: (prev[search_id] ?? 0) + total,
+				}));
+				fetchSearches();
+			},
+		);
+		return () => {
+			unlistenPromise.then((fn) => fn());
+		};
+	}, [fetchSearches]);
+
+	const handleSave = async () => {
+		try {
+			await invoke<number>("save_saved_search", {
+				search: { name: name.trim(), query, filters, watch: false },
+			});
+			setNaming(false);
+			setName("");
+			setError(null);
+			fetchSearches();
+		} catch (e) {
+			setError(String(e));
+		}
+	};
+
+	const handleToggleWatch = async (search: SavedSearch) => {
+		try {
+			await invoke<number>("save_saved_search", {
+				id: search.id,
+				search: {
+					name: search.name,
+					query: search.query,
+					filters: search.filters,
+					watch: !search.watch,
+				},
+			});
+			setError(null);
+			fetchSearches();
+		} catch (e) {
+			setError(String(e));
+		}
+	};
+
+	const handleDelete = async (search: SavedSearch) => {
+		try {
+			await invoke("delete_saved_search", { id: search.id });
+			setError(null);
+			fetchSearches();
+		} catch (e) {
+			setError(String(e));
+		}
+	};
+
+	const handleRun = (search: SavedSearch) => {
+		setUnseen((prev) => ({ ...prev, [search.id]: 0 }));
+		onRun(search);
+	};
+
+	return (
+		<div className="sessions-saved">
+			<div className="sessions-saved-row">
+				{searches.map((s) => (
+					<div
+						key={s.id}
+						className={`sessions-saved-chip${s.watch ? " watching" : ""}`}
+						title={s.query}
+					>
+						<button
+							className="sessions-saved-name"
+							onClick={() => handleRun(s)}
+						>
+							{s.name}
+							{(unseen[s.id] ?? 0) > 0 && (
+								<span className="sessions-saved-badge">{unseen[s.id]}</span>
+							)}
+						</button>
+						<button
+							className="sessions-saved-action sessions-saved-watch"
+							onClick={() => handleToggleWatch(s)}
+							title={
+								s.watch
+									? "Watching for new matches. Click to stop"
+									: "Notify when new messages match"
+							}
+						>
+							{s.watch ? "◉" : "○"}
+						</button>
+						<button
+							className="sessions-saved-action"
+							onClick={() => handleDelete(s)}
+							aria-label={`Delete ${s.name}`}
+						>
+							&times;
+						</button>
+					</div>
+				))}
+				{naming ? (
+					<input
+						className="sessions-saved-input"
+						autoFocus
+						placeholder="Name"
+						value={name}
+						onChange={(e) => setName(e.target.value)}
+						onKeyDown={(e) => {
+							if (e.key === "Enter" && name.trim()) handleSave();
+							if (e.key === "Escape") {
+								setNaming(false);
+								setName("");
+							}
+						}}
+					/>
+				) : (
+					<button
+						className="sessions-saved-add"
+						onClick={() => setNaming(true)}
+						disabled={!query.trim()}
+						title="Save the current query and filters"
+					>
+						Save search
+					</button>
+				)}
+			</div>
+			{error && <div className="sessions-query-error">{error}</div>}
+		</div>
+	);
+}
+
+export default SavedSearches;
@@ -2,9 +2,10 @@ import { useRef, useEffect, useCallback } from "react";
 
 interface SearchBarProps {
   onSearch: (value: string) => void;
+  defaultValue?: string;
 }
 
-function SearchBar({ onSearch }: SearchBarProps) {
+function SearchBar({ onSearch, defaultValue }: SearchBarProps) {
   const timerRef = useRef<ReturnType<typeof setTimeout> | null>(null);
 
   const handleChange = useCallback(
@@ -33,7 +34,10 @@ function SearchBar({ onSearch }: SearchBarProps) {
       className="sessions-search-input"
       type="text"
       autoFocus
-      placeholder="Search sessions..."
+      placeholder='Search sessions... e.g. tool:Edit file:src/ after:-7d "migration"'
+      title='Fields: project: host: role: branch: session: tool: file: command: after: before: (dates like 2026-10-01, -7d, today). Use "phrases", -exclude, OR and ( ).'
+      spellCheck={false}
+      defaultValue={defaultValue}
       onChange={handleChange}
     />
   );
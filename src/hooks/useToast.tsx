@@ -10,14 +10,20 @@ import type { ReactNode } from "react";
 
 export type ToastLevel = "error" | "warning" | "info";
 
+export interface ToastAction {
+  label: string;
+  onClick: () => void;
+}
+
 interface Toast {
   id: number;
   level: ToastLevel;
   message: string;
+  action?: ToastAction;
 }
 
 interface ToastContextValue {
-  toast: (level: ToastLevel, message: string) => void;
+  toast: (level: ToastLevel, message: string, action?: ToastAction) => void;
 }
 
 const ToastContext = createContext<ToastContextValue>({
@@ -30,10 +36,13 @@ export function ToastProvider({ children }: { children: ReactNode }) {
   const [toasts, setToasts] = useState<Toast[]>([]);
   const nextId = useRef(0);
 
-  const addToast = useCallback((level: ToastLevel, message: string) => {
-    const id = nextId.current++;
-    setToasts((prev) => [...prev, { id, level, message }]);
-  }, []);
+  const addToast = useCallback(
+    (level: ToastLevel, message: string, action?: ToastAction) => {
+      const id = nextId.current++;
+      setToasts((prev) => [...prev, { id, level, message, action }]);
+    },
+    [],
+  );
 
   const removeToast = useCallback((id: number) => {
     setToasts((prev) => prev.filter((t) => t.id !== id));
@@ -80,6 +89,18 @@ function ToastItem({
     >
       <span className="toast-icon">{icon}</span>
       <span className="toast-message">{toast.message}</span>
+      {toast.action && (
+        <button
+          className="toast-action"
+          onClick={(e) => {
+            e.stopPropagation();
+            toast.action?.onClick();
+            setExiting(true);
+          }}
+        >
+          {toast.action.label}
+        </button>
+      )}
     </div>
   );
 }
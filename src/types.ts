@@ -34,6 +34,7 @@ export interface TokenStats {
   turn_count: number;
   avg_input_per_turn: number;
   avg_output_per_turn: number;
+  cost_usd: number;
 }
 
 export interface BucketStats {
@@ -47,11 +48,174 @@ export interface BucketStats {
   sample_count: number;
 }
 
+export interface UsageForecast {
+  label: string;
+  current: number;
+  rate_per_hour: number;
+  resets_at: string | null;
+  exhausted_at: string | null;
+  minutes_before_reset: number | null;
+  sample_count: number;
+  summary: string;
+}
+
+export interface AlertRule {
+  id: number;
+  name: string;
+  bucket_label: string;
+  threshold: number;
+  min_hours_to_reset: number | null;
+  max_hours_to_reset: number | null;
+  notify_desktop: boolean;
+  webhook_url: string | null;
+  enabled: boolean;
+  created_at: string;
+}
+
+export interface AlertRulePayload {
+  name: string;
+  bucket_label: string;
+  threshold: number;
+  min_hours_to_reset: number | null;
+  max_hours_to_reset: number | null;
+  notify_desktop: boolean;
+  webhook_url: string | null;
+  enabled: boolean;
+}
+
+export interface AlertFiring {
+  id: number;
+  rule_id: number;
+  rule_name: string;
+  bucket_label: string;
+  utilization: number;
+  threshold: number;
+  resets_at: string | null;
+  window_key: string;
+  message: string;
+  fired_at: string;
+}
+
+export interface ModelBreakdown {
+  model: string | null;
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+  total_tokens: number;
+  turn_count: number;
+  session_count: number;
+  last_active: string;
+  cost_usd: number;
+}
+
+export interface TokenImportResult {
+  hostname: string;
+  cutoff: string;
+  files_scanned: number;
+  turns_imported: number;
+  turns_skipped: number;
+}
+
+export interface ModelPricing {
+  model_pattern: string;
+  input_per_mtok: number;
+  output_per_mtok: number;
+  cache_write_per_mtok: number;
+  cache_read_per_mtok: number;
+  updated_at: string;
+}
+
+export interface BudgetPayload {
+  name: string;
+  scope_type: "project" | "host";
+  scope_value: string;
+  period: "daily" | "weekly" | "monthly";
+  metric: "tokens" | "cost";
+  limit_value: number;
+  enabled: boolean;
+}
+
+export interface Budget {
+  id: number;
+  name: string;
+  scope_type: "project" | "host";
+  scope_value: string;
+  period: "daily" | "weekly" | "monthly";
+  metric: "tokens" | "cost";
+  limit_value: number;
+  enabled: boolean;
+  created_at: string;
+}
+
+export interface BudgetStatus {
+  budget: Budget;
+  period_start: string;
+  period_end: string;
+  consumed: number;
+  percent: number;
+  projected: number;
+  projected_overrun: number;
+  exhausted_at: string | null;
+}
+
+export interface BudgetThresholdEvent {
+  budget_id: number;
+  name: string;
+  threshold: number;
+  percent: number;
+  consumed: number;
+  limit_value: number;
+  metric: "tokens" | "cost";
+  period_start: string;
+}
+
+export type ApiTokenScope =
+  | "*"
+  | "tokens:write"
+  | "usage:read"
+  | "learning:read"
+  | "learning:write"
+  | "sessions:write"
+  | "search:read"
+  | "events:read"
+  | "metrics:read"
+  | "relay:forward";
+
+export interface ApiToken {
+  id: number;
+  name: string;
+  hostname: string | null;
+  scopes: ApiTokenScope[];
+  token_prefix: string;
+  created_at: string;
+  last_used_at: string | null;
+  revoked_at: string | null;
+}
+
+export interface IssuedApiToken {
+  token: string;
+  info: ApiToken;
+}
+
+export interface ModelCost {
+  model: string | null;
+  pricing_pattern: string | null;
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+  total_tokens: number;
+  turn_count: number;
+  cost_usd: number;
+}
+
 export interface HostBreakdown {
   hostname: string;
   total_tokens: number;
   turn_count: number;
   last_active: string;
+  cost_usd: number;
 }
 
 export interface SessionBreakdown {
@@ -62,6 +226,7 @@ export interface SessionBreakdown {
   first_seen: string;
   last_active: string;
   project: string | null;
+  cost_usd: number;
 }
 
 export interface ProjectBreakdown {
@@ -71,6 +236,16 @@ export interface ProjectBreakdown {
   turn_count: number;
   session_count: number;
   last_active: string;
+  cost_usd: number;
+}
+
+export interface TrashBatch {
+  id: string;
+  kind: "host" | "project" | "session";
+  target: string;
+  deleted_at: string;
+  snapshot_count: number;
+  hourly_count: number;
 }
 
 export type TimeMode = "marker" | "dual" | "background";
@@ -197,6 +372,7 @@ export interface SearchFilters {
   date_from?: string;
   date_to?: string;
   git_branch?: string;
+  session_id?: string;
 }
 
 export interface SearchHit {
@@ -223,6 +399,26 @@ export interface SearchResults {
   query_time_ms: number;
 }
 
+export interface SavedSearch {
+  id: number;
+  name: string;
+  query: string;
+  filters: SearchFilters;
+  watch: boolean;
+  created_at: string;
+  updated_at: string;
+  match_count: number;
+  last_matched_at: string | null;
+  watch_since: string | null;
+}
+
+export interface SavedSearchMatch {
+  search_id: number;
+  name: string;
+  total: number;
+  hits: SearchHit[];
+}
+
 export interface FacetCount {
   name: string;
   count: number;
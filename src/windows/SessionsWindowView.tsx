@@ -5,8 +5,10 @@ import SearchBar from "../components/sessions/SearchBar";
 import FilterBar from "../components/sessions/FilterBar";
 import ResultCard from "../components/sessions/ResultCard";
 import DetailPanel from "../components/sessions/DetailPanel";
+import SavedSearches from "../components/sessions/SavedSearches";
 import { useSessionCodeStats } from "../hooks/useSessionCodeStats";
 import type {
+	SavedSearch,
 	SearchFilters,
 	SearchResults,
 	SearchHit,
@@ -38,16 +40,20 @@ function SessionsWindowView() {
 	const [loading, setLoading] = useState(false);
 	const [page, setPage] = useState(0);
 	const [query, setQuery] = useState("");
+	const [queryError, setQueryError] = useState<string | null>(null);
+	// Bumped to remount the search bar with a saved search's query
+	const [searchBarKey, setSearchBarKey] = useState(0);
 
 	useEffect(() => {
 		invoke<SearchFacets>("get_search_facets").then(setFacets).catch(() => {});
 	}, []);
 
 	const handleSearch = useCallback(
-		async (value: string) => {
+		async (value: string, searchFilters: SearchFilters = filters) => {
 			setQuery(value);
 			setPage(0);
 			setSelectedHit(null);
+			setQueryError(null);
 			if (!value.trim()) {
 				setResults([]);
 				setTotalHits(0);
@@ -58,7 +64,7 @@ function SessionsWindowView() {
 			try {
 				const res = await invoke<SearchResults>("search_sessions", {
 					query: value,
-					filters,
+					filters: searchFilters,
 					sortBy,
 					page: 0,
 					pageSize: PAGE_SIZE,
@@ -66,9 +72,10 @@ function SessionsWindowView() {
 				setResults(res.hits);
 				setTotalHits(res.total_hits);
 				setQueryTimeMs(res.query_time_ms);
-			} catch {
+			} catch (e) {
 				setResults([]);
 				setTotalHits(0);
+				setQueryError(String(e));
 			} finally {
 				setLoading(false);
 			}
@@ -76,6 +83,15 @@ function SessionsWindowView() {
 		[filters, sortBy],
 	);
 
+	const handleRunSaved = useCallback(
+		(search: SavedSearch) => {
+			setFilters(search.filters);
+			setSearchBarKey((k) => k + 1);
+			handleSearch(search.query, search.filters);
+		},
+		[handleSearch],
+	);
+
 	const handleLoadMore = useCallback(async () => {
 		const nextPage = page + 1;
 		setLoading(true);
@@ -133,10 +149,12 @@ function SessionsWindowView() {
 						setResults(res.hits);
 						setTotalHits(res.total_hits);
 						setQueryTimeMs(res.query_time_ms);
+						setQueryError(null);
 					})
-					.catch(() => {
+					.catch((e) => {
 						setResults([]);
 						setTotalHits(0);
+						setQueryError(String(e));
 					})
 					.finally(() => setLoading(false));
 			}
@@ -162,10 +180,12 @@ function SessionsWindowView() {
 						setResults(res.hits);
 						setTotalHits(res.total_hits);
 						setQueryTimeMs(res.query_time_ms);
+						setQueryError(null);
 					})
-					.catch(() => {
+					.catch((e) => {
 						setResults([]);
 						setTotalHits(0);
+						setQueryError(String(e));
 					})
 					.finally(() => setLoading(false));
 			}
@@ -194,7 +214,16 @@ function SessionsWindowView() {
 			<div className="sessions-split">
 				<div className="sessions-list-panel">
 					<div className="sessions-list-scroll">
-						<SearchBar onSearch={handleSearch} />
+						<SearchBar
+							key={searchBarKey}
+							onSearch={(value) => handleSearch(value)}
+							defaultValue={query}
+						/>
+						<SavedSearches
+							query={query}
+							filters={filters}
+							onRun={handleRunSaved}
+						/>
 						<FilterBar
 							facets={facets}
 							filters={filters}
@@ -202,7 +231,10 @@ function SessionsWindowView() {
 							sortBy={sortBy}
 							onSortChange={handleSortChange}
 						/>
-						{query.trim() && !loading && (
+						{queryError && (
+							<div className="sessions-query-error">{queryError}</div>
+						)}
+						{query.trim() && !loading && !queryError && (
 							<div className="sessions-results-header">
 								{totalHits} result{totalHits !== 1 ? "s" : ""} in {queryTimeMs}ms
 							</div>